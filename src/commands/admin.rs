@@ -1,22 +1,42 @@
 use clap::Parser;
 use core::time;
 use poise::serenity_prelude::Attachment;
-use poise::serenity_prelude::{self as serenity, Activity, Member, OnlineStatus};
-use poise::serenity_prelude::{ChannelId, Colour};
-use rand::seq::SliceRandom;
-use rand::Rng;
-use rusted_fbt_lib::checks::guild_auth_check;
-use rusted_fbt_lib::structs::GuildSettings;
-use rusted_fbt_lib::utils::{auth, open_redis_connection, set_guild_settings};
+use poise::serenity_prelude::{self as serenity, Activity, Member, OnlineStatus, Role};
+use poise::serenity_prelude::{
+    colours, AttachmentType, CollectComponentInteraction, InteractionResponseType, UserId,
+};
+use rusted_fbt_lib::announcements::{
+    announcement_embed, claim_announcement, deliver_announcement, get_all_announcements,
+    get_announcement, lock_announcement, parse_send_time, release_announcement,
+    retract_announcement, save_announcement, update_delivered_announcement,
+};
+use rusted_fbt_lib::auth::{get_guild_grants, grant_access, revoke_access, role_grant, user_grant};
+use rusted_fbt_lib::checks::{guild_manager_check, guild_submitter_check, owner_check};
+use rusted_fbt_lib::enums::{AnnouncementStatus, AuthLevel, OnboardingStatus};
+use rusted_fbt_lib::onboarding::{
+    get_onboarding_request, post_onboarding_request, save_onboarding_request,
+};
+use rusted_fbt_lib::settings::{
+    apply_setting, parse_guild_settings, settings_components, settings_embed,
+};
+use rusted_fbt_lib::structs::{
+    Announcement, AnnouncementFailure, GuildSettings, Offense, OnboardingRequest,
+    GUILD_SETTINGS_VERSION,
+};
+use rusted_fbt_lib::utils::{
+    add_offence, bulk_ban, extract_csv_user_ids, extract_user_ids, get_guild_settings,
+    open_redis_connection, set_guild_settings, setup_guild_settings, truncate_text,
+};
+use rusted_fbt_lib::vars::{BULK_BAN_MAX_USERS, SETTINGS_TIMEOUT_SECS};
 use rusted_fbt_lib::{
     args::Args,
     checks::bot_admin_check,
     types::{Context, Error},
     utils::{inc_execution_count, verbose_mode},
 };
-use std::collections::HashSet;
 use std::ops::Add;
 use std::process::exit;
+use std::time::Duration;
 use tokio::time::sleep;
 use tracing::instrument;
 use tracing::{event, Level};
@@ -58,8 +78,8 @@ pub async fn botmsg(
 pub async fn ban_help(ctx: Context<'_>) -> Result<(), Error> {
     let args = Args::parse();
 
-    ctx.say("To ban a single user the easiest way is with the slash command `/ban ban_user @USER/ID` since you don't need to provide message deletion numbers or a reason.").await?;
-    ctx.say(format!("In order to ban multiple people please use this command as a a prefix command like so:\n```\n{}ban ban_user \"Reason in qutoation marks\" 0(A number from 0 to 7, how many days worth of messages you want to delet) userID1 userID2 userID3\n```", args.prefix)).await?;
+    ctx.say(format!("To ban a single user the easiest way is with the slash command `/ban ban_user users:@USER/ID` since you don't need to provide message deletion numbers or a reason.\nYou can give it as many IDs or mentions as you want, they don't have to be in the server, or attach a `.txt`/`.csv` file full of IDs with `id_file` (for a `.csv` only the `AuthorID`/`ID` column is read if it has one).\nSet `dry_run` to see who would be banned without banning anyone and `submit_to_db` to also add the reason to the DB.\nUp to {BULK_BAN_MAX_USERS} users can be banned at once.")).await?;
+    ctx.say(format!("In order to ban multiple people with the prefix command use it like so:\n```\n{}ban ban_user \"Reason in qutoation marks\" 0(A number from 0 to 7, how many days worth of messages you want to delet) userID1 userID2 userID3\n```", args.prefix)).await?;
 
     Ok(())
 }
@@ -74,7 +94,7 @@ pub async fn ban_help(ctx: Context<'_>) -> Result<(), Error> {
     guild_only,
     ephemeral
 )]
-/// Explains how to ban a list of users with `ban
+/// Ban a list of user IDs, they don't need to be in the server
 pub async fn ban_user(
     ctx: Context<'_>,
     #[description = "Ban reason"] reason: Option<String>,
@@ -82,96 +102,164 @@ pub async fn ban_user(
     #[min = 0]
     #[max = 7]
     dmd: Option<u8>,
-    #[description = "Member(s) to ban"] members: Vec<Member>,
+    #[description = "Only list who would be banned, without banning anyone"] dry_run: Option<bool>,
+    #[description = "Also add everyone banned to the DB with this reason"] submit_to_db: Option<
+        bool,
+    >,
+    #[description = "A .txt or .csv file of user IDs to ban"] id_file: Option<Attachment>,
+    #[description = "User ID(s) or mention(s) to ban"]
+    #[rest]
+    users: Option<String>,
 ) -> Result<(), Error> {
-    let delete_count: u8 = dmd.map_or(0u8, |num| {
-        let num_check = num;
-        if num_check.le(&7u8) {
-            num
-        } else {
-            7u8
-        }
-    });
+    ctx.defer_or_broadcast().await?;
 
-    let reason_sanitised = reason.map_or_else(|| "Banned via bot ban command".to_string(), |r| r);
+    let delete_count: u8 = dmd.map_or(0u8, |num| num.min(7u8));
 
-    // TODO: Change to your own emojis!
+    let reason_sanitised = reason.map_or_else(|| "Banned via bot ban command".to_string(), |r| r);
 
-    // Mojo test server emoji version
-    // let phrase_list = vec!["has been ejected", "banned quietly", "terminated", "thrown out", "<a:Banned1:1000474420864880831><a:Banned2:1000474423683452998><a:Banned3:1000474426447503441>"];
+    let mut ids = extract_user_ids(&users.unwrap_or_default());
 
-    // FBT Emoji version
-    let phrase_list = ["has been ejected", "banned quietly", "terminated", "thrown out", "<a:Banned1:1000474106929631433><a:Banned2:1000474109802725457><a:Banned3:1000474112734531715>"];
+    if let Some(file) = id_file {
+        let file_content = file.download().await?;
+        let text = String::from_utf8_lossy(&file_content);
 
-    match ctx.guild() {
-        Some(guild) => match members.len() {
-            0 => {
-                let args = Args::parse();
+        // Only the ID column of a CSV, the rest can be full of channel and message IDs
+        let file_ids = if file.filename.to_lowercase().ends_with(".csv") {
+            extract_csv_user_ids(&text).unwrap_or_else(|| extract_user_ids(&text))
+        } else {
+            extract_user_ids(&text)
+        };
 
-                ctx.say("You must provide at least one user to ban!")
-                    .await?;
-                ctx.say(format!("In order to ban multiple people please use this command as a a prefix command like so:\n```\n{}ban userID1 userID2 userID3\n```", args.prefix)).await?;
+        for id in file_ids {
+            if !ids.contains(&id) {
+                ids.push(id);
             }
-            1 => {
-                let member = guild.member(ctx, members[0].user.id).await?;
-                if let Err(error) = member
-                    .ban_with_reason(ctx, delete_count, reason_sanitised.clone())
-                    .await
-                {
-                    if verbose_mode() {
-                        ctx.say(format!(
-                            "Failed to ban {} because of {:?}",
-                            member.display_name(),
-                            error
-                        ))
-                        .await?;
-                    } else {
-                        ctx.say(format!("Failed to ban {}", member.display_name()))
-                            .await?;
-                    }
-                    // 0u8
+        }
+    }
+
+    if ids.is_empty() {
+        let args = Args::parse();
+
+        ctx.say("You must provide at least one user ID to ban!")
+            .await?;
+        ctx.say(format!("In order to ban multiple people please use this command as a a prefix command like so:\n```\n{}ban ban_user userID1 userID2 userID3\n```\nOr attach a file of IDs with the slash command.", args.prefix)).await?;
+
+        return Ok(());
+    }
+
+    let Some(guild) = ctx.guild() else {
+        ctx.say("This must be ran from inside a guild").await?;
+        return Ok(());
+    };
+
+    if dry_run.unwrap_or(false) {
+        let report = ids
+            .iter()
+            .map(|id| {
+                if guild.members.contains_key(&UserId(*id)) {
+                    format!("<@{id}>/{id} (In server)")
                 } else {
-                    let phrase = phrase_list
-                        .choose(&mut rand::thread_rng())
-                        .expect("Unable to get meme phrase for ban");
-                    ctx.say(format!("{} has been {phrase}", member.display_name()))
-                        .await?;
-                    // 0u8
-                };
-            }
-            _ => {
-                for member in members {
-                    if let Err(error) = member
-                        .ban_with_reason(ctx, delete_count, reason_sanitised.clone())
-                        .await
-                    {
-                        if verbose_mode() {
-                            ctx.say(format!(
-                                "Failed to ban {} because of {:?}",
-                                member.display_name(),
-                                error
-                            ))
-                            .await?;
-                        } else {
-                            ctx.say(format!("Failed to ban {}", member.display_name()))
-                                .await?;
-                        }
-                        // 0u8
-                    } else {
-                        let phrase = phrase_list
-                            .choose(&mut rand::thread_rng())
-                            .expect("Unable to get meme phrase for ban");
-                        ctx.say(format!("{} has been {phrase}", member.display_name()))
-                            .await?;
-                        // 0u8
-                    };
+                    format!("<@{id}>/{id} (Not in server)")
                 }
-            }
-        },
-        None => {
-            ctx.say("This must be ran from inside a guild").await?;
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let summary = format!(
+            "Dry run, nobody was banned.\n{} user(s) would be banned for: `{reason_sanitised}`",
+            ids.len()
+        );
+
+        if report.chars().count() > 4000 {
+            ctx.send(|b| {
+                b.embed(|e| {
+                    e.title("Ban preview")
+                        .description(format!("{summary}\nThe full list is in the attached file."))
+                        .color(colours::css::WARNING)
+                })
+                .attachment(AttachmentType::Bytes {
+                    data: std::borrow::Cow::Borrowed(report.as_bytes()),
+                    filename: format!("{}_ban_preview.txt", ctx.id()),
+                })
+            })
+            .await?;
+        } else {
+            ctx.send(|b| {
+                b.embed(|e| {
+                    e.title("Ban preview")
+                        .description(format!("{summary}\n\n{report}"))
+                        .color(colours::css::WARNING)
+                })
+            })
+            .await?;
         }
+
+        return Ok(());
+    }
+
+    if ids.len() > BULK_BAN_MAX_USERS {
+        ctx.say(format!(
+            "I can only ban {BULK_BAN_MAX_USERS} users at once so the results can still be sent when I'm done, you gave {}. Split the list up and run it again.",
+            ids.len()
+        ))
+        .await?;
+        return Ok(());
     }
+
+    // Check this before banning anyone so we don't half finish the job
+    let submit = submit_to_db.unwrap_or(false) && guild_submitter_check(ctx).await?;
+
+    let targets = ids
+        .iter()
+        .map(|id| (UserId(*id), reason_sanitised.clone()))
+        .collect();
+
+    let results = bulk_ban(ctx, guild.id, targets, delete_count).await;
+
+    let footer = if submit {
+        let mut con = open_redis_connection().await?;
+        let mut added: u64 = 0;
+
+        for (user_id, _) in results.iter().filter(|(_, result)| result.is_ok()) {
+            let uname = (user_id.to_user(ctx).await)
+                .map_or_else(|_| "NoUsernameFoundInDB".to_string(), |u| u.tag());
+
+            let offense = Offense {
+                guild_id: guild.id.to_string(),
+                reason: reason_sanitised.clone(),
+                image: None,
+                extra: None,
+                category: None,
+                provenance: None,
+                evidence: Vec::new(),
+                expires_at: None,
+                added_at: None,
+            };
+
+            match add_offence(
+                &mut con,
+                user_id.0,
+                uname,
+                None,
+                offense,
+                &ctx.author().id.to_string(),
+            )
+            .await
+            {
+                Ok(()) => added = added.add(1),
+                Err(error) => {
+                    event!(Level::WARN, "Failed to add banned user to DB" = ?user_id, error = ?error);
+                }
+            }
+        }
+
+        Some(format!("Added {added} banned user(s) to the DB"))
+    } else {
+        None
+    };
+
+    send_ban_report(ctx, &results, footer).await?;
+
     Ok(())
 }
 
@@ -193,9 +281,80 @@ pub async fn ban(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// Replies with one embed covering every ban in `results`, moving the list into a `.txt` file if it won't fit
+pub async fn send_ban_report(
+    ctx: Context<'_>,
+    results: &[(UserId, Result<(), serenity::Error>)],
+    footer: Option<String>,
+) -> Result<(), Error> {
+    let banned = results.iter().filter(|(_, result)| result.is_ok()).count();
+    let failed = results.len() - banned;
+
+    let report = results
+        .iter()
+        .map(|(id, result)| match result {
+            Ok(()) => format!("✅ <@{id}>/{id}"),
+            Err(error) if verbose_mode() => format!("❌ <@{id}>/{id}: {error:?}"),
+            Err(error) => format!("❌ <@{id}>/{id}: {error}"),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let summary = format!(
+        "Banned {banned}/{} user(s), {failed} failed.",
+        results.len()
+    );
+
+    let colour = if failed == 0 {
+        colours::css::POSITIVE
+    } else {
+        colours::css::WARNING
+    };
+
+    // Embed descriptions cap out at 4096 characters
+    if report.chars().count() > 4000 {
+        ctx.send(|b| {
+            b.embed(|e| {
+                e.title("Ban results")
+                    .description(format!("{summary}\nThe full list is in the attached file."))
+                    .color(colour);
+                if let Some(text) = &footer {
+                    e.footer(|f| f.text(text));
+                }
+                e
+            })
+            .attachment(AttachmentType::Bytes {
+                data: std::borrow::Cow::Borrowed(report.as_bytes()),
+                filename: format!("{}_ban_results.txt", ctx.id()),
+            })
+        })
+        .await?;
+    } else {
+        ctx.send(|b| {
+            b.embed(|e| {
+                e.title("Ban results")
+                    .description(format!("{summary}\n\n{report}"))
+                    .color(colour);
+                if let Some(text) = &footer {
+                    e.footer(|f| f.text(text));
+                }
+                e
+            })
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
 // TODO: Change to your own emojis!
 #[instrument(skip(ctx))]
-#[poise::command(prefix_command, slash_command, category = "Admin", owners_only)]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Admin",
+    check = "owner_check"
+)]
 /// Literally just shoot the bot!
 pub async fn shutdown(ctx: Context<'_>) -> Result<(), Error> {
     let pewpew = ctx
@@ -235,154 +394,119 @@ pub async fn shutdown(ctx: Context<'_>) -> Result<(), Error> {
     exit(0)
 }
 
+/// Picks the user or role a command was given, they can't both be left empty or both be filled in
+#[cfg(feature = "database")]
+fn grant_target(user: Option<&serenity::User>, role: Option<&Role>) -> Option<(String, String)> {
+    match (user, role) {
+        (Some(user), None) => Some((user_grant(user.id.0), user.tag())),
+        (None, Some(role)) => Some((role_grant(role.id.0), role.name.clone())),
+        _ => None,
+    }
+}
+
+/// Shows a `guild-auth:` field as a mention
+#[cfg(feature = "database")]
+fn describe_grant(grant: &str) -> String {
+    match grant.split_once(':') {
+        Some(("role", id)) => format!("<@&{id}>"),
+        Some((_, id)) => format!("<@{id}>"),
+        None => grant.to_string(),
+    }
+}
+
 #[cfg(feature = "database")]
-/// Authorize someone in this guild
+/// Authorize a user or role in this guild
 #[instrument(skip(ctx))]
 #[poise::command(
     prefix_command,
     slash_command,
     category = "Admin",
-    check = "guild_auth_check",
+    check = "guild_manager_check",
     guild_only
 )]
 pub async fn authorize(
     ctx: Context<'_>,
-    #[description = "User to authorise in this server"] user: Member,
+    #[description = "User to authorise in this server"] user: Option<Member>,
+    #[description = "Role to authorise in this server"] role: Option<Role>,
+    #[description = "What they can do, defaults to Viewer"] level: Option<AuthLevel>,
 ) -> Result<(), Error> {
     ctx.defer_or_broadcast().await?;
 
-    let uid = format!("{}", user.user.id.as_u64());
+    let Some((grant, name)) = grant_target(user.as_ref().map(|m| &m.user), role.as_ref()) else {
+        ctx.say("Pick either a user or a role to authorize").await?;
+        return Ok(());
+    };
+
+    let level = level.unwrap_or(AuthLevel::Viewer);
+    let guild_id = *ctx.guild_id().unwrap().as_u64();
 
     let mut con = open_redis_connection().await?;
 
-    // * json format: {users:[ID1, ID2, IDect]}
-    let key_list: Option<HashSet<String>> = redis::cmd("SMEMBERS")
-        .arg(format!(
-            "authed-server-users:{}",
-            ctx.guild_id().unwrap().as_u64()
+    if get_guild_grants(&mut con, guild_id).await?.get(&grant) == Some(&level) {
+        ctx.say(format!(
+            "{name} is already authorised as {level} in this server!"
         ))
-        .clone()
-        .query_async(&mut con)
         .await?;
+        return Ok(());
+    }
 
-    if let Some(list) = key_list {
-        if list.contains(&uid) {
-            ctx.say("User already authorised in this server!").await?;
-        } else {
-            match auth(ctx, &mut con, uid).await {
-                Ok(()) => {
-                    ctx.say(format!(
-                        "{} is now authorized to use commands in this server!",
-                        user.display_name()
-                    ))
-                    .await?;
-                }
-                Err(_error) if !verbose_mode() => {
-                    ctx.say(format!("Failed to auth {}!", user.display_name()))
-                        .await?;
-                }
-                Err(error) => {
-                    ctx.say(format!(
-                        "Failed to auth {}! Caused by {:?}",
-                        user.display_name(),
-                        error
-                    ))
-                    .await?;
-                }
-            }
+    match grant_access(&mut con, guild_id, &grant, level).await {
+        Ok(()) => {
+            ctx.say(format!(
+                "{name} is now authorized as {level} in this server!"
+            ))
+            .await?;
+        }
+        Err(_error) if !verbose_mode() => {
+            ctx.say(format!("Failed to auth {name}!")).await?;
+        }
+        Err(error) => {
+            ctx.say(format!("Failed to auth {name}! Caused by {error:?}"))
+                .await?;
         }
-    } else {
-        auth(ctx, &mut con, uid).await?;
-
-        ctx.say(format!(
-            "{} is now authorized to use commands in this server!",
-            user.display_name()
-        ))
-        .await?;
     }
 
     Ok(())
 }
 
 #[cfg(feature = "database")]
+/// Take away a user or role's access in this guild
 #[instrument(skip(ctx))]
 #[poise::command(
     prefix_command,
     slash_command,
     category = "Admin",
-    check = "bot_admin_check"
+    check = "guild_manager_check",
+    guild_only
 )]
-/// Send annoucement to any server that has been setup
-pub async fn announcement(
+pub async fn deauthorize(
     ctx: Context<'_>,
-    #[description = "Title of announcement embed"] title: String,
-    #[description = "Message to send to all servers (As a .txt file!)"] message_file: Attachment,
+    #[description = "User to deauthorise in this server"] user: Option<serenity::User>,
+    #[description = "Role to deauthorise in this server"] role: Option<Role>,
 ) -> Result<(), Error> {
-    ctx.defer_or_broadcast().await?;
-
-    let message_content = message_file.download().await?;
-    let message = std::str::from_utf8(&message_content)?;
+    let Some((grant, name)) = grant_target(user.as_ref(), role.as_ref()) else {
+        ctx.say("Pick either a user or a role to deauthorize")
+            .await?;
+        return Ok(());
+    };
 
     let mut con = open_redis_connection().await?;
 
-    let key_list: Vec<String> = redis::cmd("KEYS")
-        .arg("guild-settings:*")
-        .clone()
-        .query_async(&mut con)
-        .await?;
-
-    let mut key_pipe = redis::pipe();
-
-    for key in key_list {
-        key_pipe.cmd("JSON.GET").arg(key);
-    }
-
-    let setting_entries: Vec<String> = key_pipe.atomic().query_async(&mut con).await?;
-
-    let mut guild_settings_collection = Vec::new();
-    for settings in setting_entries {
-        let gs: GuildSettings = serde_json::from_str(&settings)?;
-
-        guild_settings_collection.push(gs);
-    }
+    if revoke_access(&mut con, *ctx.guild_id().unwrap().as_u64(), &grant).await? {
+        event!(
+            Level::INFO,
+            "Access revoked" = grant,
+            "Guild" = ctx.guild_id().unwrap().as_u64(),
+            "By" = ctx.author().id.as_u64()
+        );
 
-    // TODO: Change to custom announcement message!
-
-    let mut count: u64 = 0;
-    for guild in guild_settings_collection.clone() {
-        let colour = &mut rand::thread_rng().gen_range(0..10_000_000);
-        match ChannelId(guild.channel_id.parse::<u64>()?).send_message(ctx, |f| {
-            f.embed(|e| {
-                e.title(format!("New announcement from FBT Security: {}", title.clone()))
-                .description(message)
-                .color(Colour::new(*colour))
-                .author(|a| {
-                    a.icon_url("https://cdn.discordapp.com/avatars/743269383438073856/959512463b1559b14818590d8c8a9d2a.webp?size=4096")
-                    .name("FBT Security")
-                })
-                .thumbnail("https://media.giphy.com/media/U4sfHXAALLYBQzPcWk/giphy.gif")
-            })
-        }).await {
-            Err(e)=>{
-                event!(
-                    Level::INFO,
-                    "Failed to send announcement to a server because of" = ?e
-                );
-            },
-            Ok(msg) => {
-                count = count.add(1);
-                println!("Sent to: {}", msg.link());
-            },
-        };
+        ctx.say(format!("{name} is no longer authorized in this server."))
+            .await?;
+    } else {
+        ctx.say(format!("{name} wasn't authorized in this server."))
+            .await?;
     }
 
-    ctx.say(format!(
-        "Sent annoucement to {}/{} servers!",
-        count,
-        guild_settings_collection.len()
-    ))
-    .await?;
-
     Ok(())
 }
 
@@ -392,80 +516,193 @@ pub async fn announcement(
     prefix_command,
     slash_command,
     category = "Admin",
-    required_permissions = "ADMINISTRATOR",
-    guild_only
+    check = "guild_manager_check",
+    guild_only,
+    subcommands("auth_list")
 )]
-/// Request an FBT staff member to come and auth your server
-pub async fn request_setup(
-    ctx: Context<'_>,
-    #[description = "Do you want to kick accounts that are under 90 days old"] alt_protection: bool,
-) -> Result<(), Error> {
-    ctx.defer().await?;
-    let link = ChannelId(*ctx.channel_id().as_u64())
-        .create_invite(ctx, |f| f.temporary(false).max_age(0).unique(false))
+/// See who's authorized in this server
+pub async fn auth(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Use `auth list` to see who's authorized, `authorize` and `deauthorize` change it.")
         .await?;
 
-
-    // TODO: this channel is where the bot alterts you when a server is requesting use of the bot's moderation stuff
-    ChannelId(953_435_498_318_286_898).send_message(ctx, |f| {
-        f.content(format!("{0} is requesting authentication! {1}\n They requested for alt protection to be: `{alt_protection}`", ctx.guild().unwrap().name, link.url()))
-    }).await?;
-
-    ctx.send(|b| b.content("Request sent, sit tight!\nOnce an administrator joins make sure to give them permissions to acess the channel so they can set it up!").ephemeral(true)).await?;
-
     Ok(())
 }
 
 #[cfg(feature = "database")]
 #[instrument(skip(ctx))]
 #[poise::command(
+    prefix_command,
     slash_command,
+    rename = "list",
     category = "Admin",
-    check = "guild_auth_check",
+    check = "guild_manager_check",
     guild_only
 )]
-/// Setup your server's settings
-pub async fn setup(
+/// List every user and role authorized in this server and their level
+pub async fn auth_list(ctx: Context<'_>) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let mut grants: Vec<(String, AuthLevel)> =
+        get_guild_grants(&mut con, *ctx.guild_id().unwrap().as_u64())
+            .await?
+            .into_iter()
+            .collect();
+
+    if grants.is_empty() {
+        ctx.say("Nobody is authorized in this server yet.").await?;
+        return Ok(());
+    }
+
+    grants.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let lines = grants
+        .iter()
+        .map(|(grant, level)| format!("{level}: {}", describe_grant(grant)))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title("Authorized in this server")
+                .description(truncate_text(&lines, 4096))
+                .footer(|f| {
+                    f.text("Viewers can search, submitters can also add users and managers can also change who's authorized and the server's settings")
+                })
+        })
+        .allowed_mentions(|m| m.empty_parse())
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Replies with how many servers an announcement action worked in and lists any that failed, moving the list into a `.txt` file if it won't fit
+#[cfg(feature = "database")]
+async fn send_delivery_report(
     ctx: Context<'_>,
-    #[description = "Do you want to kick accounts that are under 90 days old when they join"]
-    alt_protection: bool,
+    summary: String,
+    failures: &[AnnouncementFailure],
 ) -> Result<(), Error> {
-    let mut con = open_redis_connection().await?;
+    let report = failures
+        .iter()
+        .map(|f| format!("`{}` ({}): {}", f.server_name, f.guild_id, f.reason))
+        .collect::<Vec<String>>()
+        .join("\n");
 
-    let guild_settings_json_in: Option<String> = redis::cmd("JSON.GET")
-        .arg(format!(
-            "guild-settings:{}",
-            ctx.guild_id().unwrap().as_u64()
-        ))
-        .clone()
-        .query_async(&mut con)
+    let colour = if failures.is_empty() {
+        colours::css::POSITIVE
+    } else {
+        colours::css::WARNING
+    };
+
+    if report.chars().count() > 4000 {
+        ctx.send(|b| {
+            b.embed(|e| {
+                e.title("Delivery report")
+                    .description(format!(
+                        "{summary}\nThe servers that failed are in the attached file."
+                    ))
+                    .color(colour)
+            })
+            .attachment(AttachmentType::Bytes {
+                data: std::borrow::Cow::Borrowed(report.as_bytes()),
+                filename: format!("{}_failed_servers.txt", ctx.id()),
+            })
+        })
+        .await?;
+    } else {
+        ctx.send(|b| {
+            b.embed(|e| {
+                e.title("Delivery report").color(colour);
+                if report.is_empty() {
+                    e.description(summary.clone())
+                } else {
+                    e.description(format!("{summary}\n\nFailed servers:\n{report}"))
+                }
+            })
+        })
         .await?;
+    }
 
-    let ch_id = format!("{}", ctx.channel_id().as_u64());
-    let g_name = ctx
-        .partial_guild()
-        .await
-        .expect("Unable to get Guild info")
-        .name;
+    Ok(())
+}
+
+/// Loads an announcement or tells the user it doesn't exist
+#[cfg(feature = "database")]
+async fn find_announcement(
+    ctx: Context<'_>,
+    con: &mut redis::aio::MultiplexedConnection,
+    id: u64,
+) -> Result<Option<Announcement>, Error> {
+    let announcement = get_announcement(con, id).await?;
 
-    if let Some(json_in) = guild_settings_json_in {
-        let mut settings: GuildSettings = serde_json::from_str(&json_in)?;
-        settings.channel_id = ch_id.clone();
-        settings.kick = alt_protection;
-        settings.server_name = g_name;
+    if announcement.is_none() {
+        ctx.say(format!("There is no announcement #{id}.")).await?;
+    }
 
-        set_guild_settings(ctx, &mut con, settings).await?;
-        ctx.say(format!("Settings have been updated for your server!\nChannel for kick messages and bot announcements: <#{0}>.\nAlt protection: {alt_protection:?}.", ch_id.clone())).await?;
-    } else {
-        let settings = GuildSettings {
-            channel_id: ch_id.clone(),
-            kick: alt_protection,
-            server_name: g_name,
-        };
+    Ok(announcement)
+}
 
-        set_guild_settings(ctx, &mut con, settings).await?;
-        ctx.say(format!("Settings have been created for your server!\nChannel for kick messages and bot announcements: <#{0}>.\nAlt protection: {alt_protection:?}.", ch_id.clone())).await?;
+/// Locks an announcement before it's scheduled, edited or retracted so it can't change while it's being sent
+///
+/// Tells the user and returns `false` if it's locked somewhere else, call [`release_announcement`] once done.
+#[cfg(feature = "database")]
+async fn lock_for_change(
+    ctx: Context<'_>,
+    con: &mut redis::aio::MultiplexedConnection,
+    id: u64,
+) -> Result<bool, Error> {
+    let locked = lock_announcement(con, id).await?;
+
+    if !locked {
+        ctx.say(format!(
+            "Announcement #{id} is being sent or changed right now, try again once that's done."
+        ))
+        .await?;
+    }
+
+    Ok(locked)
+}
+
+/// Shows an announcement the way it would look if sent to the current server
+#[cfg(feature = "database")]
+async fn show_preview(
+    ctx: Context<'_>,
+    con: &mut redis::aio::MultiplexedConnection,
+    announcement: &Announcement,
+) -> Result<(), Error> {
+    let settings = match ctx.guild_id() {
+        Some(gid) => get_guild_settings(con, *gid.as_u64()).await?,
+        None => None,
     }
+    .unwrap_or_else(|| GuildSettings {
+        version: GUILD_SETTINGS_VERSION,
+        channel_id: ctx.channel_id().to_string(),
+        kick: false,
+        server_name: ctx
+            .guild()
+            .map_or_else(|| "Example server".to_string(), |g| g.name),
+        log_channel_id: None,
+        announcements_opt_out: false,
+        alert_min_score: 0,
+        kick_min_score: None,
+    });
+
+    let status = match announcement.send_at {
+        Some(send_at) if announcement.status == AnnouncementStatus::Scheduled => {
+            format!("{} for <t:{send_at}:f>", announcement.status)
+        }
+        _ => announcement.status.to_string(),
+    };
+
+    ctx.send(|b| {
+        b.content(format!(
+            "Preview of announcement #{} ({status}):",
+            announcement.id
+        ))
+        .embed(|e| announcement_embed(e, announcement, &settings))
+    })
+    .await?;
 
     Ok(())
 }
@@ -476,26 +713,537 @@ pub async fn setup(
     prefix_command,
     slash_command,
     category = "Admin",
-    check = "guild_auth_check",
-    guild_only
+    check = "bot_admin_check",
+    subcommands(
+        "announcement_draft",
+        "announcement_preview",
+        "announcement_send",
+        "announcement_schedule",
+        "announcement_edit",
+        "announcement_retract",
+        "announcement_list"
+    )
 )]
-/// Set your server's alt protection policy
-pub async fn toggle_kick(ctx: Context<'_>) -> Result<(), Error> {
-    let mut con = open_redis_connection().await?;
-
-    let guild_settings_json_in: Option<String> = redis::cmd("JSON.GET")
-        .arg(format!(
-            "guild-settings:{}",
-            ctx.guild_id().unwrap().as_u64()
-        ))
-        .clone()
-        .query_async(&mut con)
+/// Write, schedule and send annoucements to any server that has been setup
+pub async fn announcement(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Start with `/announcement draft`, check it with `/announcement preview` then use `/announcement send` or `/announcement schedule`.\nAnnouncement text can use `{server_name}` and `{channel}` which get filled in for each server.")
         .await?;
 
-    match guild_settings_json_in {
+    Ok(())
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "draft",
+    category = "Admin",
+    check = "bot_admin_check"
+)]
+/// Save a new announcement as a draft and preview it
+pub async fn announcement_draft(
+    ctx: Context<'_>,
+    #[description = "Title of announcement embed"] title: String,
+    #[description = "Message to send to all servers (As a .txt file!)"] message_file: Attachment,
+    #[description = "Image URL for the embed thumbnail (Leave blank for the default)"]
+    thumbnail: Option<String>,
+    #[description = "Put the usual announcement prefix before the title (Default: true)"]
+    title_prefix: Option<bool>,
+) -> Result<(), Error> {
+    ctx.defer_or_broadcast().await?;
+
+    let message_content = message_file.download().await?;
+    let message = std::str::from_utf8(&message_content)?;
+
+    let mut con = open_redis_connection().await?;
+
+    let id: u64 = redis::cmd("INCR")
+        .arg("announcement-count")
+        .query_async(&mut con)
+        .await?;
+
+    let announcement = Announcement {
+        id,
+        title,
+        body: message.to_string(),
+        use_title_prefix: title_prefix.unwrap_or(true),
+        thumbnail,
+        author_id: ctx.author().id.to_string(),
+        created_at: chrono::Utc::now().timestamp(),
+        send_at: None,
+        status: AnnouncementStatus::Draft,
+        deliveries: Vec::new(),
+        failures: Vec::new(),
+    };
+
+    save_announcement(&mut con, &announcement).await?;
+
+    show_preview(ctx, &mut con, &announcement).await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "preview",
+    category = "Admin",
+    check = "bot_admin_check"
+)]
+/// Preview an announcement as it would look in this server
+pub async fn announcement_preview(
+    ctx: Context<'_>,
+    #[description = "Announcement number"] id: u64,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    if let Some(announcement) = find_announcement(ctx, &mut con, id).await? {
+        show_preview(ctx, &mut con, &announcement).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "send",
+    category = "Admin",
+    check = "bot_admin_check"
+)]
+/// Send an announcement to every server right now
+pub async fn announcement_send(
+    ctx: Context<'_>,
+    #[description = "Announcement number"] id: u64,
+) -> Result<(), Error> {
+    ctx.defer_or_broadcast().await?;
+
+    let mut con = open_redis_connection().await?;
+
+    if find_announcement(ctx, &mut con, id).await?.is_none() {
+        return Ok(());
+    }
+
+    let Some(mut announcement) = claim_announcement(&mut con, id).await? else {
+        ctx.say(format!(
+            "Announcement #{id} has already been sent or is being sent right now, use `/announcement edit` to change it."
+        ))
+        .await?;
+        return Ok(());
+    };
+
+    let delivered =
+        deliver_announcement(&ctx.serenity_context().http, &mut con, &mut announcement).await;
+    release_announcement(&mut con, id).await?;
+    delivered?;
+
+    let summary = format!(
+        "Sent annoucement #{id} to {}/{} servers!",
+        announcement.deliveries.len(),
+        announcement.deliveries.len() + announcement.failures.len()
+    );
+
+    send_delivery_report(ctx, summary, &announcement.failures).await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "schedule",
+    category = "Admin",
+    check = "bot_admin_check"
+)]
+/// Schedule an announcement to be sent later
+pub async fn announcement_schedule(
+    ctx: Context<'_>,
+    #[description = "Announcement number"] id: u64,
+    #[description = "When to send it, like 2h, a unix timestamp or YYYY-MM-DD HH:MM (UTC)"]
+    #[rest]
+    when: String,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    if !lock_for_change(ctx, &mut con, id).await? {
+        return Ok(());
+    }
+
+    let scheduled = async {
+        let Some(mut announcement) = find_announcement(ctx, &mut con, id).await? else {
+            return Ok(());
+        };
+
+        if matches!(
+            announcement.status,
+            AnnouncementStatus::Sent | AnnouncementStatus::Retracted
+        ) {
+            ctx.say(format!("Announcement #{id} has already been sent!"))
+                .await?;
+            return Ok(());
+        }
+
+        let Some(send_at) = parse_send_time(&when, chrono::Utc::now().timestamp()) else {
+            ctx.say("I couldn't understand that time or it's in the past, try something like `2h`, a unix timestamp or `2024-01-31 18:00` (UTC).")
+                .await?;
+            return Ok(());
+        };
+
+        announcement.send_at = Some(send_at);
+        announcement.status = AnnouncementStatus::Scheduled;
+
+        save_announcement(&mut con, &announcement).await?;
+
+        ctx.say(format!(
+            "Announcement #{id} will be sent <t:{send_at}:R> (<t:{send_at}:f>)."
+        ))
+        .await?;
+
+        Ok::<(), Error>(())
+    }
+    .await;
+
+    release_announcement(&mut con, id).await?;
+    scheduled
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "edit",
+    category = "Admin",
+    check = "bot_admin_check"
+)]
+/// Change an announcement, if it's already been sent every server's copy gets edited too
+pub async fn announcement_edit(
+    ctx: Context<'_>,
+    #[description = "Announcement number"] id: u64,
+    #[description = "New title"] title: Option<String>,
+    #[description = "New message (As a .txt file!)"] message_file: Option<Attachment>,
+    #[description = "New thumbnail image URL"] thumbnail: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer_or_broadcast().await?;
+
+    // Downloaded before locking so a slow download can't hold up the scheduler
+    let body = match message_file {
+        Some(file) => Some(std::str::from_utf8(&file.download().await?)?.to_string()),
+        None => None,
+    };
+
+    let mut con = open_redis_connection().await?;
+
+    if !lock_for_change(ctx, &mut con, id).await? {
+        return Ok(());
+    }
+
+    let edited = async {
+        let Some(mut announcement) = find_announcement(ctx, &mut con, id).await? else {
+            return Ok(());
+        };
+
+        if announcement.status == AnnouncementStatus::Retracted {
+            ctx.say(format!(
+                "Announcement #{id} has been retracted and can't be edited."
+            ))
+            .await?;
+            return Ok(());
+        }
+
+        if let Some(new_title) = title {
+            announcement.title = new_title;
+        }
+
+        if let Some(body) = body {
+            announcement.body = body;
+        }
+
+        if thumbnail.is_some() {
+            announcement.thumbnail = thumbnail;
+        }
+
+        save_announcement(&mut con, &announcement).await?;
+
+        if announcement.status == AnnouncementStatus::Sent {
+            let failures = update_delivered_announcement(
+                &ctx.serenity_context().http,
+                &mut con,
+                &announcement,
+            )
+            .await?;
+
+            let summary = format!(
+                "Edited announcement #{id} in {}/{} servers!",
+                announcement.deliveries.len() - failures.len(),
+                announcement.deliveries.len()
+            );
+
+            send_delivery_report(ctx, summary, &failures).await?;
+        } else {
+            show_preview(ctx, &mut con, &announcement).await?;
+        }
+
+        Ok::<(), Error>(())
+    }
+    .await;
+
+    release_announcement(&mut con, id).await?;
+    edited
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "retract",
+    category = "Admin",
+    check = "bot_admin_check"
+)]
+/// Delete a sent announcement from every server, or cancel one that hasn't been sent yet
+pub async fn announcement_retract(
+    ctx: Context<'_>,
+    #[description = "Announcement number"] id: u64,
+) -> Result<(), Error> {
+    ctx.defer_or_broadcast().await?;
+
+    let mut con = open_redis_connection().await?;
+
+    if !lock_for_change(ctx, &mut con, id).await? {
+        return Ok(());
+    }
+
+    let retracted = async {
+        let Some(mut announcement) = find_announcement(ctx, &mut con, id).await? else {
+            return Ok(());
+        };
+
+        match announcement.status {
+            AnnouncementStatus::Retracted => {
+                ctx.say(format!("Announcement #{id} has already been retracted."))
+                    .await?;
+            }
+            AnnouncementStatus::Draft | AnnouncementStatus::Scheduled => {
+                announcement.status = AnnouncementStatus::Retracted;
+                save_announcement(&mut con, &announcement).await?;
+
+                ctx.say(format!(
+                    "Announcement #{id} was never sent, it has been cancelled."
+                ))
+                .await?;
+            }
+            AnnouncementStatus::Sent => {
+                let failures =
+                    retract_announcement(&ctx.serenity_context().http, &mut con, &mut announcement)
+                        .await?;
+
+                let summary = format!(
+                    "Deleted announcement #{id} from {}/{} servers!",
+                    announcement.deliveries.len() - failures.len(),
+                    announcement.deliveries.len()
+                );
+
+                send_delivery_report(ctx, summary, &failures).await?;
+            }
+        }
+
+        Ok::<(), Error>(())
+    }
+    .await;
+
+    release_announcement(&mut con, id).await?;
+    retracted
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "list",
+    category = "Admin",
+    check = "bot_admin_check"
+)]
+/// List the most recent announcements and where they're at
+pub async fn announcement_list(ctx: Context<'_>) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let announcements = get_all_announcements(&mut con).await?;
+
+    if announcements.is_empty() {
+        ctx.say("There are no announcements yet.").await?;
+        return Ok(());
+    }
+
+    let lines = announcements
+        .iter()
+        .rev()
+        .take(20)
+        .map(|a| match (a.status, a.send_at) {
+            (AnnouncementStatus::Scheduled, Some(send_at)) => {
+                format!("#{} `{}`: Scheduled for <t:{send_at}:f>", a.id, a.title)
+            }
+            (AnnouncementStatus::Sent, _) => format!(
+                "#{} `{}`: Sent to {} server(s), {} failed",
+                a.id,
+                a.title,
+                a.deliveries.len(),
+                a.failures.len()
+            ),
+            (status, _) => format!("#{} `{}`: {status}", a.id, a.title),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title("Announcements")
+                .description(lines)
+                .color(colours::css::POSITIVE)
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Admin",
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+/// Ask FBT staff to set your server up, kick messages and announcements will go to this channel
+pub async fn request_setup(
+    ctx: Context<'_>,
+    #[description = "Do you want to kick accounts that are under 90 days old"] alt_protection: bool,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    let mut con = open_redis_connection().await?;
+
+    let guild_id = ctx.guild_id().unwrap();
+
+    if get_onboarding_request(&mut con, *guild_id.as_u64())
+        .await?
+        .is_some_and(|request| request.status == OnboardingStatus::Pending)
+    {
+        ctx.send(|b| {
+            b.content("This server already has a request waiting, sit tight!")
+                .ephemeral(true)
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let guild = ctx.guild().expect("Unable to get Guild info");
+
+    let request = OnboardingRequest {
+        guild_id: guild_id.to_string(),
+        guild_name: guild.name.clone(),
+        owner_id: guild.owner_id.to_string(),
+        member_count: guild.member_count,
+        guild_created_at: guild_id.created_at().unix_timestamp(),
+        requested_by: ctx.author().id.to_string(),
+        channel_id: ctx.channel_id().to_string(),
+        alt_protection,
+        requested_at: chrono::Utc::now().timestamp(),
+        status: OnboardingStatus::Pending,
+        reviewed_by: None,
+    };
+
+    save_onboarding_request(&mut con, &request).await?;
+    post_onboarding_request(&ctx.serenity_context().http, &request).await?;
+
+    event!(
+        Level::INFO,
+        "Onboarding requested" = guild_id.as_u64(),
+        "By" = ctx.author().id.as_u64()
+    );
+
+    ctx.send(|b| b.content("Request sent, sit tight!\nYou'll get a message in this channel once staff have looked at it, if it's approved you'll be able to manage the bot here.").ephemeral(true)).await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    category = "Admin",
+    check = "guild_manager_check",
+    guild_only
+)]
+/// Setup your server's settings
+pub async fn setup(
+    ctx: Context<'_>,
+    #[description = "Do you want to kick accounts that are under 90 days old when they join"]
+    alt_protection: bool,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let ch_id = format!("{}", ctx.channel_id().as_u64());
+    let g_name = ctx
+        .partial_guild()
+        .await
+        .expect("Unable to get Guild info")
+        .name;
+
+    let created = setup_guild_settings(
+        &mut con,
+        *ctx.guild_id().unwrap().as_u64(),
+        ch_id.clone(),
+        g_name,
+        alt_protection,
+    )
+    .await?;
+
+    if created {
+        ctx.say(format!("Settings have been created for your server!\nChannel for kick messages and bot announcements: <#{0}>.\nAlt protection: {alt_protection:?}.", ch_id.clone())).await?;
+    } else {
+        ctx.say(format!("Settings have been updated for your server!\nChannel for kick messages and bot announcements: <#{0}>.\nAlt protection: {alt_protection:?}.", ch_id.clone())).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Admin",
+    check = "guild_manager_check",
+    guild_only
+)]
+/// Set your server's alt protection policy
+pub async fn toggle_kick(ctx: Context<'_>) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let guild_settings_json_in: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!(
+            "guild-settings:{}",
+            ctx.guild_id().unwrap().as_u64()
+        ))
+        .clone()
+        .query_async(&mut con)
+        .await?;
+
+    match guild_settings_json_in {
         // Update settings
         Some(json_in) => {
-            let mut settings: GuildSettings = serde_json::from_str(&json_in)?;
+            let (mut settings, _) = parse_guild_settings(&json_in)?;
             settings.kick = !settings.kick;
 
             set_guild_settings(ctx, &mut con, settings.clone()).await?;
@@ -514,3 +1262,185 @@ pub async fn toggle_kick(ctx: Context<'_>) -> Result<(), Error> {
 
     Ok(())
 }
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Admin",
+    check = "guild_manager_check",
+    guild_only
+)]
+/// Turn bot announcements on or off for your server
+pub async fn toggle_announcements(ctx: Context<'_>) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    match get_guild_settings(&mut con, *ctx.guild_id().unwrap().as_u64()).await? {
+        Some(mut settings) => {
+            settings.announcements_opt_out = !settings.announcements_opt_out;
+
+            set_guild_settings(ctx, &mut con, settings.clone()).await?;
+            ctx.say(format!(
+                "Settings have been updated for your server!\nReceive bot announcements: {:?}.",
+                !settings.announcements_opt_out
+            ))
+            .await?;
+        }
+        None => {
+            ctx.say("Your server has not been setup by a bot admin yet! Please context a bot admin or azuki to get authorised.").await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Admin",
+    check = "guild_manager_check",
+    guild_only
+)]
+/// Set which risk scores your server gets warned about or kicks when they join
+pub async fn risk_policy(
+    ctx: Context<'_>,
+    #[description = "Lowest risk score to warn about, 0 warns about everyone in the DB"]
+    alert_min_score: Option<u32>,
+    #[description = "Risk score to kick blacklisted users at as they join"] kick_min_score: Option<
+        u32,
+    >,
+    #[description = "Stop kicking blacklisted users by risk score"] disable_kick: Option<bool>,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    match get_guild_settings(&mut con, *ctx.guild_id().unwrap().as_u64()).await? {
+        Some(mut settings) => {
+            if let Some(alert_min_score) = alert_min_score {
+                settings.alert_min_score = alert_min_score;
+            }
+
+            if disable_kick.unwrap_or(false) {
+                settings.kick_min_score = None;
+            } else if kick_min_score.is_some() {
+                settings.kick_min_score = kick_min_score;
+            }
+
+            set_guild_settings(ctx, &mut con, settings.clone()).await?;
+            ctx.say(format!(
+                "Settings have been updated for your server!\nWarn about risk scores of: {} or more\nKick at a risk score of: {}",
+                settings.alert_min_score,
+                settings
+                    .kick_min_score
+                    .map_or_else(|| "Never".to_string(), |score| score.to_string())
+            ))
+            .await?;
+        }
+        None => {
+            ctx.say("Your server has not been setup by a bot admin yet! Please context a bot admin or azuki to get authorised.").await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    category = "Admin",
+    check = "guild_manager_check",
+    guild_only
+)]
+/// See and change all of your server's settings in one place
+pub async fn settings(ctx: Context<'_>) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let Some(mut settings) =
+        get_guild_settings(&mut con, *ctx.guild_id().unwrap().as_u64()).await?
+    else {
+        ctx.say("Your server has not been setup yet! Run `/setup` or `/request_setup` first.")
+            .await?;
+        return Ok(());
+    };
+
+    let mut channels: Vec<(i64, u64, String)> = ctx
+        .guild()
+        .expect("Unable to get Guild info")
+        .channels
+        .into_values()
+        .filter_map(|channel| match channel {
+            serenity::Channel::Guild(c) if c.kind == serenity::ChannelType::Text => {
+                Some((c.position, *c.id.as_u64(), c.name))
+            }
+            _ => None,
+        })
+        .collect();
+    channels.sort_unstable();
+    let channels: Vec<(u64, String)> = channels
+        .into_iter()
+        .map(|(_, id, name)| (id, name))
+        .collect();
+
+    let ctx_id = ctx.id();
+    let prefix = format!("{ctx_id}-settings-");
+
+    let reply = ctx
+        .send(|b| {
+            b.embed(|e| settings_embed(e, &settings))
+                .components(|c| settings_components(c, ctx_id, &settings, &channels))
+        })
+        .await?;
+
+    while let Some(press) = CollectComponentInteraction::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+        .timeout(Duration::from_secs(SETTINGS_TIMEOUT_SECS))
+        .await
+    {
+        let setting = press.data.custom_id.trim_start_matches(&prefix);
+
+        // Someone else could have changed a setting since the menu was sent, don't overwrite it with an old copy
+        if let Some(latest) =
+            get_guild_settings(&mut con, *ctx.guild_id().unwrap().as_u64()).await?
+        {
+            settings = latest;
+        }
+
+        if apply_setting(
+            &mut settings,
+            setting,
+            press.data.values.first().map(String::as_str),
+        ) {
+            set_guild_settings(ctx, &mut con, settings.clone()).await?;
+
+            event!(
+                Level::INFO,
+                "Guild settings changed" = ctx.guild_id().unwrap().as_u64(),
+                "Setting" = setting,
+                "By" = ctx.author().id.as_u64()
+            );
+        }
+
+        press
+            .create_interaction_response(ctx, |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| {
+                        d.embed(|e| settings_embed(e, &settings))
+                            .components(|c| settings_components(c, ctx_id, &settings, &channels))
+                    })
+            })
+            .await?;
+    }
+
+    reply
+        .edit(ctx, |b| {
+            b.embed(|e| settings_embed(e, &settings)).components(|c| c)
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use poise::serenity_prelude::colours;
+use rusted_fbt_lib::api::{
+    generate_api_key, get_all_api_keys, hash_api_key, revoke_api_key, save_api_key,
+};
+use rusted_fbt_lib::checks::owner_check;
+use rusted_fbt_lib::structs::ApiKey;
+use rusted_fbt_lib::types::{Context, Error};
+use rusted_fbt_lib::utils::open_redis_connection;
+use rusted_fbt_lib::vars::API_DEFAULT_RATE_LIMIT;
+use tracing::instrument;
+use tracing::{event, Level};
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    category = "Admin",
+    check = "owner_check",
+    subcommands("api_key_create", "api_key_revoke", "api_key_list")
+)]
+/// Manage the keys partners use for the HTTP API
+pub async fn api_key(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Use `/api_key create`, `/api_key revoke` or `/api_key list`.")
+        .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "create",
+    category = "Admin",
+    check = "owner_check"
+)]
+/// Make a new API key for a partner, it's only shown once
+pub async fn api_key_create(
+    ctx: Context<'_>,
+    #[description = "Who the key is for"] name: String,
+    #[description = "Allow adding offences (Default: false)"] can_submit: Option<bool>,
+    #[description = "Server ID submitted offences are recorded under (Needed to submit)"]
+    guild_id: Option<String>,
+    #[description = "Requests allowed per minute (Default: 60)"] rate_limit: Option<u32>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    let can_submit = can_submit.unwrap_or(false);
+
+    if can_submit && guild_id.is_none() {
+        ctx.say("Keys that can submit need a `guild_id` to record offences under.")
+            .await?;
+        return Ok(());
+    }
+
+    let key = generate_api_key();
+    let api_key = ApiKey {
+        name: name.clone(),
+        created_at: chrono::Utc::now().timestamp(),
+        created_by: ctx.author().id.to_string(),
+        can_submit,
+        guild_id,
+        rate_limit_per_minute: rate_limit.unwrap_or(API_DEFAULT_RATE_LIMIT),
+    };
+
+    let mut con = open_redis_connection().await?;
+    save_api_key(&mut con, &hash_api_key(&key), &api_key).await?;
+
+    event!(
+        Level::INFO,
+        "API key created" = name,
+        "Can submit" = can_submit,
+        "By" = ctx.author().id.as_u64()
+    );
+
+    ctx.send(|b| {
+        b.content(format!(
+            "API key for `{name}`, this is the only time it will be shown:\n||`{key}`||\nSend it as `Authorization: Bearer <key>`."
+        ))
+        .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "revoke",
+    category = "Admin",
+    check = "owner_check"
+)]
+/// Revoke every API key with the given name
+pub async fn api_key_revoke(
+    ctx: Context<'_>,
+    #[description = "Who the key is for"] name: String,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let revoked = revoke_api_key(&mut con, &name).await?;
+
+    if revoked == 0 {
+        ctx.say(format!("There are no API keys called `{name}`."))
+            .await?;
+    } else {
+        ctx.say(format!("Revoked {revoked} API key(s) for `{name}`."))
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "list",
+    category = "Admin",
+    check = "owner_check"
+)]
+/// List every API key
+pub async fn api_key_list(ctx: Context<'_>) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let keys = get_all_api_keys(&mut con).await?;
+
+    if keys.is_empty() {
+        ctx.say("There are no API keys yet.").await?;
+        return Ok(());
+    }
+
+    let lines = keys
+        .iter()
+        .map(|(_, key)| {
+            format!(
+                "`{}`: {} requests/minute, {}, made <t:{}:R> by <@{}>",
+                key.name,
+                key.rate_limit_per_minute,
+                key.guild_id
+                    .as_ref()
+                    .filter(|_| key.can_submit)
+                    .map_or_else(
+                        || "lookup only".to_string(),
+                        |g| format!("submits as `{g}`")
+                    ),
+                key.created_at,
+                key.created_by
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title("API keys")
+                .description(lines)
+                .color(colours::css::POSITIVE)
+        })
+        .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use poise::serenity_prelude::colours;
+use rusted_fbt_lib::botban::{add_bot_ban, get_bot_bans, remove_bot_ban};
+use rusted_fbt_lib::checks::{reviewer_check, support_check};
+use rusted_fbt_lib::enums::{BotBanKind, StaffTier};
+use rusted_fbt_lib::staff::has_staff_tier;
+use rusted_fbt_lib::structs::BotBan;
+use rusted_fbt_lib::types::{Context, Error};
+use rusted_fbt_lib::utils::{open_redis_connection, parse_duration, truncate_text};
+use tracing::instrument;
+use tracing::{event, Level};
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Admin",
+    check = "support_check",
+    subcommands("botban_add", "botban_remove", "botban_list")
+)]
+/// Block users or whole servers from using the bot
+pub async fn botban(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say(
+        "Use `botban list` to see who's blocked, reviewers can use `botban add` and `botban remove`.",
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "add",
+    category = "Admin",
+    check = "reviewer_check"
+)]
+/// Block a user or server from using the bot, replacing any ban they already have
+pub async fn botban_add(
+    ctx: Context<'_>,
+    #[description = "User or server ID to block"] id: String,
+    #[description = "Why they're blocked, they get shown this"] reason: String,
+    #[description = "Block a user or a whole server (Default: User)"] kind: Option<BotBanKind>,
+    #[description = "How long until the ban ends, like 30d or 6w (Default: never)"]
+    expires_in: Option<String>,
+) -> Result<(), Error> {
+    let kind = kind.unwrap_or(BotBanKind::User);
+
+    let Ok(id) = id.trim().parse::<u64>() else {
+        ctx.send(|b| b.content("Make sure you supplied an ID").ephemeral(true))
+            .await?;
+        return Ok(());
+    };
+
+    let expires_in = expires_in.as_deref().map(parse_duration);
+
+    if expires_in == Some(None) {
+        ctx.send(|b| {
+            b.content("Make sure the expiry is a duration like `30d`, `6w` or `1d12h`")
+                .ephemeral(true)
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let mut con = open_redis_connection().await?;
+
+    if kind == BotBanKind::User && has_staff_tier(&mut con, id, StaffTier::Support).await? {
+        ctx.say("That user is on the bot's staff, take them off with `staff revoke` first.")
+            .await?;
+        return Ok(());
+    }
+
+    let now = chrono::Utc::now().timestamp();
+
+    let ban = BotBan {
+        kind,
+        id: id.to_string(),
+        reason,
+        banned_by: ctx.author().id.to_string(),
+        banned_at: now,
+        expires_at: expires_in
+            .flatten()
+            .map(|duration| now + duration.num_seconds()),
+    };
+
+    add_bot_ban(&mut con, &ban).await?;
+
+    event!(
+        Level::INFO,
+        "Bot ban added" = ban.id,
+        "Kind" = kind.to_string(),
+        "By" = ctx.author().id.as_u64()
+    );
+
+    let target = match kind {
+        BotBanKind::User => format!("<@{id}>"),
+        BotBanKind::Guild => format!("Server `{id}`"),
+    };
+
+    let until = ban.expires_at.map_or_else(
+        || "for good".to_string(),
+        |expires_at| format!("until <t:{expires_at}:f>"),
+    );
+
+    ctx.send(|b| {
+        b.content(format!(
+            "{target} is blocked from using the bot {until}.\nReason: {}",
+            ban.reason
+        ))
+        .allowed_mentions(|m| m.empty_parse())
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "remove",
+    category = "Admin",
+    check = "reviewer_check"
+)]
+/// Let a user or server use the bot again
+pub async fn botban_remove(
+    ctx: Context<'_>,
+    #[description = "User or server ID to unblock"] id: String,
+    #[description = "Unblock a user or a whole server (Default: User)"] kind: Option<BotBanKind>,
+) -> Result<(), Error> {
+    let kind = kind.unwrap_or(BotBanKind::User);
+    let id = id.trim();
+
+    let mut con = open_redis_connection().await?;
+
+    if remove_bot_ban(&mut con, kind, id).await? {
+        event!(
+            Level::INFO,
+            "Bot ban removed" = id,
+            "Kind" = kind.to_string(),
+            "By" = ctx.author().id.as_u64()
+        );
+
+        ctx.say(format!("{kind} `{id}` can use the bot again."))
+            .await?;
+    } else {
+        ctx.say(format!("{kind} `{id}` isn't blocked.")).await?;
+    }
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "list",
+    category = "Admin",
+    check = "support_check"
+)]
+/// List every user and server blocked from using the bot
+pub async fn botban_list(ctx: Context<'_>) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let bans = get_bot_bans(&mut con).await?;
+
+    let lines = if bans.is_empty() {
+        "Nobody is blocked.".to_string()
+    } else {
+        bans.iter()
+            .map(|ban| {
+                let until = ban.expires_at.map_or_else(String::new, |expires_at| {
+                    format!(" (ends <t:{expires_at}:R>)")
+                });
+                format!("{} `{}`: {}{until}", ban.kind, ban.id, ban.reason)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(format!("Bot bans ({})", bans.len()))
+                .description(truncate_text(&lines, 4096))
+                .color(colours::css::DANGER)
+        })
+    })
+    .await?;
+
+    Ok(())
+}
@@ -1,22 +1,54 @@
-use meilisearch_sdk::client::Client;
-use merge::Merge;
 use poise::serenity_prelude::Attachment;
-use poise::serenity_prelude::{colours, AttachmentType, Colour, Member, UserId};
+use poise::serenity_prelude::{
+    colours, AttachmentType, ButtonStyle, CollectComponentInteraction, Colour, CreateComponents,
+    CreateEmbed, InteractionResponseType, Member, User, UserId,
+};
 use rand::Rng;
-use rusted_fbt_lib::checks::guild_auth_check;
-use rusted_fbt_lib::structs::{BlacklistHit, CsvEntry, Offense, UserInfo};
-use rusted_fbt_lib::utils::{open_redis_connection, verbose_mode};
+use rusted_fbt_lib::backup::{
+    collect_backup, describe_restore, read_backup, restore_backup, write_backup,
+};
+use rusted_fbt_lib::checks::{
+    guild_auth_check, guild_manager_check, guild_submitter_check, owner_check, reviewer_check,
+    staff_check,
+};
+use rusted_fbt_lib::enums::{BackupFormat, ImportFormat, OffenceCategory, StaffTier, WebhookEvent};
+use rusted_fbt_lib::evidence::{parse_evidence_hash, purge_evidence, store_evidence};
+use rusted_fbt_lib::expiry::{get_archived_offences, is_active};
+use rusted_fbt_lib::identity::{alt_of, get_identities_of, get_identity_of};
+use rusted_fbt_lib::import::{
+    commit_import, detect_import_format, get_existing_users, parse_csv_import, parse_dce_import,
+    parse_discord_id, parse_id_list_import, parse_json_import, plan_import,
+};
+use rusted_fbt_lib::risk::get_risk_score;
+use rusted_fbt_lib::search::{
+    entries_index, find_search_drift, queue_search_sync, search_entries, search_page_embed,
+    search_pages, wait_for_search_task,
+};
+use rusted_fbt_lib::settings::{migrate_guild_settings, settings_problems};
+use rusted_fbt_lib::staff::get_all_staff;
+use rusted_fbt_lib::structs::{
+    BlacklistHit, ClearedUser, ColumnMapping, ImportRecord, Offense, RiskScore, UserInfo,
+    GUILD_SETTINGS_VERSION,
+};
+use rusted_fbt_lib::utils::{
+    add_offence, open_redis_connection, parse_duration, truncate_text, verbose_mode,
+};
 use rusted_fbt_lib::vars::BlacklistOutput;
-use rusted_fbt_lib::vars::{BOT_ADMINS, BOT_IDS, MEILISEARCH_API_KEY, MEILISEARCH_HOST};
+use rusted_fbt_lib::vars::{BULK_BAN_MAX_USERS, EVIDENCE_MAX_BYTES, PAGINATION_TIMEOUT_SECS};
+use rusted_fbt_lib::vrchat::{get_users_by_vrc_id, migrate_vrc_ids, normalize_vrc_id};
+use rusted_fbt_lib::webhooks::fire_webhook_event;
 use rusted_fbt_lib::{
     checks::bot_admin_check,
     types::{Context, Error},
 };
-use std::collections::HashSet;
-use std::ops::{Add, Mul};
+use std::collections::{HashMap, HashSet};
+use std::ops::Add;
+use std::time::Duration;
 use tracing::instrument;
 use tracing::{event, Level};
 
+use crate::commands::admin::send_ban_report;
+
 #[cfg(feature = "database")]
 #[instrument(skip(ctx))]
 #[poise::command(
@@ -25,9 +57,10 @@ use tracing::{event, Level};
     category = "DB",
     member_cooldown = 1,
     required_permissions = "BAN_MEMBERS",
-    check = "guild_auth_check",
+    check = "guild_submitter_check",
     guild_only
 )]
+#[allow(clippy::too_many_arguments)]
 /// Add an ID to the bot
 pub async fn add(
     ctx: Context<'_>,
@@ -38,9 +71,19 @@ pub async fn add(
     #[description = "ID or URL to VRChat account."] vrc_id: Option<String>,
     #[description = "Link to image or google drive folder of images."] image: Option<String>,
     #[description = "Anything extra you want to add."] extra: Option<String>,
+    #[description = "Screenshot or file to keep as evidence."] evidence: Option<Attachment>,
+    #[description = "Another file to keep as evidence."] evidence_2: Option<Attachment>,
+    #[description = "Another file to keep as evidence."] evidence_3: Option<Attachment>,
+    #[description = "How long until this offence stops counting, like 30d or 6w (Default: never)"]
+    expires_in: Option<String>,
 ) -> Result<(), Error> {
     ctx.defer().await?;
 
+    let attachments: Vec<Attachment> = [evidence, evidence_2, evidence_3]
+        .into_iter()
+        .flatten()
+        .collect();
+
     let mut is_not_user = false;
 
     let uid = id.trim().parse::<u64>().map_or_else(
@@ -51,128 +94,115 @@ pub async fn add(
         |i| i,
     );
 
+    let normalized_vrc_id = vrc_id.as_deref().map(normalize_vrc_id);
+    let expires_in = expires_in.as_deref().map(parse_duration);
+
     if is_not_user {
         ctx.send(|b| {
             b.content("Make sure you supplied a user ID")
                 .ephemeral(true)
         })
         .await?;
+    } else if normalized_vrc_id == Some(None) {
+        ctx.send(|b| {
+            b.content("Make sure you supplied a VRChat user ID (`usr_...`) or a link to their VRChat profile")
+                .ephemeral(true)
+        })
+        .await?;
+    } else if expires_in == Some(None) {
+        ctx.send(|b| {
+            b.content("Make sure the expiry is a duration like `30d`, `6w` or `1d12h`")
+                .ephemeral(true)
+        })
+        .await?;
+    } else if let Some(too_big) = attachments.iter().find(|a| a.size > EVIDENCE_MAX_BYTES) {
+        ctx.send(|b| {
+            b.content(format!(
+                "`{}` is too big to keep as evidence, the limit is {}MB",
+                too_big.filename,
+                EVIDENCE_MAX_BYTES / 1024 / 1024
+            ))
+            .ephemeral(true)
+        })
+        .await?;
     } else {
-        let gid: String = guild_id.map_or_else(|| ctx.guild_id().unwrap().to_string(), |url| url);
-
-        let mut con = open_redis_connection().await?;
+        let vrc_id = normalized_vrc_id.flatten();
+
+        // Copy the attachments somewhere they won't expire before anything is written to the DB
+        let mut stored_evidence = Vec::new();
+        for attachment in attachments {
+            let stored = match attachment.download().await {
+                Ok(data) => {
+                    store_evidence(data, &attachment.filename, &ctx.author().id.to_string()).await
+                }
+                Err(error) => Err(error.into()),
+            };
 
-        let result: Option<String> = redis::cmd("JSON.GET")
-            .arg(format!("user:{uid}"))
-            .clone()
-            .query_async(&mut con)
-            .await?;
+            match stored {
+                Ok(stored) => stored_evidence.push(stored),
+                Err(error) => {
+                    event!(Level::ERROR, "Failed to store evidence" = attachment.filename, error = ?error);
 
-        let uname = (UserId::from(uid).to_user(ctx).await)
-            .map_or_else(|_| "NoUsernameFoundInDB".to_string(), |u| u.tag());
+                    ctx.say(format!(
+                        "Couldn't save `{}` as evidence, nothing was added. Try again or link it in `image` instead.",
+                        attachment.filename
+                    ))
+                    .await?;
 
-        match result {
-            None => {
-                let mut new: UserInfo = UserInfo {
-                    vrc_id: None,
-                    username: Some(uname),
-                    discord_id: Some(format!("{uid}")),
-                    offences: Vec::new(),
-                };
-
-                let mut new_offense = Offense {
-                    guild_id: gid,
-                    reason: reason.clone(),
-                    image: image.clone().or_else(|| Some("N/A".to_string())),
-                    extra: extra.clone().or_else(|| Some("N/A".to_string())),
-                };
-
-                match vrc_id {
-                    None => {
-                        new.vrc_id = Some("N/A".to_string());
-                    }
-                    Some(url) => {
-                        new.vrc_id = Some(url);
-                    }
+                    return Ok(());
                 }
+            }
+        }
 
-                match image {
-                    None => {
-                        new_offense.image = Some("N/A".to_string());
-                    }
-                    Some(url) => {
-                        new_offense.image = Some(url);
-                    }
-                }
+        let gid: String = guild_id.map_or_else(|| ctx.guild_id().unwrap().to_string(), |url| url);
 
-                match extra {
-                    None => {
-                        new_offense.extra = Some("N/A".to_string());
-                    }
-                    Some(url) => {
-                        new_offense.extra = Some(url);
-                    }
-                }
+        let mut con = open_redis_connection().await?;
 
-                new.offences.push(new_offense);
+        let uname = (UserId::from(uid).to_user(ctx).await)
+            .map_or_else(|_| "NoUsernameFoundInDB".to_string(), |u| u.tag());
 
-                let json_user = serde_json::to_string(&new).unwrap();
+        let new_offense = Offense {
+            guild_id: gid,
+            reason: reason.clone(),
+            image: image.or_else(|| Some("N/A".to_string())),
+            extra: extra.or_else(|| Some("N/A".to_string())),
+            category: None,
+            provenance: None,
+            evidence: stored_evidence,
+            expires_at: expires_in
+                .flatten()
+                .map(|duration| (chrono::Utc::now() + duration).timestamp()),
+            added_at: None,
+        };
+
+        add_offence(
+            &mut con,
+            uid,
+            uname,
+            vrc_id,
+            new_offense.clone(),
+            &ctx.author().id.to_string(),
+        )
+        .await?;
 
-                redis::cmd("JSON.SET")
-                    .arg(format!("user:{uid}"))
-                    .arg("$".to_string())
-                    .arg(json_user)
-                    .clone()
-                    .query_async(&mut con)
-                    .await?;
+        match new_offense.expires_at {
+            Some(expires_at) => {
+                ctx.say(format!(
+                    "<@{uid}> added into DB!\nReason: {reason}\nExpires: <t:{expires_at}:R>"
+                ))
+                .await?;
             }
-            Some(_) => {
-                let mut new_offense = Offense {
-                    guild_id: gid,
-                    reason: reason.clone(),
-                    image: image.clone().or_else(|| Some("N/A".to_string())),
-                    extra: extra.clone().or_else(|| Some("N/A".to_string())),
-                };
-
-                match image {
-                    None => {
-                        new_offense.image = Some("N/A".to_string());
-                    }
-                    Some(url) => {
-                        new_offense.image = Some(url);
-                    }
-                }
-
-                match extra {
-                    None => {
-                        new_offense.extra = Some("N/A".to_string());
-                    }
-                    Some(url) => {
-                        new_offense.extra = Some(url);
-                    }
-                }
-
-                let json_offense = serde_json::to_string(&new_offense).unwrap();
-
-                redis::cmd("JSON.ARRAPPEND")
-                    .arg(format!("user:{uid}"))
-                    .arg("$.offences".to_string())
-                    .arg(json_offense)
-                    .clone()
-                    .query_async(&mut con)
+            None => {
+                ctx.say(format!("<@{uid}> added into DB!\nReason: {reason}"))
                     .await?;
             }
         }
-
-        ctx.say(format!("<@{uid}> added into DB!\nReason: {reason}"))
-            .await?;
     }
 
     Ok(())
 }
 
-// TODO: Change into sub command when we add vrc DB back
-/// Search DB for yourself or with ID
+/// Search DB by ID, username or VRChat ID
 #[cfg(feature = "database")]
 #[instrument(skip(ctx))]
 #[poise::command(
@@ -181,16 +211,147 @@ pub async fn add(
     category = "DB",
     member_cooldown = 5,
     check = "guild_auth_check",
-    guild_only
+    guild_only,
+    subcommands("search_id", "search_name", "search_vrchat")
 )]
 pub async fn search(
+    ctx: Context<'_>,
+    #[description = "Member to search for. This must be a user ID."] user_id: Option<String>,
+) -> Result<(), Error> {
+    match user_id {
+        Some(user_id) => search_by_id(ctx, user_id, false).await,
+        None => {
+            ctx.say("Use `search id`, `search name` or `search vrchat` to look someone up!")
+                .await?;
+            Ok(())
+        }
+    }
+}
+
+/// Search DB with a Discord user ID
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "id",
+    category = "DB",
+    member_cooldown = 5,
+    check = "guild_auth_check",
+    guild_only
+)]
+pub async fn search_id(
     ctx: Context<'_>,
     #[description = "Member to search for. This must be a user ID."] user_id: String,
+    #[description = "Include expired offences, reviewers and up only (Default: false)"]
+    history: Option<bool>,
+) -> Result<(), Error> {
+    search_by_id(ctx, user_id, history.unwrap_or(false)).await
+}
+
+/// Search DB for usernames close to what you type, small typos are fine
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "name",
+    category = "DB",
+    member_cooldown = 5,
+    check = "guild_auth_check",
+    guild_only
+)]
+pub async fn search_name(
+    ctx: Context<'_>,
+    #[description = "Username to search for"]
+    #[rest]
+    username: String,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let matches = search_entries(&username, "username").await?;
+
+    send_search_matches(ctx, &username, &matches).await
+}
+
+/// Search DB for a VRChat ID, small typos are fine
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "vrchat",
+    category = "DB",
+    member_cooldown = 5,
+    check = "guild_auth_check",
+    guild_only
+)]
+pub async fn search_vrchat(
+    ctx: Context<'_>,
+    #[description = "VRChat ID or profile link to search for"] vrc_id: String,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    // Full IDs get an exact lookup, anything else is left to the search engine
+    if let Some(vrc_id) = normalize_vrc_id(&vrc_id) {
+        let mut con = open_redis_connection().await?;
+        let matches = get_users_by_vrc_id(&mut con, &vrc_id).await?;
+
+        if !matches.is_empty() {
+            return send_search_matches(ctx, &vrc_id, &matches).await;
+        }
+    }
+
+    let matches = search_entries(&vrc_id, "vrc_id").await?;
+
+    send_search_matches(ctx, &vrc_id, &matches).await
+}
+
+/// Lists the users the search engine matched along with their IDs and how many offences they have
+#[cfg(feature = "database")]
+async fn send_search_matches(
+    ctx: Context<'_>,
+    query: &str,
+    matches: &[UserInfo],
 ) -> Result<(), Error> {
+    if matches.is_empty() {
+        ctx.say(format!("No matches found for `{query}`.")).await?;
+        return Ok(());
+    }
+
+    let lines = matches
+        .iter()
+        .enumerate()
+        .map(|(i, user)| {
+            format!(
+                "{}. `{}` - ID: `{}` - VRChat ID: `{}` - {} offence(s)",
+                i + 1,
+                user.username.as_deref().unwrap_or("N/A"),
+                user.discord_id.as_deref().unwrap_or("N/A"),
+                user.vrc_id.as_deref().unwrap_or("N/A"),
+                user.offences.len()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(format!("Top matches for \"{query}\""))
+                .description(lines)
+                .color(colours::css::POSITIVE)
+                .footer(|f| f.text("Use search id with one of these IDs to see their offences"))
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Searches the DB for one user ID and shows their active offences, or every offence including archived ones with `history`
+#[cfg(feature = "database")]
+async fn search_by_id(ctx: Context<'_>, user_id: String, history: bool) -> Result<(), Error> {
     use chrono::DateTime;
-    use pastemyst::paste::*;
-    use pastemyst::str;
-    use poise::serenity_prelude::User;
 
     ctx.defer().await?;
 
@@ -219,160 +380,52 @@ pub async fn search(
     if let Some(u) = u_opt {
         let result = check_username_against_db(uid).await?;
 
-        match result {
-            None => {
+        let mut con = open_redis_connection().await?;
+        let identity = get_identity_of(&mut con, &uid.to_string()).await?;
+
+        match (result, identity) {
+            (None, Some(identity)) if alt_of(&identity, &uid.to_string()).is_some() => {
+                ctx.say(format!(
+                    "No result found for {uid}, but they're linked as an alt of <@{0}>/{0}. Use `search id {0}` to see their offences.",
+                    identity.primary
+                ))
+                .await?;
+            }
+            (None, _) => {
                 ctx.say(format!("No result found for {uid}.")).await?;
             }
-            Some(hit) => {
-                let hit_string: String = hit;
-                let user: UserInfo = serde_json::from_str(hit_string.as_str())?;
-
-                let username = user.username.unwrap_or_else(|| u.tag());
-                let id = user.discord_id.unwrap_or(format!("{}", uid.clone()));
-                let vrc = user.vrc_id.unwrap_or_else(|| "N/A".to_string());
-
-                let field_count = user.offences.len() as u64;
-
-                if field_count.mul(7).add(4).ge(&25_u64) {
-                    let mut offences = String::new();
-
-                    let mut i: u64 = 0;
-                    for hit in user.offences.clone() {
-                        i = i.add(1);
-
-                        let image = hit.image.unwrap_or_else(|| "N/A".to_string());
-                        let extra = hit.extra.unwrap_or_else(|| "N/A".to_string());
-                        offences.push_str(format!("\n\nOffence {i}:\n Guild ID: {0}\n Reason: {1}\n Image(s): {image}\n Extra info: {extra}", hit.guild_id, hit.reason).as_str());
-                    }
-
-                    let msg_start = format!(
-                        "Result found!\nUser has {} hit(s).\nUsername logged in DB: {}\nCurrent username: {}\nUser ID: {}\nVRChat ID: {}",
-                        user.offences.clone().len() as u64,
-                        username,
-                        u.tag(),
-                        id,
-                        vrc
-                        );
-
-                    if (offences.chars().count() + msg_start.chars().count()) < 2000 {
-                        // for format! merges msg_start and offences
-                        ctx.say(format!("{msg_start}```{offences}```")).await?;
-                    } else {
-                        let txt_contents = format!("{msg_start}\n\n{offences}");
-                        ctx.send(|f| {
-                            f.content("Result found!\nThis user has so many hits that we can't display them all in Discord.\nBellow is a `.txt` file with all of their offences.")
-                                .ephemeral(false)
-                                .attachment(AttachmentType::Bytes {
-                                    data: std::borrow::Cow::Borrowed(txt_contents.as_bytes()),
-                                    filename: format!("{id}.txt"),
-                                })
-                        })
-                        .await?;
-                    }
+            (Some(hit), identity) => {
+                let mut user: UserInfo = serde_json::from_str(hit.as_str())?;
+                let risk = get_risk_score(&mut con, &uid.to_string(), &user).await?;
+
+                if history && staff_check(ctx, StaffTier::Reviewer).await? {
+                    let archived = get_archived_offences(&mut con, &uid.to_string()).await?;
+                    user.offences
+                        .extend(archived.into_iter().map(|archived| archived.offence));
                 } else {
-                    let mut fields: Vec<(String, String, bool)> = vec![
-                        ("Username logged in DB:".to_string(), username.clone(), true),
-                        ("Current username:".to_string(), u.tag(), true),
-                        ("User ID:".to_string(), id.clone(), true),
-                        ("VRChat ID:".to_string(), vrc.clone(), true),
-                    ];
-
-                    let mut i: u64 = 0;
-
-                    // let mut buffer_len: usize = 0;
-
-                    // for offense in user.offences.clone() {
-                    //     buffer_len += offense.reason.chars().into_iter().count();
-                    // }
-
-                    for offense in user.offences.clone() {
-                        i = i.add(1);
-                        fields.push(("Offense:".to_string(), format!("#{i}"), false));
-
-                        let image = offense.image.unwrap_or_else(|| "N/A".to_string());
-                        let extra = offense.extra.unwrap_or_else(|| "N/A".to_string());
-
-                        fields.push(("Guild ID:".to_string(), offense.guild_id, true));
-                        fields.push((
-                            "Reason:".to_string(),
-                            {
-                                if offense.reason.chars().into_iter().count() > 1000 {
-                                    let pasties: Vec<PastyObject> = vec![PastyObject {
-                                        _id: str!(""),
-                                        language: str!(pastemyst::data::language::MARKDOWN),
-                                        title: format!("{}'s long offence", username.clone()),
-                                        code: offense.reason,
-                                    }];
-
-                                    let data: CreateObject = CreateObject {
-                                        title: format!("{}'s long offence", username.clone()),
-                                        expiresIn: String::from("1d"),
-                                        isPrivate: false,
-                                        isPublic: false,
-                                        tags: String::from(""),
-                                        pasties: pasties,
-                                    };
-
-                                    let paste = create_paste(data)?;
-
-                                    format!("https://paste.myst.rs/{}", paste._id)
-                                } else {
-                                    offense.reason
-                                }
-                            },
-                            true,
-                        ));
-                        fields.push(("Image(s):".to_string(), image, true));
-                        fields.push(("Extra info:".to_string(), extra, true));
-                    }
+                    let now = chrono::Utc::now().timestamp();
+                    user.offences.retain(|offense| is_active(offense, now));
+                }
 
-                    let colour = &mut rand::thread_rng().gen_range(0..10_000_000);
-
-                    let msg = ctx.send(|b| {
-                        b.embed(|e| {
-                        e.title("Result found!")
-                        .description(format!(
-                        "User has {} hit(s).",
-                        user.offences.clone().len() as u64
-                        ))
-                        .fields(fields)
-                        .color(Colour::new(*colour))
-                        .thumbnail(u.avatar_url().unwrap_or_else(|| "https://discord.com/assets/1f0bfc0865d324c2587920a7d80c609b.png".to_string()))
-                        })
-                        })
-                        .await;
-                    if msg.is_err() {
-                        let msg_start = format!(
-                            "Result found!\nUser has {} hit(s).\nUsername logged in DB: {}\nCurrent username: {}\nUser ID: {}\nVRChat ID: {}",
-                            user.offences.clone().len() as u64,
-                            username,
-                            u.tag(),
-                            id,
-                            vrc
-                            );
-
-                        let mut offences = String::new();
-
-                        let mut i: u64 = 0;
-                        for hit in user.offences.clone() {
-                            i = i.add(1);
-
-                            let image = hit.image.unwrap_or_else(|| "N/A".to_string());
-                            let extra = hit.extra.unwrap_or_else(|| "N/A".to_string());
-                            offences.push_str(format!("\n\nOffence {i}:\n Guild ID: {0}\n Reason: {1}\n Image(s): {image}\n Extra info: {extra}", hit.guild_id, hit.reason).as_str());
-                        }
+                let identity = identity.map(|identity| {
+                    alt_of(&identity, &uid.to_string()).map_or_else(
+                        || {
+                            format!(
+                                "Main account with {} linked account(s), see `identity show`",
+                                identity.accounts.len()
+                            )
+                        },
+                        |primary| format!("Alt of <@{primary}>/{primary}"),
+                    )
+                });
 
-                        let txt_contents = format!("{msg_start}\n\n{offences}");
-                        ctx.send(|f| {
-                                f.content("Result found!\nThere was some kinda of error sending the fancy version, most likely one of the field (for example the images) was too long.\nBellow is a `.txt` file with all of their offences formated as nicely as I could make it.")
-                                    .ephemeral(false)
-                                    .attachment(AttachmentType::Bytes {
-                                        data: std::borrow::Cow::Borrowed(txt_contents.as_bytes()),
-                                        filename: format!("{username}_{id}.txt"),
-                                    })
-                            })
-                            .await?;
-                    }
+                if user.offences.is_empty() {
+                    ctx.say(format!(
+                        "{uid} is in the DB but all of their offences have expired."
+                    ))
+                    .await?;
+                } else {
+                    paginate_search_result(ctx, &user, &u, identity.as_deref(), risk).await?;
                 }
             }
         }
@@ -420,6 +473,116 @@ pub async fn search(
     Ok(())
 }
 
+/// Adds the previous/next/export buttons under a page of search results
+#[cfg(feature = "database")]
+fn search_page_buttons(
+    c: &mut CreateComponents,
+    ctx_id: u64,
+    page: usize,
+    page_count: usize,
+) -> &mut CreateComponents {
+    c.create_action_row(|r| {
+        r.create_button(|b| {
+            b.custom_id(format!("{ctx_id}-search-prev"))
+                .label("Previous")
+                .style(ButtonStyle::Secondary)
+                .disabled(page == 0)
+        })
+        .create_button(|b| {
+            b.custom_id(format!("{ctx_id}-search-next"))
+                .label("Next")
+                .style(ButtonStyle::Secondary)
+                .disabled(page + 1 >= page_count)
+        })
+        .create_button(|b| {
+            b.custom_id(format!("{ctx_id}-search-export"))
+                .label("Export")
+                .style(ButtonStyle::Primary)
+        })
+    })
+}
+
+/// Shows a user's search results one page at a time, the buttons stop working after [`PAGINATION_TIMEOUT_SECS`] without a press
+#[cfg(feature = "database")]
+async fn paginate_search_result(
+    ctx: Context<'_>,
+    user: &UserInfo,
+    current: &User,
+    identity: Option<&str>,
+    risk: RiskScore,
+) -> Result<(), Error> {
+    let ctx_id = ctx.id();
+    let page_count = search_pages(user).len();
+    let colour = rand::thread_rng().gen_range(0..10_000_000);
+    let mut page = 0;
+
+    let reply = ctx
+        .send(|b| {
+            b.embed(|e| search_page_embed(e, user, current, identity, risk, colour, page))
+                .components(|c| search_page_buttons(c, ctx_id, page, page_count))
+        })
+        .await?;
+
+    while let Some(press) = CollectComponentInteraction::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+        .timeout(Duration::from_secs(PAGINATION_TIMEOUT_SECS))
+        .await
+    {
+        match press.data.custom_id.trim_start_matches(&ctx_id.to_string()) {
+            "-search-export" => {
+                press
+                    .create_interaction_response(ctx, |r| {
+                        r.kind(InteractionResponseType::DeferredUpdateMessage)
+                    })
+                    .await?;
+
+                let json = serde_json::to_string_pretty(user)?;
+                let id = user
+                    .discord_id
+                    .clone()
+                    .unwrap_or_else(|| current.id.to_string());
+
+                ctx.send(|b| {
+                    b.content(format!("Here's everything in the DB for `{id}`."))
+                        .attachment(AttachmentType::Bytes {
+                            data: std::borrow::Cow::Borrowed(json.as_bytes()),
+                            filename: format!("{id}.json"),
+                        })
+                })
+                .await?;
+
+                continue;
+            }
+            "-search-prev" => page = page.saturating_sub(1),
+            "-search-next" => page = (page + 1).min(page_count - 1),
+            _ => continue,
+        }
+
+        press
+            .create_interaction_response(ctx, |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| {
+                        d.embed(|e| {
+                            search_page_embed(e, user, current, identity, risk, colour, page)
+                        })
+                        .components(|c| search_page_buttons(c, ctx_id, page, page_count))
+                    })
+            })
+            .await?;
+    }
+
+    reply
+        .edit(ctx, |b| {
+            b.embed(|e| search_page_embed(e, user, current, identity, risk, colour, page))
+                .components(|c| c)
+        })
+        .await?;
+
+    Ok(())
+}
+
 pub async fn check_username_against_db(uid: u64) -> anyhow::Result<Option<String>> {
     let mut con = open_redis_connection().await?;
     let result: Option<String> = redis::cmd("JSON.GET")
@@ -437,7 +600,7 @@ pub async fn check_username_against_db(uid: u64) -> anyhow::Result<Option<String
     slash_command,
     category = "DB",
     member_cooldown = 5,
-    check = "guild_auth_check",
+    check = "guild_manager_check",
     guild_only
 )]
 pub async fn remove_guild(
@@ -522,6 +685,12 @@ pub async fn remove_guild(
     // Edit all entries at once
     pipe2.atomic().query_async(&mut con).await?;
 
+    let changed_ids: Vec<String> = guild_offences
+        .iter()
+        .filter_map(|entry| entry.discord_id.clone())
+        .collect();
+    queue_search_sync(&mut con, &changed_ids).await?;
+
     // Respond to the user with the amount of users that had offences in the guild we removed
     ctx.say(format!(
         "Removed guild {} from {} users.",
@@ -533,18 +702,96 @@ pub async fn remove_guild(
     Ok(())
 }
 
+/// Compare the search engine against the DB and report anything that doesn't match
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, category = "DB", check = "owner_check")]
+pub async fn reconcile_search_engine(
+    ctx: Context<'_>,
+    #[description = "Queue every mismatched user to be fixed (Default: false)"] fix: Option<bool>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    let mut con = open_redis_connection().await?;
+
+    let drift = find_search_drift(&mut con).await?;
+
+    let queued: u64 = redis::cmd("LLEN")
+        .arg("search-sync-queue")
+        .query_async(&mut con)
+        .await?;
+    let failed: u64 = redis::cmd("LLEN")
+        .arg("search-sync-failed")
+        .query_async(&mut con)
+        .await?;
+
+    let list = |ids: &[String]| {
+        if ids.is_empty() {
+            "None".to_string()
+        } else if ids.len() > 20 {
+            format!("{}\n...and {} more", ids[..20].join("\n"), ids.len() - 20)
+        } else {
+            ids.join("\n")
+        }
+    };
+
+    let fixing = fix.unwrap_or(false)
+        && !(drift.missing.is_empty() && drift.stale.is_empty() && drift.outdated.is_empty());
+
+    if fixing {
+        let mut ids = drift.missing.clone();
+        ids.extend(drift.stale.clone());
+        ids.extend(drift.outdated.clone());
+
+        queue_search_sync(&mut con, &ids).await?;
+    }
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title("Search engine drift")
+                .field(
+                    format!("Missing from search ({})", drift.missing.len()),
+                    list(&drift.missing),
+                    false,
+                )
+                .field(
+                    format!(
+                        "Removed from DB but still searchable ({})",
+                        drift.stale.len()
+                    ),
+                    list(&drift.stale),
+                    false,
+                )
+                .field(
+                    format!("Out of date ({})", drift.outdated.len()),
+                    list(&drift.outdated),
+                    false,
+                )
+                .field("Waiting in sync queue", queued, true)
+                .field("Gave up syncing", failed, true)
+                .footer(|f| {
+                    f.text(if fixing {
+                        "Every mismatched user has been queued to sync."
+                    } else {
+                        "Run again with fix: True to queue them to sync."
+                    })
+                })
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
 /// Update the search engine entries
 #[cfg(feature = "database")]
 #[instrument(skip(ctx))]
-#[poise::command(slash_command, category = "DB", owners_only)]
+#[poise::command(slash_command, category = "DB", check = "owner_check")]
 pub async fn update_search_engine(ctx: Context<'_>) -> Result<(), Error> {
     ctx.defer_ephemeral().await?;
 
-    // Create a client (without sending any request so that can't fail)
-    let client = Client::new(MEILISEARCH_HOST, Some(MEILISEARCH_API_KEY));
-
     // connect to index "entries"
-    let entries = client?.index("entries");
+    let entries = entries_index()?;
 
     let msg = ctx
         .send(|b| b.content("Connected to search engine"))
@@ -577,7 +824,7 @@ pub async fn update_search_engine(ctx: Context<'_>) -> Result<(), Error> {
 
     let mut black_listed_users = Vec::new();
     for user in blacklist_entries {
-        let user: UserInfo = serde_json::from_str(&user).expect("It was 701");
+        let user: UserInfo = serde_json::from_str(&user)?;
 
         black_listed_users.push(user);
     }
@@ -585,30 +832,214 @@ pub async fn update_search_engine(ctx: Context<'_>) -> Result<(), Error> {
     msg.edit(ctx, |b| b.content("Updating search engine"))
         .await?;
 
-    // push entries to meidisearch
-    match entries
+    // push entries to meidisearch and wait for it to actually take them
+    let task = entries
         .add_documents(black_listed_users.as_slice(), Some("discord_id"))
-        .await
-    {
-        Ok(_) => {}
-        Err(err) => {
-            event!(
-                Level::INFO,
-                suggestion = "This error can probably be ignored?",
-                error = ?err
-            );
-        }
+        .await?;
+
+    if let Err(error) = wait_for_search_task(&entries, task, Some(Duration::from_secs(600))).await {
+        event!(Level::ERROR, "Search engine update failed" = ?error);
+
+        msg.edit(ctx, |b| {
+            b.content(format!("The search engine rejected the update: {error}"))
+        })
+        .await?;
+
+        return Ok(());
     }
 
     msg.edit(ctx, |b| {
-            b.content("All DB entries sent to search engine, any changes or new entries should be avaliable in a couple minutes.")
-        }).await?;
+        b.content(format!(
+            "All {} DB entries are now in the search engine!",
+            black_listed_users.len()
+        ))
+    })
+    .await?;
 
     event!(Level::INFO, "Search engine was updated.");
 
     Ok(())
 }
 
+/// Turn every VRChat ID in the DB into a plain `usr_` ID and rebuild the VRChat ID lookup
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, category = "DB", check = "owner_check")]
+pub async fn migrate_vrchat_ids(
+    ctx: Context<'_>,
+    #[description = "Only report what would change (Default: true)"] dry_run: Option<bool>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    let dry_run = dry_run.unwrap_or(true);
+
+    let mut con = open_redis_connection().await?;
+    let report = migrate_vrc_ids(&mut con, dry_run).await?;
+
+    if !dry_run {
+        event!(
+            Level::INFO,
+            "VRChat IDs migrated" = report.normalized.len(),
+            "Invalid" = report.invalid.len()
+        );
+    }
+
+    let invalid = if report.invalid.is_empty() {
+        "None".to_string()
+    } else {
+        let lines = report
+            .invalid
+            .iter()
+            .take(15)
+            .map(|(id, value)| format!("`{id}`: `{}`", truncate_text(value, 60)))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        if report.invalid.len() > 15 {
+            format!("{lines}\n...and {} more", report.invalid.len() - 15)
+        } else {
+            lines
+        }
+    };
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(if dry_run {
+                "VRChat ID migration (dry run)"
+            } else {
+                "VRChat ID migration"
+            })
+            .field("Entries with a VRChat ID", report.checked, true)
+            .field("Already valid", report.already_valid, true)
+            .field("Normalized", report.normalized.len(), true)
+            .field(
+                format!("No VRChat ID found ({})", report.invalid.len()),
+                invalid,
+                false,
+            )
+            .footer(|f| {
+                f.text(if dry_run {
+                    "Nothing was changed, run again with dry_run: False to apply."
+                } else {
+                    "Entries with no VRChat ID found were left as they are."
+                })
+            })
+            .color(colours::css::POSITIVE)
+        })
+        .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Lists up to 15 `(guild ID, problem)` pairs for the settings audit embed
+#[cfg(feature = "database")]
+fn settings_audit_list(items: &[(String, String)]) -> String {
+    if items.is_empty() {
+        return "None".to_string();
+    }
+
+    let lines = items
+        .iter()
+        .take(15)
+        .map(|(guild_id, problem)| format!("`{guild_id}`: {}", truncate_text(problem, 80)))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    if items.len() > 15 {
+        format!("{lines}\n...and {} more", items.len() - 15)
+    } else {
+        lines
+    }
+}
+
+/// Migrate every server's settings and report ones that are broken or point at deleted channels
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, category = "DB", check = "owner_check")]
+pub async fn settings_audit(
+    ctx: Context<'_>,
+    #[description = "Only report what would change (Default: true)"] dry_run: Option<bool>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    let dry_run = dry_run.unwrap_or(true);
+
+    let mut con = open_redis_connection().await?;
+    let report = migrate_guild_settings(&mut con, dry_run).await?;
+
+    if !dry_run {
+        event!(
+            Level::INFO,
+            "Guild settings migrated" = report.migrated.len(),
+            "Invalid" = report.invalid.len()
+        );
+    }
+
+    let cache = &ctx.serenity_context().cache;
+
+    let stale: Vec<(String, String)> = report
+        .settings
+        .iter()
+        .flat_map(|(guild_id, settings)| {
+            let channels = guild_id
+                .parse::<u64>()
+                .ok()
+                .and_then(|id| cache.guild(id))
+                .map(|guild| {
+                    guild
+                        .channels
+                        .keys()
+                        .map(|id| *id.as_u64())
+                        .collect::<HashSet<u64>>()
+                });
+
+            settings_problems(settings, channels.as_ref())
+                .into_iter()
+                .map(|problem| (guild_id.clone(), problem))
+        })
+        .collect();
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(if dry_run {
+                "Guild settings audit (dry run)"
+            } else {
+                "Guild settings audit"
+            })
+            .field("Servers checked", report.checked, true)
+            .field(
+                format!("Older than version {GUILD_SETTINGS_VERSION}"),
+                report.migrated.len(),
+                true,
+            )
+            .field(
+                format!("Invalid ({})", report.invalid.len()),
+                settings_audit_list(&report.invalid),
+                false,
+            )
+            .field(
+                format!("Stale ({})", stale.len()),
+                settings_audit_list(&stale),
+                false,
+            )
+            .footer(|f| {
+                f.text(if dry_run {
+                    "Nothing was changed, run again with dry_run: False to migrate."
+                } else {
+                    "Older settings were migrated, invalid and stale ones were left as they are."
+                })
+            })
+            .color(colours::css::POSITIVE)
+        })
+        .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
 /// Check your entire server against the database. Now with output options!
 #[cfg(feature = "database")]
 #[instrument(skip(ctx))]
@@ -683,6 +1114,31 @@ pub async fn footprint_lookup(
         black_listed_users.push(user);
     }
 
+    // Members linked as an alt of someone are reported with the main account's offences
+    let member_ids: Vec<String> = guild_members
+        .iter()
+        .map(|member| member.user.id.to_string())
+        .collect();
+
+    let alts: HashMap<String, String> = get_identities_of(&mut con, &member_ids)
+        .await?
+        .into_iter()
+        .filter_map(|(member_id, identity)| {
+            alt_of(&identity, &member_id).map(|primary| (member_id.clone(), primary.to_string()))
+        })
+        .collect();
+
+    let mut main_accounts: HashMap<String, UserInfo> = HashMap::new();
+    for primary in alts.values() {
+        if main_accounts.contains_key(primary) {
+            continue;
+        }
+
+        if let Some(entry) = check_username_against_db(primary.parse::<u64>()?).await? {
+            main_accounts.insert(primary.clone(), serde_json::from_str(&entry)?);
+        }
+    }
+
     let mut hit_count: u64 = 0;
 
     // Setup CSV
@@ -695,15 +1151,21 @@ pub async fn footprint_lookup(
             "Reason",
             "Related image(s)",
             "Extra details",
+            "Alt of",
+            "Risk score",
         ])
         .expect("Unable to repare CSV");
 
     // Setup "Json"
     let mut struct_hit_list: Vec<BlacklistHit> = Vec::new();
 
+    // ID, username and reason of everyone found, used to offer bans afterwards
+    let mut ban_targets: Vec<(UserId, String, String)> = Vec::new();
+
     for member in guild_members {
         let member_id = format!("{}", member.user.id.as_u64());
-        let hit_list: Vec<UserInfo> = black_listed_users
+        let alt_of_id = alts.get(&member_id).cloned();
+        let mut hit_list: Vec<UserInfo> = black_listed_users
             .clone()
             .into_iter()
             .filter(|blu| {
@@ -714,6 +1176,12 @@ pub async fn footprint_lookup(
             })
             .collect();
 
+        if hit_list.is_empty() {
+            if let Some(main_account) = alt_of_id.as_ref().and_then(|p| main_accounts.get(p)) {
+                hit_list.push(main_account.clone());
+            }
+        }
+
         // Moves to next loop if empty
         if hit_list.is_empty() {
             continue;
@@ -729,16 +1197,26 @@ pub async fn footprint_lookup(
 
         // Write discord specific instances to CSV
         for hit in hit_list {
-            let cloned_hit = hit.clone();
-            let username = UserId::from(cloned_hit.discord_id.as_ref().unwrap().parse::<u64>()?)
-                .to_user(ctx)
-                .await?
-                .name
-                .to_string();
-            let user_id = cloned_hit
-                .discord_id
-                .expect("Missing ID in BL user somehow?")
-                .to_string();
+            let username = member.user.name.clone();
+            // Alts are reported under their own ID even though the offences are the main account's
+            let user_id = member_id.clone();
+            let risk =
+                get_risk_score(&mut con, alt_of_id.as_deref().unwrap_or(&user_id), &hit).await?;
+
+            let reasons = hit
+                .offences
+                .iter()
+                .map(|offense| offense.reason.clone())
+                .collect::<Vec<String>>()
+                .join("; ");
+
+            ban_targets.push((
+                UserId::from(user_id.parse::<u64>()?),
+                username.clone(),
+                alt_of_id
+                    .as_ref()
+                    .map_or_else(|| reasons.clone(), |p| format!("Alt of {p}: {reasons}")),
+            ));
 
             match output_format {
                 None | Some(BlacklistOutput::Csv) => {
@@ -760,6 +1238,8 @@ pub async fn footprint_lookup(
                                     .extra
                                     .map_or_else(|| "N/A".to_string(), |extra| extra)
                             ),
+                            format!("'{}'", alt_of_id.clone().unwrap_or_default()),
+                            format!("'{}'", risk.score),
                         ])?;
                     }
                 }
@@ -780,6 +1260,8 @@ pub async fn footprint_lookup(
                                 .extra
                                 .map_or_else(|| "N/A".to_string(), |extra| extra))
                             .to_string(),
+                            alt_of: alt_of_id.clone(),
+                            risk_score: risk.score,
                         });
                     }
                 }
@@ -838,9 +1320,17 @@ pub async fn footprint_lookup(
                 let mut message_content: Vec<String> = Vec::new();
 
                 for hit in struct_hit_list {
+                    let alt_note = hit
+                        .alt_of
+                        .as_ref()
+                        .map(|p| format!(" (alt of <@{p}>/{p})"))
+                        .unwrap_or_default();
+
                     let hit_msg = format!(
-                        "<@{}>/{0} was found in your server for the following reason: `{}`\nExtras: {}\nImages: {}",
+                        "<@{}>/{0}{} (risk score {}) was found in your server for the following reason: `{}`\nExtras: {}\nImages: {}",
                         hit.user_id,
+                        alt_note,
+                        hit.risk_score,
                         hit.reason,
                         hit.extra,
                         hit.image
@@ -903,7 +1393,20 @@ pub async fn footprint_lookup(
                 let mut unique_hits: HashSet<String> = HashSet::new();
 
                 for hit in struct_hit_list {
-                    unique_hits.insert(format!("Found: <@{0}>/{0}", hit.user_id));
+                    unique_hits.insert(hit.alt_of.map_or_else(
+                        || {
+                            format!(
+                                "Found: <@{0}>/{0} - risk score {1}",
+                                hit.user_id, hit.risk_score
+                            )
+                        },
+                        |p| {
+                            format!(
+                                "Found: <@{0}>/{0} (alt of <@{p}>/{p}) - risk score {1}",
+                                hit.user_id, hit.risk_score
+                            )
+                        },
+                    ));
                 }
 
                 for unique_hit in unique_hits {
@@ -966,10 +1469,195 @@ pub async fn footprint_lookup(
         })
         .await?;
 
+    if !ban_targets.is_empty() {
+        offer_bulk_ban(ctx, ban_targets).await?;
+    }
+
+    Ok(())
+}
+
+/// Offers to ban everyone (or a selection of) the users `footprint_lookup` found, asking for confirmation first
+async fn offer_bulk_ban(
+    ctx: Context<'_>,
+    targets: Vec<(UserId, String, String)>,
+) -> Result<(), Error> {
+    use rusted_fbt_lib::utils::bulk_ban;
+
+    // Only offer the buttons to people who could ban these users by hand anyway
+    let can_ban = match ctx.author_member().await {
+        Some(member) => member
+            .permissions(ctx.serenity_context())
+            .is_ok_and(|permissions| permissions.ban_members()),
+        None => false,
+    };
+
+    if !can_ban {
+        return Ok(());
+    }
+
+    let ctx_id = ctx.id();
+    let ban_all_id = format!("{ctx_id}-ban-all");
+    let ban_selected_id = format!("{ctx_id}-ban-selected");
+    let confirm_id = format!("{ctx_id}-ban-confirm");
+    let cancel_id = format!("{ctx_id}-ban-cancel");
+
+    // Select menus can only hold 25 options
+    let selectable = &targets[..targets.len().min(25)];
+
+    let mut question = format!(
+        "Want me to ban the {} user(s) I found? Use \"Ban all\" or pick who to ban from the list, you'll be asked to confirm first.",
+        targets.len()
+    );
+
+    if targets.len() > selectable.len() {
+        question.push_str(&format!(
+            "\nOnly the first {} can be picked from the list, \"Ban all\" covers everyone else.",
+            selectable.len()
+        ));
+    }
+
+    if targets.len() > BULK_BAN_MAX_USERS {
+        question.push_str(&format!("\n\"Ban all\" only bans the first {BULK_BAN_MAX_USERS} so the results can still be sent when it's done."));
+    }
+
+    let prompt = ctx
+        .send(|b| {
+            b.content(&question).components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| {
+                        b.custom_id(&ban_all_id)
+                            .label("Ban all")
+                            .style(ButtonStyle::Danger)
+                    })
+                })
+                .create_action_row(|r| {
+                    r.create_select_menu(|m| {
+                        m.custom_id(&ban_selected_id)
+                            .placeholder("Ban selected")
+                            .min_values(1)
+                            .max_values(selectable.len() as u64)
+                            .options(|o| {
+                                for (id, username, reason) in selectable {
+                                    o.create_option(|opt| {
+                                        opt.label(username.chars().take(100).collect::<String>())
+                                            .value(id);
+                                        if !reason.is_empty() {
+                                            opt.description(
+                                                reason.chars().take(100).collect::<String>(),
+                                            );
+                                        }
+                                        opt
+                                    });
+                                }
+                                o
+                            })
+                    })
+                })
+            })
+        })
+        .await?;
+
+    let mut selected: Vec<UserId> = Vec::new();
+
+    while let Some(press) = CollectComponentInteraction::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+        .timeout(Duration::from_secs(120))
+        .await
+    {
+        let pressed = press.data.custom_id.as_str();
+
+        if pressed == ban_all_id || pressed == ban_selected_id {
+            selected = if pressed == ban_all_id {
+                targets
+                    .iter()
+                    .take(BULK_BAN_MAX_USERS)
+                    .map(|(id, _, _)| *id)
+                    .collect()
+            } else {
+                press
+                    .data
+                    .values
+                    .iter()
+                    .filter_map(|value| value.parse::<u64>().ok())
+                    .map(UserId)
+                    .collect()
+            };
+
+            press
+                .create_interaction_response(ctx, |r| {
+                    r.kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|d| {
+                            d.content(format!(
+                                "Are you sure you want to ban {} user(s)? This can't be undone from here.",
+                                selected.len()
+                            ))
+                            .components(|c| {
+                                c.create_action_row(|r| {
+                                    r.create_button(|b| {
+                                        b.custom_id(&confirm_id)
+                                            .label("Confirm")
+                                            .style(ButtonStyle::Danger)
+                                    })
+                                    .create_button(|b| {
+                                        b.custom_id(&cancel_id)
+                                            .label("Cancel")
+                                            .style(ButtonStyle::Secondary)
+                                    })
+                                })
+                            })
+                        })
+                })
+                .await?;
+        } else if pressed == confirm_id {
+            press
+                .create_interaction_response(ctx, |r| {
+                    r.kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|d| {
+                            d.content(format!(
+                                "Banning {} user(s), this might take a little while...",
+                                selected.len()
+                            ))
+                            .components(|c| c)
+                        })
+                })
+                .await?;
+
+            let to_ban: Vec<(UserId, String)> = targets
+                .into_iter()
+                .filter(|(id, _, _)| selected.contains(id))
+                .map(|(id, _, reason)| (id, format!("FBT Security: {reason}")))
+                .collect();
+
+            let results = bulk_ban(ctx, ctx.guild_id().unwrap(), to_ban, 0).await;
+
+            send_ban_report(ctx, &results, None).await?;
+
+            return Ok(());
+        } else if pressed == cancel_id {
+            press
+                .create_interaction_response(ctx, |r| {
+                    r.kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|d| {
+                            d.content("Cancelled, nobody was banned.").components(|c| c)
+                        })
+                })
+                .await?;
+
+            return Ok(());
+        }
+    }
+
+    // Timed out, don't leave buttons that look like they still work
+    prompt.edit(ctx, |b| b.components(|c| c)).await?;
+
     Ok(())
 }
 
-/// Process an entire CSV into the Redis database (V2.0)
+/// Import users from a CSV, JSON list or list of IDs into the Redis database (V3.0)
+///
+/// Shows a preview of what will change first and only writes once it's confirmed.
 #[cfg(feature = "database")]
 #[instrument(skip(ctx))]
 #[poise::command(
@@ -980,190 +1668,260 @@ pub async fn footprint_lookup(
     check = "bot_admin_check",
     guild_only
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn excel(
     ctx: Context<'_>,
-    #[description = "CSV file to upload"] csv_file: Attachment,
+    #[description = "File to upload, a .csv, .json, DiscordChatExporter export (.json or .zip) or a .txt of IDs"]
+    csv_file: Attachment,
     #[description = "ID of server the users are from"] guild_id: String,
     #[description = "Reason for being added to the DB"] reason: String,
+    #[description = "Category for everyone in this file (Overridden by a category column)"]
+    category: Option<OffenceCategory>,
+    #[description = "What kind of file this is (Default: worked out from the file name)"]
+    format: Option<ImportFormat>,
+    #[description = "Column with the user IDs (Default: AuthorID)"] id_column: Option<String>,
+    #[description = "Column with the usernames (Default: Author)"] name_column: Option<String>,
+    #[description = "Column with a reason for each user"] reason_column: Option<String>,
+    #[description = "Column with a category for each user"] category_column: Option<String>,
+    #[description = "Column with each user's VRChat ID"] vrc_column: Option<String>,
+    #[description = "Only show what would change (Default: false)"] dry_run: Option<bool>,
 ) -> Result<(), Error> {
     ctx.defer_or_broadcast().await?;
 
-    #[allow(clippy::case_sensitive_file_extension_comparisons)]
-    // Not actually case sensitive thanks to `.to_lowercase()`
-    if csv_file.url.to_lowercase().ends_with(".csv") {
-        let embed_message = ctx
-            .send(|b| {
-                b.embed(|e| {
-                    e.description("Uploading entried to DB now")
-                        .color(colours::css::WARNING)
-                        .thumbnail("https://media1.giphy.com/media/3o7bu3XilJ5BOiSGic/giphy.gif")
-                })
-            })
+    if parse_discord_id(&guild_id).is_none() {
+        ctx.say("The server ID doesn't look right, make sure you coppied the right one!")
             .await?;
+        return Ok(());
+    }
 
-        // Download file and convert to csv reader
-        let response = reqwest::get(csv_file.url).await?;
-        let csv_content = response.text().await?;
-        let mut csv_reader = csv::Reader::from_reader(csv_content.as_bytes());
-
-        // Start timer
-        let start = tokio::time::Instant::now();
-
-        let mut records = Vec::new();
-
-        // Converts each line csv into a CsvEntry object
-        for record in csv_reader.deserialize() {
-            let entry: CsvEntry = match record {
-                Err(err) => {
-                    if verbose_mode() {
-                        ctx.send(|b| {
-                            b.embed(|e| {
-                            e.description(format!("Failed to read a line of the CSV because: {err:?}"))
-                            .color(colours::css::DANGER)
-                            .thumbnail("https://media0.giphy.com/media/TqiwHbFBaZ4ti/giphy.gif?cid=ecf05e476gqwddci6tjtal5ohkp9ql3tq3m3scilolen1jh8&rid=giphy.gif&ct=g")
-                            })
-                            }).await?;
-                    } else {
-                        ctx.say("Failed to read a line of the CSV, probably a , in someone's name.\nYou can probably ignore this.".to_string())
-                            .await?;
-                    }
-                    CsvEntry {
-                        AuthorID: "0".to_string(),
-                        Author: "0".to_string(),
-                    }
-                }
-                Ok(out) => out,
-            };
+    let defaults = ColumnMapping::default();
+    let mapping = ColumnMapping {
+        id: id_column.unwrap_or(defaults.id),
+        name: name_column.unwrap_or(defaults.name),
+        reason: reason_column,
+        category: category_column,
+        vrc_id: vrc_column,
+    };
 
-            if entry.AuthorID == *"0" && entry.Author == *"0" {
-                continue;
-            }
+    // Download file
+    let content = csv_file.download().await?;
+
+    let format = format.unwrap_or_else(|| detect_import_format(&csv_file.filename, &content));
+
+    let parsed = match format {
+        ImportFormat::DiscordChatExporter => parse_dce_import(&content),
+        text_format => match std::str::from_utf8(&content) {
+            Err(_) => Err(
+                "That file isn't text, zipped exports only work as DiscordChatExporter exports"
+                    .into(),
+            ),
+            Ok(text) => match text_format {
+                ImportFormat::Csv => parse_csv_import(text, &mapping),
+                ImportFormat::Json => parse_json_import(text, &mapping),
+                _ => Ok(parse_id_list_import(text)),
+            },
+        },
+    };
 
-            records.push(entry);
+    let (records, errors) = match parsed {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            ctx.say(format!("I couldn't read that file: {error}"))
+                .await?;
+            return Ok(());
         }
+    };
 
-        #[allow(clippy::iter_with_drain)]
-        let records_set: HashSet<_> = records.drain(..).collect(); // dedup
-        records.extend(records_set.into_iter());
-
-        // End conversion
-
-        let mut con = open_redis_connection().await?;
-        let mut pipe = redis::pipe();
-
-        let mut users = Vec::new();
-        let mut user_ids = Vec::new();
-
-        for entry in records {
-            // TODO: Add more checks once other lists are setup?
-            if !BOT_IDS.contains(&entry.AuthorID.parse::<u64>()?)
-                || BOT_ADMINS.contains(&entry.AuthorID.parse::<u64>()?)
-            {
-                let offense = vec![Offense {
-                    guild_id: guild_id.parse().expect("Invalid Guild ID"),
-                    reason: reason.clone(),
-                    image: None,
-                    extra: None,
-                }];
-
-                let user = UserInfo {
-                    vrc_id: None,
-                    username: Some(entry.Author),
-                    discord_id: Some(entry.AuthorID),
-                    offences: offense,
-                };
-
-                users.push(user.clone());
-                user_ids.push(user.discord_id);
-            }
-        }
+    run_import(
+        ctx,
+        records,
+        errors,
+        guild_id.trim(),
+        &reason,
+        category,
+        dry_run.unwrap_or(false),
+    )
+    .await
+}
 
-        #[allow(clippy::iter_with_drain)]
-        let user_ids_set: HashSet<_> = user_ids.drain(..).collect(); // dedup
-        user_ids.extend(user_ids_set.into_iter());
+/// Previews an import, waits for it to be confirmed and then writes it to the DB
+#[cfg(feature = "database")]
+async fn run_import(
+    ctx: Context<'_>,
+    records: Vec<ImportRecord>,
+    errors: Vec<String>,
+    guild_id: &str,
+    reason: &str,
+    category: Option<OffenceCategory>,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
 
-        let actual_count = user_ids.len() as u64;
+    let whitelist: HashSet<String> = redis::cmd("SMEMBERS")
+        .arg("kick-whitelist")
+        .clone()
+        .query_async(&mut con)
+        .await?;
+    let mut whitelist: HashSet<u64> = whitelist.iter().filter_map(|id| id.parse().ok()).collect();
 
-        let mut combined_users = Vec::new();
+    // The bot's own staff never get imported
+    whitelist.extend(get_all_staff(&mut con).await?.into_iter().map(|(id, _)| id));
 
-        let key_list: Vec<String> = redis::cmd("KEYS")
-            .arg("user:*")
-            .clone()
-            .query_async(&mut con)
-            .await?;
+    let existing = get_existing_users(&mut con, &records).await?;
+    let plan = plan_import(records, &existing, &whitelist, guild_id, reason);
 
-        let mut key_pipe = redis::pipe();
+    let names = |records: &[ImportRecord]| {
+        records
+            .iter()
+            .map(|r| format!("{} {}", r.discord_id, r.username.as_deref().unwrap_or("")))
+            .collect::<Vec<String>>()
+    };
 
-        for key in key_list {
-            key_pipe.cmd("JSON.GET").arg(key);
-        }
+    let preview = format!(
+        "New users:\n{}\n\nAlready in DB, will get this offence added:\n{}\n\nAlready in DB for this, skipped:\n{}\n\nAlready in DB, will get their VRChat ID filled in:\n{}\n\nSkipped bots:\n{}\n\nSkipped whitelisted users:\n{}\n\nLines that couldn't be read:\n{}",
+        names(&plan.new).join("\n"),
+        names(&plan.existing).join("\n"),
+        names(&plan.already_listed).join("\n"),
+        names(&plan.vrc_ids_filled).join("\n"),
+        names(&plan.skipped_bots).join("\n"),
+        names(&plan.skipped_whitelisted).join("\n"),
+        errors.join("\n")
+    );
 
-        let old_users: Vec<String> = key_pipe.atomic().query_async(&mut con).await?;
+    let sample = names(&plan.new)
+        .into_iter()
+        .take(10)
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let preview_embed = |e: &mut CreateEmbed, title: &str, colour: Colour| {
+        e.title(title)
+            .color(colour)
+            .field("New users", plan.new.len(), true)
+            .field("Offence added", plan.existing.len(), true)
+            .field("Already listed", plan.already_listed.len(), true)
+            .field("VRChat IDs filled in", plan.vrc_ids_filled.len(), true)
+            .field("Skipped bots", plan.skipped_bots.len(), true)
+            .field("Skipped whitelisted", plan.skipped_whitelisted.len(), true)
+            .field("Unreadable lines", errors.len(), true)
+            .field(
+                "First new users",
+                if sample.is_empty() {
+                    "None".to_string()
+                } else {
+                    format!("```{sample}```")
+                },
+                false,
+            )
+            .footer(|f| f.text("The attached file has everyone in each group"));
+    };
 
-        for old_user in old_users {
-            let user: UserInfo = serde_json::from_str(&old_user)?;
+    let ctx_id = ctx.id();
+    let confirm_id = format!("{ctx_id}-import-confirm");
+    let cancel_id = format!("{ctx_id}-import-cancel");
+    let nothing_to_do =
+        plan.new.is_empty() && plan.existing.is_empty() && plan.vrc_ids_filled.is_empty();
+
+    let reply = ctx
+        .send(|b| {
+            b.embed(|e| {
+                preview_embed(e, "Import preview", colours::css::WARNING);
+                e
+            })
+            .attachment(AttachmentType::Bytes {
+                data: std::borrow::Cow::Borrowed(preview.as_bytes()),
+                filename: format!("{ctx_id}_import_preview.txt"),
+            });
+
+            if !dry_run && !nothing_to_do {
+                b.components(|c| {
+                    c.create_action_row(|r| {
+                        r.create_button(|b| {
+                            b.custom_id(&confirm_id)
+                                .label("Import")
+                                .style(ButtonStyle::Danger)
+                        })
+                        .create_button(|b| {
+                            b.custom_id(&cancel_id)
+                                .label("Cancel")
+                                .style(ButtonStyle::Secondary)
+                        })
+                    })
+                });
+            }
 
-            users.push(user);
-        }
+            b
+        })
+        .await?;
 
-        for id in user_ids {
-            let matches: Vec<UserInfo> = users
-                .clone()
-                .into_iter()
-                .filter(|u| u.discord_id == id)
-                .collect();
+    if dry_run || nothing_to_do {
+        return Ok(());
+    }
 
-            let mut out_user = UserInfo {
-                vrc_id: None,
-                username: None,
-                discord_id: None,
-                offences: Vec::new(),
-            };
+    let press = CollectComponentInteraction::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+        .timeout(Duration::from_secs(PAGINATION_TIMEOUT_SECS))
+        .await;
 
-            // Merge every match of this user into out_user
-            for user in matches {
-                out_user.merge(user);
-            }
-            let out_user_offences_set: HashSet<_> = out_user.offences.drain(..).collect(); // dedup
-            out_user.offences.extend(out_user_offences_set.into_iter());
+    let Some(press) = press.filter(|press| press.data.custom_id == confirm_id) else {
+        reply
+            .edit(ctx, |b| {
+                b.embed(|e| {
+                    preview_embed(e, "Import cancelled", colours::css::DANGER);
+                    e
+                })
+                .components(|c| c)
+            })
+            .await?;
+        return Ok(());
+    };
 
-            combined_users.push(out_user);
-        }
+    press
+        .create_interaction_response(ctx, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| {
+                    d.embed(|e| {
+                        e.description("Uploading entried to DB now")
+                            .color(colours::css::WARNING)
+                            .thumbnail(
+                                "https://media1.giphy.com/media/3o7bu3XilJ5BOiSGic/giphy.gif",
+                            )
+                    })
+                    .components(|c| c)
+                })
+        })
+        .await?;
 
-        for user in combined_users {
-            let json_user = serde_json::to_string(&user).unwrap();
-
-            // Queue up JSON.SET commands
-            pipe.cmd("JSON.SET").arg(&[
-                format!(
-                    "user:{}",
-                    user.discord_id.expect("Invalid Discord ID in entry")
-                ),
-                "$".to_string(),
-                json_user,
-            ]);
-        }
+    // Start timer
+    let start = tokio::time::Instant::now();
 
-        // Upload all entries at once
-        pipe.atomic().query_async(&mut con).await?;
+    let actual_count = commit_import(
+        &mut con,
+        &plan,
+        guild_id,
+        reason,
+        category,
+        &ctx.author().id.to_string(),
+    )
+    .await?;
 
-        // End timer
-        let duration = start.elapsed();
+    // End timer
+    let duration = start.elapsed();
 
-        embed_message.edit(ctx, |b| {
+    reply.edit(ctx, |b| {
         b.embed(|e| {
-        e.description("Completed upload!".to_string())
-        .color(colours::css::POSITIVE)
-        .thumbnail("https://media3.giphy.com/media/mJHSkWKziszrkcNJPo/giphy.gif?cid=ecf05e47sli83f591onowkgacia9xezewha5pcoj6651yszz&rid=giphy.gif&ct=g")
-        .fields([
-        ("Upload time", format!("{duration:?}"), false),
-        ("Entries processed", format!("{actual_count}"), false)
-        ])
+            e.description("Completed upload!".to_string())
+            .color(colours::css::POSITIVE)
+            .thumbnail("https://media3.giphy.com/media/mJHSkWKziszrkcNJPo/giphy.gif?cid=ecf05e47sli83f591onowkgacia9xezewha5pcoj6651yszz&rid=giphy.gif&ct=g")
+            .fields([
+                ("Upload time", format!("{duration:?}"), false),
+                ("Entries processed", format!("{actual_count}"), false)
+            ])
         })
-        }).await?;
-    } else {
-        ctx.say("The file needs to be a `.csv`!").await?;
-    }
+        .components(|c| c)
+    }).await?;
 
     Ok(())
 }
@@ -1187,7 +1945,7 @@ pub async fn key(ctx: Context<'_>) -> Result<(), Error> {
 #[poise::command(
     prefix_command,
     slash_command,
-    check = "bot_admin_check",
+    check = "reviewer_check",
     category = "DB"
 )]
 pub async fn whitelist(ctx: Context<'_>, user_id: String) -> Result<(), Error> {
@@ -1215,3 +1973,233 @@ pub async fn whitelist(ctx: Context<'_>, user_id: String) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Export or restore a full copy of the DB
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    category = "DB",
+    check = "owner_check",
+    subcommands("backup_export", "backup_restore")
+)]
+pub async fn backup(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say(
+        "Use `/backup export` to download a backup or `/backup restore` to merge one into the DB.",
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Download everything in the DB as a backup file
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    category = "DB",
+    check = "owner_check",
+    rename = "export"
+)]
+pub async fn backup_export(
+    ctx: Context<'_>,
+    #[description = "File format (Default: JSON)"] format: Option<BackupFormat>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    let format = format.unwrap_or(BackupFormat::Json);
+
+    let mut con = open_redis_connection().await?;
+    let backup = collect_backup(&mut con).await?;
+    let file = write_backup(&backup, format)?;
+
+    let extension = match format {
+        BackupFormat::Json => "json",
+        BackupFormat::Jsonl => "jsonl",
+    };
+
+    ctx.send(|b| {
+        b.content(format!(
+            "Backup of {} users, {} guild settings and {} feedback.",
+            backup.users.len(),
+            backup.guild_settings.len(),
+            backup.feedback.len()
+        ))
+        .attachment(AttachmentType::Bytes {
+            data: std::borrow::Cow::Borrowed(file.as_bytes()),
+            filename: format!("backup_{}.{extension}", backup.created_at),
+        })
+        .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Merge a backup file into the DB, nothing already in the DB is removed
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    category = "DB",
+    check = "owner_check",
+    rename = "restore"
+)]
+pub async fn backup_restore(
+    ctx: Context<'_>,
+    #[description = "Backup file made by /backup export"] file: Attachment,
+    #[description = "Only report what would change (Default: false)"] dry_run: Option<bool>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    let dry_run = dry_run.unwrap_or(false);
+
+    let content = String::from_utf8(file.download().await?)?;
+    let backup = read_backup(&content)?;
+
+    let mut con = open_redis_connection().await?;
+    let report = restore_backup(&mut con, &backup, dry_run).await?;
+
+    event!(
+        Level::INFO,
+        "Backup restored" = %file.filename,
+        "Dry run" = dry_run,
+        "By" = ctx.author().id.as_u64()
+    );
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(if dry_run {
+                "Restore preview (nothing was written)"
+            } else {
+                "Backup restored"
+            })
+            .description(describe_restore(&report))
+            .field("Backup made:", format!("<t:{}:F>", backup.created_at), true)
+            .field("Version:", backup.version, true)
+            .color(colours::css::POSITIVE)
+        })
+        .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Mark someone in the DB as cleared after looking into them
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "DB",
+    check = "reviewer_check"
+)]
+pub async fn clear(
+    ctx: Context<'_>,
+    #[description = "ID of the user to clear"] user_id: String,
+    #[description = "Why they were cleared"] reason: String,
+    #[description = "Where they were found (Default: this server's name)"] where_found: Option<
+        String,
+    >,
+) -> Result<(), Error> {
+    let Some(uid) = parse_discord_id(&user_id) else {
+        ctx.say("Make sure you supplied a user ID").await?;
+        return Ok(());
+    };
+
+    let username = match UserId(uid.parse::<u64>()?).to_user(ctx).await {
+        Ok(user) => user.tag(),
+        Err(_) => "NoUsernameFoundInDB".to_string(),
+    };
+
+    let cleared = ClearedUser {
+        user_id: uid.clone(),
+        username,
+        where_found: where_found
+            .or_else(|| ctx.guild().map(|g| g.name))
+            .unwrap_or_else(|| "Unknown".to_string()),
+        reason,
+    };
+
+    let mut con = open_redis_connection().await?;
+
+    redis::cmd("JSON.SET")
+        .arg(format!("cleared-user:{uid}"))
+        .arg("$".to_string())
+        .arg(serde_json::to_string(&cleared)?)
+        .query_async::<_, ()>(&mut con)
+        .await?;
+
+    fire_webhook_event(
+        &mut con,
+        WebhookEvent::UserCleared,
+        None,
+        serde_json::json!({
+            "discord_id": cleared.user_id,
+            "username": cleared.username,
+            "reason": cleared.reason,
+            "cleared_by": ctx.author().id.to_string(),
+        }),
+    )
+    .await?;
+
+    ctx.say(format!(
+        "<@{uid}> has been cleared.\nReason: {}",
+        cleared.reason
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Delete a piece of evidence from the evidence store and every offence it's attached to
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "DB",
+    check = "bot_admin_check"
+)]
+pub async fn purge_evidence_file(
+    ctx: Context<'_>,
+    #[description = "Evidence link or hash from search"] evidence: String,
+    #[description = "Why it's being purged"]
+    #[rest]
+    reason: String,
+) -> Result<(), Error> {
+    let Some(sha256) = parse_evidence_hash(&evidence) else {
+        ctx.say("Make sure you supplied an evidence link or hash from `search`")
+            .await?;
+        return Ok(());
+    };
+
+    ctx.defer_ephemeral().await?;
+
+    let mut con = open_redis_connection().await?;
+    let changed = purge_evidence(&mut con, &sha256, &evidence).await?;
+
+    event!(
+        Level::INFO,
+        "Evidence purged" = sha256,
+        "Users changed" = changed.len(),
+        "Reason" = reason,
+        "By" = ctx.author().id.as_u64()
+    );
+
+    if changed.is_empty() {
+        ctx.say(format!(
+            "No offences use `{sha256}`, it was still deleted from the evidence store in case it was there."
+        ))
+        .await?;
+    } else {
+        ctx.say(format!(
+            "Purged `{sha256}` from the evidence store and {} user(s) offences.",
+            changed.len()
+        ))
+        .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,340 @@
+use poise::serenity_prelude::colours;
+use rusted_fbt_lib::checks::owner_check;
+use rusted_fbt_lib::enums::PeerTrust;
+use rusted_fbt_lib::federation::{
+    get_all_peers, get_peer, get_quarantine, queue_federation_sync, remove_peer,
+    review_quarantined, save_peer,
+};
+use rusted_fbt_lib::structs::FederationPeer;
+use rusted_fbt_lib::types::{Context, Error};
+use rusted_fbt_lib::utils::{open_redis_connection, truncate_text};
+use rusted_fbt_lib::vars::FEDERATION_INSTANCE_ID;
+use tracing::instrument;
+use tracing::{event, Level};
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    category = "Admin",
+    check = "owner_check",
+    subcommands(
+        "federation_add_peer",
+        "federation_remove_peer",
+        "federation_trust",
+        "federation_peers",
+        "federation_quarantine",
+        "federation_review",
+        "federation_resync"
+    )
+)]
+/// Share blacklist entries with other instances of the bot
+pub async fn federation(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say(format!("This instance is `{FEDERATION_INSTANCE_ID}`, add other instances with `/federation add_peer` and give them that ID and the same secret."))
+        .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx, secret))]
+#[poise::command(
+    slash_command,
+    rename = "add_peer",
+    category = "Admin",
+    check = "owner_check"
+)]
+/// Add or update another instance of the bot to exchange entries with
+pub async fn federation_add_peer(
+    ctx: Context<'_>,
+    #[description = "The other instance's ID"] id: String,
+    #[description = "Base URL of the other instance's federation server"] url: String,
+    #[description = "Secret shared with the other instance"] secret: String,
+    #[description = "What to do with entries they send (Default: Quarantine)"] trust: Option<
+        PeerTrust,
+    >,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        ctx.say("The URL needs to start with `http://` or `https://`.")
+            .await?;
+        return Ok(());
+    }
+
+    let mut con = open_redis_connection().await?;
+
+    let existing = get_peer(&mut con, &id).await?;
+
+    let peer = FederationPeer {
+        id: id.clone(),
+        url,
+        secret,
+        trust: trust
+            .or_else(|| existing.as_ref().map(|p| p.trust))
+            .unwrap_or(PeerTrust::Quarantine),
+        added_at: existing
+            .as_ref()
+            .map_or_else(|| chrono::Utc::now().timestamp(), |p| p.added_at),
+        last_sent: existing.as_ref().and_then(|p| p.last_sent),
+        last_received: existing.as_ref().and_then(|p| p.last_received),
+    };
+
+    save_peer(&mut con, &peer).await?;
+
+    event!(
+        Level::INFO,
+        "Federation peer saved" = id,
+        "Trust" = %peer.trust,
+        "By" = ctx.author().id.as_u64()
+    );
+
+    ctx.send(|b| {
+        b.content(format!(
+            "`{id}` is {} with trust `{}`. New entries will be sent to them from now on, use `/federation resync` to send everything already in the DB.",
+            if existing.is_some() { "updated" } else { "added" },
+            peer.trust
+        ))
+        .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "remove_peer",
+    category = "Admin",
+    check = "owner_check"
+)]
+/// Stop exchanging entries with an instance, anything of theirs still in quarantine is dropped
+pub async fn federation_remove_peer(
+    ctx: Context<'_>,
+    #[description = "The other instance's ID"] id: String,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    if get_peer(&mut con, &id).await?.is_none() {
+        ctx.say(format!("There's no peer called `{id}`.")).await?;
+        return Ok(());
+    }
+
+    remove_peer(&mut con, &id).await?;
+
+    ctx.say(format!(
+        "`{id}` removed. Entries already accepted from them stay in the DB."
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "trust",
+    category = "Admin",
+    check = "owner_check"
+)]
+/// Change what happens to entries from an instance
+pub async fn federation_trust(
+    ctx: Context<'_>,
+    #[description = "The other instance's ID"] id: String,
+    #[description = "What to do with entries they send"] trust: PeerTrust,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let Some(mut peer) = get_peer(&mut con, &id).await? else {
+        ctx.say(format!("There's no peer called `{id}`.")).await?;
+        return Ok(());
+    };
+
+    peer.trust = trust;
+    save_peer(&mut con, &peer).await?;
+
+    ctx.say(format!(
+        "Entries from `{id}` will now be handled with `{trust}`."
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "peers",
+    category = "Admin",
+    check = "owner_check"
+)]
+/// List every instance entries are exchanged with
+pub async fn federation_peers(ctx: Context<'_>) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let peers = get_all_peers(&mut con).await?;
+
+    if peers.is_empty() {
+        ctx.say("There are no federation peers yet.").await?;
+        return Ok(());
+    }
+
+    let timestamp =
+        |time: Option<i64>| time.map_or_else(|| "Never".to_string(), |t| format!("<t:{t}:R>"));
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(format!("Federation peers of `{FEDERATION_INSTANCE_ID}`"))
+                .color(colours::css::POSITIVE);
+
+            for peer in peers.iter().take(25) {
+                e.field(
+                    &peer.id,
+                    format!(
+                        "{}\nTrust: `{}`\nLast sent: {}\nLast received: {}",
+                        peer.url,
+                        peer.trust,
+                        timestamp(peer.last_sent),
+                        timestamp(peer.last_received)
+                    ),
+                    false,
+                );
+            }
+            e
+        })
+        .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "quarantine",
+    category = "Admin",
+    check = "owner_check"
+)]
+/// List entries from other instances that are waiting for review
+pub async fn federation_quarantine(
+    ctx: Context<'_>,
+    #[description = "Only show entries from this peer"] peer: Option<String>,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let quarantine = get_quarantine(&mut con, peer.as_deref()).await?;
+
+    if quarantine.is_empty() {
+        ctx.say("Nothing is waiting for review.").await?;
+        return Ok(());
+    }
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(format!("{} entries waiting for review", quarantine.len()))
+                .description("Accept or reject them with `/federation review`.")
+                .color(colours::css::WARNING);
+
+            for item in quarantine.iter().take(25) {
+                let reasons = item
+                    .user
+                    .offences
+                    .iter()
+                    .map(|o| format!("- {}", o.reason))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+
+                e.field(
+                    format!(
+                        "{} ({}) from {}",
+                        item.user.username.as_deref().unwrap_or("Unknown"),
+                        item.user.discord_id.as_deref().unwrap_or("Unknown"),
+                        item.peer
+                    ),
+                    truncate_text(&reasons, 1024),
+                    false,
+                );
+            }
+            e
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "review",
+    category = "Admin",
+    check = "owner_check"
+)]
+/// Accept or reject an entry another instance sent
+pub async fn federation_review(
+    ctx: Context<'_>,
+    #[description = "The peer that sent it"] peer: String,
+    #[description = "Discord ID of the user"] user_id: String,
+    #[description = "Add it to the DB (true) or throw it away (false)"] accept: bool,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    if !review_quarantined(&mut con, &peer, &user_id, accept).await? {
+        ctx.say(format!("Nothing from `{peer}` is waiting for `{user_id}`."))
+            .await?;
+        return Ok(());
+    }
+
+    event!(
+        Level::INFO,
+        "Federation entry reviewed" = user_id,
+        "Peer" = peer,
+        "Accepted" = accept,
+        "By" = ctx.author().id.as_u64()
+    );
+
+    if accept {
+        ctx.say(format!(
+            "`{user_id}` from `{peer}` has been added to the DB."
+        ))
+        .await?;
+    } else {
+        ctx.say(format!("`{user_id}` from `{peer}` has been thrown away."))
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "resync",
+    category = "Admin",
+    check = "owner_check"
+)]
+/// Send every entry added on this instance to all peers again
+pub async fn federation_resync(ctx: Context<'_>) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let keys: Vec<String> = redis::cmd("KEYS")
+        .arg("user:*")
+        .clone()
+        .query_async(&mut con)
+        .await?;
+
+    let ids: Vec<String> = keys
+        .iter()
+        .map(|key| key.trim_start_matches("user:").to_string())
+        .collect();
+
+    queue_federation_sync(&mut con, &ids).await?;
+
+    ctx.say(format!(
+        "Queued {} users to be sent to every peer, only offences added on this instance are shared.",
+        ids.len()
+    ))
+    .await?;
+
+    Ok(())
+}
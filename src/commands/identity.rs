@@ -0,0 +1,266 @@
+use poise::serenity_prelude::colours;
+use rusted_fbt_lib::checks::{guild_auth_check, reviewer_check};
+use rusted_fbt_lib::identity::{get_identity_of, link_account, unlink_account};
+use rusted_fbt_lib::import::parse_discord_id;
+use rusted_fbt_lib::structs::Identity;
+use rusted_fbt_lib::types::{Context, Error};
+use rusted_fbt_lib::utils::{open_redis_connection, truncate_text};
+use rusted_fbt_lib::vrchat::normalize_vrc_id;
+use tracing::instrument;
+use tracing::{event, Level};
+
+/// Turns a Discord ID, VRChat ID or VRChat profile link into the form identities store it as
+fn parse_account(account: &str) -> Option<String> {
+    parse_discord_id(account).or_else(|| normalize_vrc_id(account))
+}
+
+/// Shows an account the way Discord IDs and VRChat IDs are normally shown
+fn describe_account(account: &str) -> String {
+    if account.starts_with("usr_") {
+        format!("VRChat `{account}`")
+    } else {
+        format!("<@{account}> (`{account}`)")
+    }
+}
+
+/// Sends everything linked in an identity
+async fn send_identity(ctx: Context<'_>, identity: &Identity) -> Result<(), Error> {
+    let lines = identity
+        .accounts
+        .iter()
+        .map(|a| {
+            format!(
+                "{} - linked <t:{}:R> by <@{}>: {}",
+                describe_account(&a.account),
+                a.linked_at,
+                a.linked_by,
+                truncate_text(&a.evidence, 200)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(format!("Identity #{}", identity.id))
+                .field("Main account:", describe_account(&identity.primary), false)
+                .field(
+                    format!("Linked accounts ({}):", identity.accounts.len()),
+                    truncate_text(&lines, 1024),
+                    false,
+                )
+                .color(colours::css::POSITIVE)
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "DB",
+    check = "guild_auth_check",
+    guild_only,
+    subcommands("identity_link", "identity_unlink", "identity_show")
+)]
+/// Group a blacklisted user's alts together
+pub async fn identity(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Use `identity show` to see someone's linked accounts, reviewers can use `identity link` and `identity unlink`.")
+        .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "link",
+    category = "DB",
+    check = "reviewer_check"
+)]
+/// Link an alt to someone's main account
+pub async fn identity_link(
+    ctx: Context<'_>,
+    #[description = "Discord ID of the main account"] main_id: String,
+    #[description = "Discord ID, VRChat ID or VRChat profile link of the alt"] alt: String,
+    #[description = "Why these accounts are the same person"]
+    #[rest]
+    evidence: String,
+) -> Result<(), Error> {
+    let Some(primary) = parse_discord_id(&main_id) else {
+        ctx.say("Make sure the main account is a Discord user ID")
+            .await?;
+        return Ok(());
+    };
+
+    let Some(account) = parse_account(&alt) else {
+        ctx.say("Make sure the alt is a Discord user ID, VRChat ID or VRChat profile link")
+            .await?;
+        return Ok(());
+    };
+
+    if primary == account {
+        ctx.say("An account can't be an alt of itself").await?;
+        return Ok(());
+    }
+
+    if evidence.trim().is_empty() {
+        ctx.say("Say why these accounts are linked").await?;
+        return Ok(());
+    }
+
+    let mut con = open_redis_connection().await?;
+
+    let main_identity = get_identity_of(&mut con, &primary).await?;
+
+    if let Some(identity) = main_identity.as_ref().filter(|i| i.primary != primary) {
+        ctx.say(format!(
+            "{} is already an alt of {}, link to that account instead.",
+            describe_account(&primary),
+            describe_account(&identity.primary)
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    if let Some(identity) = get_identity_of(&mut con, &account).await? {
+        if Some(identity.id) != main_identity.map(|i| i.id) {
+            ctx.say(format!(
+                "{} is already linked to {}, unlink it first.",
+                describe_account(&account),
+                describe_account(&identity.primary)
+            ))
+            .await?;
+            return Ok(());
+        }
+    }
+
+    let identity = link_account(
+        &mut con,
+        &primary,
+        &account,
+        evidence.trim(),
+        &ctx.author().id.to_string(),
+    )
+    .await?;
+
+    event!(
+        Level::INFO,
+        "Account linked" = account,
+        "Main account" = primary,
+        "By" = ctx.author().id.as_u64()
+    );
+
+    ctx.say(format!(
+        "{} is now linked as an alt of {}.",
+        describe_account(&account),
+        describe_account(&identity.primary)
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "unlink",
+    category = "DB",
+    check = "reviewer_check"
+)]
+/// Take an account out of the identity it's linked to
+pub async fn identity_unlink(
+    ctx: Context<'_>,
+    #[description = "Discord ID, VRChat ID or VRChat profile link to unlink"] account: String,
+    #[description = "Why it's being unlinked"]
+    #[rest]
+    reason: String,
+) -> Result<(), Error> {
+    let Some(account) = parse_account(&account) else {
+        ctx.say("Make sure you supplied a Discord user ID, VRChat ID or VRChat profile link")
+            .await?;
+        return Ok(());
+    };
+
+    let mut con = open_redis_connection().await?;
+
+    if get_identity_of(&mut con, &account).await?.is_none() {
+        ctx.say(format!(
+            "{} isn't linked to anyone.",
+            describe_account(&account)
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let remaining = unlink_account(&mut con, &account).await?;
+
+    event!(
+        Level::INFO,
+        "Account unlinked" = account,
+        "Reason" = reason,
+        "By" = ctx.author().id.as_u64()
+    );
+
+    match remaining {
+        Some(identity) => {
+            ctx.say(format!(
+                "{} has been unlinked, {} is the main account with {} linked account(s) left.",
+                describe_account(&account),
+                describe_account(&identity.primary),
+                identity.accounts.len()
+            ))
+            .await?;
+        }
+        None => {
+            ctx.say(format!(
+                "{} has been unlinked, the identity had nothing left to link so it was removed.",
+                describe_account(&account)
+            ))
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "show",
+    category = "DB",
+    member_cooldown = 5,
+    check = "guild_auth_check",
+    guild_only
+)]
+/// Show every account linked to someone
+pub async fn identity_show(
+    ctx: Context<'_>,
+    #[description = "Discord ID, VRChat ID or VRChat profile link"] account: String,
+) -> Result<(), Error> {
+    let Some(account) = parse_account(&account) else {
+        ctx.say("Make sure you supplied a Discord user ID, VRChat ID or VRChat profile link")
+            .await?;
+        return Ok(());
+    };
+
+    let mut con = open_redis_connection().await?;
+
+    match get_identity_of(&mut con, &account).await? {
+        Some(identity) => send_identity(ctx, &identity).await,
+        None => {
+            ctx.say(format!(
+                "{} isn't linked to anyone.",
+                describe_account(&account)
+            ))
+            .await?;
+            Ok(())
+        }
+    }
+}
@@ -1,12 +1,17 @@
-use poise::serenity_prelude::{ChannelId, Colour};
+use poise::serenity_prelude::{ChannelId, Colour, UserId};
 use rand::Rng;
 use rusted_fbt_lib::{
+    checks::support_check,
+    enums::FeedbackStatus,
+    feedback::{
+        feedback_buttons, feedback_embed, get_all_feedback, get_feedback, save_feedback,
+        update_feedback_post,
+    },
+    structs::{Feedback, FeedbackReply},
     types::{Context, Error},
-    utils::open_redis_connection,
+    utils::{is_dm_blocked, open_redis_connection, truncate_text},
     vars::{FEEDBACK_CHANNEL_ID, HELP_EXTRA_TEXT, VERSION},
 };
-#[cfg(feature = "database")]
-use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::instrument;
 use tracing::{event, Level};
 
@@ -34,49 +39,203 @@ pub async fn help(
 
 #[cfg(feature = "database")]
 #[instrument(skip(ctx))]
-#[poise::command(slash_command, category = "Info", member_cooldown = 10, ephemeral)]
+#[poise::command(
+    slash_command,
+    category = "Info",
+    subcommands("feedback_send", "feedback_list", "feedback_reply")
+)]
+/// Send feedback to the bot team, or look through it if you're staff
+pub async fn feedback(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Use `/feedback send` to send us your feedback!")
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "send",
+    category = "Info",
+    member_cooldown = 10,
+    ephemeral
+)]
 /// Provide feedback for the bot team to look at!
-pub async fn feedback(
+pub async fn feedback_send(
     ctx: Context<'_>,
     #[description = "Feedback you want to provide"] feedback: String,
 ) -> Result<(), Error> {
     ctx.defer_ephemeral().await?;
     let mut con = open_redis_connection().await?;
 
-    redis::cmd("SET")
-        .arg(format!(
-            "feedback:{}-{}-{}",
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            ctx.author().id.as_u64(),
-            ctx.author().tag()
-        ))
-        .arg(feedback.clone())
-        .clone()
+    let id: u64 = redis::cmd("INCR")
+        .arg("feedback-count")
         .query_async(&mut con)
         .await?;
 
-    let colour = &mut rand::thread_rng().gen_range(0..10_000_000);
-    ChannelId(FEEDBACK_CHANNEL_ID)
+    let mut entry = Feedback {
+        id,
+        user_id: ctx.author().id.to_string(),
+        username: ctx.author().tag(),
+        avatar_url: ctx.author().face(),
+        guild_id: ctx.guild_id().map(|g| g.to_string()),
+        message: feedback,
+        created_at: chrono::Utc::now().timestamp(),
+        status: FeedbackStatus::New,
+        message_id: None,
+        replies: Vec::new(),
+    };
+
+    let post = ChannelId(FEEDBACK_CHANNEL_ID)
         .send_message(ctx, |f| {
-            f.embed(|e| {
-                e.title("New feedback!".to_string())
-                    .description(feedback)
-                    .color(Colour::new(*colour))
-                    .author(|a| a.icon_url(ctx.author().face()).name(ctx.author().tag()))
-                    .thumbnail("https://media.giphy.com/media/U4sfHXAALLYBQzPcWk/giphy.gif")
-            })
+            f.embed(|e| feedback_embed(e, &entry))
+                .components(|c| feedback_buttons(c, &entry))
         })
         .await?;
 
+    entry.message_id = Some(post.id.to_string());
+    save_feedback(&mut con, &entry).await?;
+
     ctx.say("Thank you for the feedback! It has been sent directly to our developers.")
         .await?;
 
     Ok(())
 }
 
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "list",
+    category = "Info",
+    check = "support_check",
+    ephemeral
+)]
+/// List feedback that still needs looking at
+pub async fn feedback_list(
+    ctx: Context<'_>,
+    #[description = "Only show feedback with this status (Default: New and Acknowledged)"]
+    status: Option<FeedbackStatus>,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let lines = get_all_feedback(&mut con)
+        .await?
+        .into_iter()
+        .filter(|f| {
+            status.map_or(f.status != FeedbackStatus::Done, |status| {
+                f.status == status
+            })
+        })
+        .rev()
+        .take(20)
+        .map(|f| {
+            let mut message = f.message.replace('\n', " ");
+            if message.chars().count() > 80 {
+                message = format!("{}...", message.chars().take(77).collect::<String>());
+            }
+
+            format!("#{} [{}] `{}`: {message}", f.id, f.status, f.username)
+        })
+        .collect::<Vec<String>>();
+
+    if lines.is_empty() {
+        ctx.say("There is no feedback to look at!").await?;
+        return Ok(());
+    }
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title("Feedback inbox")
+                .description(lines.join("\n"))
+                .footer(|f| f.text("Use /feedback reply to answer someone"))
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "database")]
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "reply",
+    category = "Info",
+    check = "support_check",
+    ephemeral
+)]
+/// DM a reply to whoever sent a piece of feedback
+pub async fn feedback_reply(
+    ctx: Context<'_>,
+    #[description = "Feedback number"] id: u64,
+    #[description = "Reply to send them"]
+    #[max_length = 4000]
+    message: String,
+    #[description = "Mark the feedback as done (Default: false)"] mark_done: Option<bool>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let mut con = open_redis_connection().await?;
+
+    let Some(mut entry) = get_feedback(&mut con, id).await? else {
+        ctx.say(format!("There is no feedback #{id}.")).await?;
+        return Ok(());
+    };
+
+    let dm = UserId(entry.user_id.parse()?)
+        .create_dm_channel(ctx)
+        .await?
+        .send_message(ctx, |m| {
+            m.embed(|e| {
+                e.title("Reply to your feedback")
+                    .description(&message)
+                    .color(Colour::BLURPLE)
+                    .field("You said:", truncate_text(&entry.message, 1024), false)
+                    .footer(|f| f.text(format!("Feedback #{id}")))
+            })
+        })
+        .await;
+
+    if let Err(error) = dm {
+        if is_dm_blocked(&error) {
+            ctx.say(format!(
+                "I couldn't DM `{}`, they have DMs turned off or blocked the bot.",
+                entry.username
+            ))
+            .await?;
+        } else {
+            ctx.say(format!("I couldn't DM `{}`: {error}", entry.username))
+                .await?;
+        }
+        return Ok(());
+    }
+
+    entry.replies.push(FeedbackReply {
+        staff_id: ctx.author().id.to_string(),
+        staff_name: ctx.author().tag(),
+        message,
+        sent_at: chrono::Utc::now().timestamp(),
+    });
+
+    if mark_done.unwrap_or(false) {
+        entry.status = FeedbackStatus::Done;
+    } else if entry.status == FeedbackStatus::New {
+        entry.status = FeedbackStatus::Acknowledged;
+    }
+
+    save_feedback(&mut con, &entry).await?;
+    update_feedback_post(&ctx.serenity_context().http, &entry).await?;
+
+    ctx.say(format!(
+        "Sent your reply to `{}`! Feedback #{id} is now {}.",
+        entry.username, entry.status
+    ))
+    .await?;
+
+    Ok(())
+}
+
 #[instrument(skip(ctx))]
 #[poise::command(prefix_command, slash_command, category = "Info", member_cooldown = 10)]
 /// Have some info about the bot
@@ -1,6 +1,20 @@
 pub mod admin;
+#[cfg(feature = "api")]
+pub mod api;
+#[cfg(feature = "database")]
+pub mod botban;
 pub mod database;
+#[cfg(feature = "federation")]
+pub mod federation;
 pub mod fun;
+#[cfg(feature = "database")]
+pub mod identity;
 pub mod info;
+#[cfg(feature = "database")]
+pub mod moderation;
+#[cfg(feature = "database")]
+pub mod staff;
 pub mod tickets;
 pub mod tools;
+#[cfg(feature = "database")]
+pub mod webhooks;
@@ -0,0 +1,560 @@
+use chrono::Utc;
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, Colour, CreateEmbed, Member, Timestamp,
+};
+use rusted_fbt_lib::checks::guild_manager_check;
+use rusted_fbt_lib::enums::ModAction;
+use rusted_fbt_lib::structs::ModCase;
+use rusted_fbt_lib::types::{Context, Error};
+use rusted_fbt_lib::utils::{
+    audit_log_reason, get_guild_settings, open_redis_connection, parse_duration,
+    set_guild_settings, truncate_text, verbose_mode,
+};
+use tracing::instrument;
+use tracing::{event, Level};
+
+const NO_REASON: &str = "No reason given";
+
+/// Fills out an embed describing a moderation case
+fn case_embed<'a>(e: &'a mut CreateEmbed, case: &ModCase) -> &'a mut CreateEmbed {
+    let colour = match case.action {
+        ModAction::Warn => Colour::new(0x00FE_E75C),
+        ModAction::Timeout => Colour::new(0x00FA_A81A),
+        ModAction::Kick | ModAction::Softban | ModAction::Ban => Colour::new(0x00ED_4245),
+        ModAction::Unban => Colour::new(0x0057_F287),
+    };
+
+    e.title(format!("Case #{} | {}", case.case_id, case.action))
+        .field(
+            "Target",
+            format!("<@{0}>/{0} ({1})", case.target_id, case.target_name),
+            true,
+        )
+        .field("Moderator", format!("<@{0}>/{0}", case.moderator_id), true)
+        .field("Reason", truncate_text(&case.reason, 1024), false)
+        .field("Created", format!("<t:{}:f>", case.created_at), true)
+        .color(colour);
+
+    if let Some(duration) = &case.duration {
+        e.field("Duration", duration, true);
+    }
+
+    e
+}
+
+/// Reads a single case from the DB
+async fn get_case(
+    con: &mut redis::aio::MultiplexedConnection,
+    guild_id: u64,
+    case_id: u64,
+) -> Result<Option<ModCase>, Error> {
+    let case_json: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!("mod-case:{guild_id}:{case_id}"))
+        .clone()
+        .query_async(con)
+        .await?;
+
+    match case_json {
+        None => Ok(None),
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+    }
+}
+
+/// Writes a case to the DB, overwriting it if it already exists
+async fn save_case(
+    con: &mut redis::aio::MultiplexedConnection,
+    case: &ModCase,
+) -> Result<(), Error> {
+    redis::cmd("JSON.SET")
+        .arg(format!("mod-case:{}:{}", case.guild_id, case.case_id))
+        .arg("$".to_string())
+        .arg(serde_json::to_string(case)?)
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Gives the action the next case number for this guild, saves it and posts it to the guild's log channel if they have one
+async fn open_case(
+    ctx: Context<'_>,
+    action: ModAction,
+    target: &serenity::User,
+    reason: String,
+    duration: Option<String>,
+) -> Result<ModCase, Error> {
+    let guild_id = ctx.guild_id().expect("Not run inside guild");
+
+    let mut con = open_redis_connection().await?;
+
+    let case_id: u64 = redis::cmd("INCR")
+        .arg(format!("mod-case-count:{}", guild_id.as_u64()))
+        .query_async(&mut con)
+        .await?;
+
+    let mut case = ModCase {
+        case_id,
+        guild_id: guild_id.to_string(),
+        action,
+        moderator_id: ctx.author().id.to_string(),
+        target_id: target.id.to_string(),
+        target_name: target.tag(),
+        reason,
+        duration,
+        created_at: Utc::now().timestamp(),
+        log_message_id: None,
+    };
+
+    if let Some(settings) = get_guild_settings(&mut con, *guild_id.as_u64()).await? {
+        if let Some(log_channel) = settings.log_channel_id {
+            match ChannelId(log_channel.parse::<u64>()?)
+                .send_message(ctx, |f| f.embed(|e| case_embed(e, &case)))
+                .await
+            {
+                Ok(msg) => case.log_message_id = Some(msg.id.to_string()),
+                Err(error) => {
+                    event!(Level::WARN, "Failed to post case to log channel" = ?error);
+                }
+            }
+        }
+    }
+
+    save_case(&mut con, &case).await?;
+
+    Ok(case)
+}
+
+/// Lets the moderator know their action didn't go through
+async fn say_action_failed(
+    ctx: Context<'_>,
+    action: ModAction,
+    name: &str,
+    error: serenity::Error,
+) -> Result<(), Error> {
+    if verbose_mode() {
+        ctx.say(format!(
+            "Failed to {} {name} because of {error:?}",
+            action.to_string().to_lowercase()
+        ))
+        .await?;
+    } else {
+        ctx.say(format!(
+            "Failed to {} {name}",
+            action.to_string().to_lowercase()
+        ))
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Moderation",
+    required_permissions = "KICK_MEMBERS",
+    required_bot_permissions = "KICK_MEMBERS",
+    guild_only
+)]
+/// Kick a member and log it as a case
+pub async fn kick(
+    ctx: Context<'_>,
+    #[description = "Member to kick"] member: Member,
+    #[description = "Reason for the kick"]
+    #[rest]
+    reason: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let reason = reason.unwrap_or_else(|| NO_REASON.to_string());
+
+    if let Err(error) = member
+        .kick_with_reason(ctx, &audit_log_reason(&reason))
+        .await
+    {
+        return say_action_failed(ctx, ModAction::Kick, member.display_name().as_str(), error)
+            .await;
+    }
+
+    let case = open_case(ctx, ModAction::Kick, &member.user, reason, None).await?;
+
+    ctx.say(format!(
+        "Case #{}: {} has been kicked.",
+        case.case_id,
+        member.display_name()
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Moderation",
+    required_permissions = "MODERATE_MEMBERS",
+    required_bot_permissions = "MODERATE_MEMBERS",
+    guild_only
+)]
+/// Time out a member (up to 28 days) and log it as a case
+pub async fn timeout(
+    ctx: Context<'_>,
+    #[description = "Member to time out"] mut member: Member,
+    #[description = "How long for, like 30m, 12h or 7d (Max of 28d)"] duration: String,
+    #[description = "Reason for the timeout"]
+    #[rest]
+    reason: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let Some(length) = parse_duration(&duration) else {
+        ctx.say("I couldn't understand that duration, try something like `30m`, `12h` or `7d`.")
+            .await?;
+        return Ok(());
+    };
+
+    if length > chrono::Duration::days(28) {
+        ctx.say("Discord only allows timeouts of up to 28 days.")
+            .await?;
+        return Ok(());
+    }
+
+    let reason = reason.unwrap_or_else(|| NO_REASON.to_string());
+    let until = Timestamp::from_unix_timestamp((Utc::now() + length).timestamp())?;
+
+    if let Err(error) = member
+        .disable_communication_until_datetime(ctx, until)
+        .await
+    {
+        return say_action_failed(
+            ctx,
+            ModAction::Timeout,
+            member.display_name().as_str(),
+            error,
+        )
+        .await;
+    }
+
+    let case = open_case(
+        ctx,
+        ModAction::Timeout,
+        &member.user,
+        reason,
+        Some(duration.trim().to_string()),
+    )
+    .await?;
+
+    ctx.say(format!(
+        "Case #{}: {} has been timed out until <t:{}:f>.",
+        case.case_id,
+        member.display_name(),
+        until.unix_timestamp()
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Moderation",
+    required_permissions = "BAN_MEMBERS",
+    required_bot_permissions = "BAN_MEMBERS",
+    guild_only
+)]
+/// Unban a user and log it as a case
+pub async fn unban(
+    ctx: Context<'_>,
+    #[description = "User to unban. Must be a user ID."] user: serenity::User,
+    #[description = "Reason for the unban"]
+    #[rest]
+    reason: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let reason = reason.unwrap_or_else(|| NO_REASON.to_string());
+
+    if let Err(error) = ctx.guild_id().unwrap().unban(ctx, user.id).await {
+        return say_action_failed(ctx, ModAction::Unban, user.tag().as_str(), error).await;
+    }
+
+    let case = open_case(ctx, ModAction::Unban, &user, reason, None).await?;
+
+    ctx.say(format!(
+        "Case #{}: {} has been unbanned.",
+        case.case_id,
+        user.tag()
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Moderation",
+    required_permissions = "MODERATE_MEMBERS",
+    guild_only
+)]
+/// Warn a member, they get a DM and it gets logged as a case
+pub async fn warn(
+    ctx: Context<'_>,
+    #[description = "Member to warn"] member: Member,
+    #[description = "Reason for the warning"]
+    #[rest]
+    reason: String,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let guild_name = ctx
+        .guild()
+        .map_or_else(|| "a server".to_string(), |g| g.name);
+
+    let dm_sent = member
+        .user
+        .direct_message(ctx, |m| {
+            m.content(format!(
+                "You have been warned in {guild_name} for: {reason}"
+            ))
+        })
+        .await
+        .is_ok();
+
+    let case = open_case(ctx, ModAction::Warn, &member.user, reason, None).await?;
+
+    if dm_sent {
+        ctx.say(format!(
+            "Case #{}: {} has been warned.",
+            case.case_id,
+            member.display_name()
+        ))
+        .await?;
+    } else {
+        ctx.say(format!(
+            "Case #{}: {} has been warned, but I couldn't DM them about it.",
+            case.case_id,
+            member.display_name()
+        ))
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Moderation",
+    required_permissions = "BAN_MEMBERS",
+    required_bot_permissions = "BAN_MEMBERS",
+    guild_only
+)]
+/// Ban then instantly unban a member to clear out their messages, logged as a case
+pub async fn softban(
+    ctx: Context<'_>,
+    #[description = "Member to softban"] member: Member,
+    #[description = "How many days of messages to purge (Max of 7, defaults to 1)"]
+    #[min = 0]
+    #[max = 7]
+    dmd: Option<u8>,
+    #[description = "Reason for the softban"]
+    #[rest]
+    reason: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let reason = reason.unwrap_or_else(|| NO_REASON.to_string());
+    let guild_id = ctx.guild_id().unwrap();
+
+    if let Err(error) = guild_id
+        .ban_with_reason(
+            ctx,
+            member.user.id,
+            dmd.unwrap_or(1).min(7),
+            audit_log_reason(&format!("Softban: {reason}")),
+        )
+        .await
+    {
+        return say_action_failed(
+            ctx,
+            ModAction::Softban,
+            member.display_name().as_str(),
+            error,
+        )
+        .await;
+    }
+
+    if let Err(error) = guild_id.unban(ctx, member.user.id).await {
+        event!(Level::WARN, "Failed to unban after softban" = ?error);
+
+        // They're still banned so that's what goes on record
+        let case = open_case(ctx, ModAction::Ban, &member.user, reason, None).await?;
+
+        ctx.say(format!(
+            "Case #{}: {} was banned but I failed to unban them again, they've been left banned and it's been logged as a ban. Unban them by hand if that's not what you want!",
+            case.case_id,
+            member.display_name()
+        ))
+        .await?;
+
+        return Ok(());
+    }
+
+    let case = open_case(ctx, ModAction::Softban, &member.user, reason, None).await?;
+
+    ctx.say(format!(
+        "Case #{}: {} has been softbanned.",
+        case.case_id,
+        member.display_name()
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Moderation",
+    required_permissions = "KICK_MEMBERS",
+    guild_only,
+    subcommands("case_view", "case_reason", "case_log_channel")
+)]
+/// View and edit moderation cases
+pub async fn case(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Run `/case view` to see a case, `/case reason` to change a case's reason or `/case log_channel` to pick where new cases get posted.")
+        .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "view",
+    category = "Moderation",
+    required_permissions = "KICK_MEMBERS",
+    guild_only
+)]
+/// Show a moderation case
+pub async fn case_view(
+    ctx: Context<'_>,
+    #[description = "Case number"] case_id: u64,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    match get_case(&mut con, *ctx.guild_id().unwrap().as_u64(), case_id).await? {
+        None => {
+            ctx.say(format!("There is no case #{case_id} in this server."))
+                .await?;
+        }
+        Some(case) => {
+            ctx.send(|b| b.embed(|e| case_embed(e, &case))).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "reason",
+    category = "Moderation",
+    required_permissions = "KICK_MEMBERS",
+    guild_only
+)]
+/// Change the reason on a moderation case
+pub async fn case_reason(
+    ctx: Context<'_>,
+    #[description = "Case number"] case_id: u64,
+    #[description = "New reason"]
+    #[rest]
+    reason: String,
+) -> Result<(), Error> {
+    let guild_id = *ctx.guild_id().unwrap().as_u64();
+
+    let mut con = open_redis_connection().await?;
+
+    let Some(mut case) = get_case(&mut con, guild_id, case_id).await? else {
+        ctx.say(format!("There is no case #{case_id} in this server."))
+            .await?;
+        return Ok(());
+    };
+
+    case.reason = reason;
+    save_case(&mut con, &case).await?;
+
+    // Keep the copy in the log channel up to date too
+    if let (Some(message_id), Some(settings)) = (
+        case.log_message_id.clone(),
+        get_guild_settings(&mut con, guild_id).await?,
+    ) {
+        if let Some(log_channel) = settings.log_channel_id {
+            if let Err(error) = ChannelId(log_channel.parse::<u64>()?)
+                .edit_message(ctx, message_id.parse::<u64>()?, |m| {
+                    m.embed(|e| case_embed(e, &case))
+                })
+                .await
+            {
+                event!(Level::INFO, "Failed to edit logged case" = ?error);
+            }
+        }
+    }
+
+    ctx.say(format!("Updated the reason for case #{case_id}."))
+        .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "log_channel",
+    category = "Moderation",
+    check = "guild_manager_check",
+    guild_only
+)]
+/// Set the channel new cases get posted to, leave empty to stop posting them
+pub async fn case_log_channel(
+    ctx: Context<'_>,
+    #[description = "Channel to post cases in"] channel: Option<serenity::Channel>,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let Some(mut settings) =
+        get_guild_settings(&mut con, *ctx.guild_id().unwrap().as_u64()).await?
+    else {
+        ctx.say("Your server has not been setup yet! Run `/setup` first.")
+            .await?;
+        return Ok(());
+    };
+
+    settings.log_channel_id = channel.as_ref().map(|c| c.id().to_string());
+
+    set_guild_settings(ctx, &mut con, settings).await?;
+
+    match channel {
+        Some(c) => {
+            ctx.say(format!("New cases will now be posted in <#{}>.", c.id()))
+                .await?;
+        }
+        None => {
+            ctx.say("Cases will no longer be posted anywhere.").await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use poise::serenity_prelude::{self as serenity, colours};
+use rusted_fbt_lib::checks::{bot_admin_check, support_check};
+use rusted_fbt_lib::enums::StaffTier;
+use rusted_fbt_lib::staff::{get_all_staff, get_staff_tier, remove_staff, set_staff_tier};
+use rusted_fbt_lib::types::{Context, Error};
+use rusted_fbt_lib::utils::{open_redis_connection, truncate_text};
+use rusted_fbt_lib::vars::BOT_OWNERS;
+use tracing::instrument;
+use tracing::{event, Level};
+
+/// Checks the command user outranks someone enough to change their tier, owners can change anyone
+///
+/// Sends the reason and gives `false` if they can't.
+async fn can_change_staff(
+    ctx: Context<'_>,
+    con: &mut redis::aio::MultiplexedConnection,
+    user: &serenity::User,
+    tier: Option<StaffTier>,
+) -> Result<bool, Error> {
+    if BOT_OWNERS.contains(user.id.as_u64()) {
+        ctx.say(format!(
+            "{} is in `BOT_OWNERS` so they're always an owner, change it in `vars.rs` instead.",
+            user.tag()
+        ))
+        .await?;
+        return Ok(false);
+    }
+
+    let own_tier = get_staff_tier(con, *ctx.author().id.as_u64()).await?;
+
+    if own_tier == Some(StaffTier::Owner) {
+        return Ok(true);
+    }
+
+    if tier.is_some_and(|tier| tier >= StaffTier::Admin) {
+        ctx.say("Only owners can make someone an admin or owner.")
+            .await?;
+        return Ok(false);
+    }
+
+    if get_staff_tier(con, *user.id.as_u64()).await? >= own_tier {
+        ctx.say(format!(
+            "{} is at or above your tier, ask an owner.",
+            user.tag()
+        ))
+        .await?;
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Admin",
+    check = "support_check",
+    subcommands("staff_grant", "staff_revoke", "staff_list")
+)]
+/// Manage who's on the bot's staff
+pub async fn staff(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say(
+        "Use `staff list` to see the bot's staff, admins can use `staff grant` and `staff revoke`.",
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "grant",
+    category = "Admin",
+    check = "bot_admin_check"
+)]
+/// Give someone a staff tier, replacing the one they have
+pub async fn staff_grant(
+    ctx: Context<'_>,
+    #[description = "Who to give the tier to"] user: serenity::User,
+    #[description = "Tier to give them"] tier: StaffTier,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    if !can_change_staff(ctx, &mut con, &user, Some(tier)).await? {
+        return Ok(());
+    }
+
+    set_staff_tier(&mut con, *user.id.as_u64(), tier).await?;
+
+    event!(
+        Level::INFO,
+        "Staff tier granted" = user.id.as_u64(),
+        "Tier" = tier.to_string(),
+        "By" = ctx.author().id.as_u64()
+    );
+
+    ctx.say(format!("{} is now {tier}.", user.tag())).await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "revoke",
+    category = "Admin",
+    check = "bot_admin_check"
+)]
+/// Take someone off the bot's staff
+pub async fn staff_revoke(
+    ctx: Context<'_>,
+    #[description = "Who to take off the staff"] user: serenity::User,
+) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    if !can_change_staff(ctx, &mut con, &user, None).await? {
+        return Ok(());
+    }
+
+    if remove_staff(&mut con, *user.id.as_u64()).await? {
+        event!(
+            Level::INFO,
+            "Staff tier revoked" = user.id.as_u64(),
+            "By" = ctx.author().id.as_u64()
+        );
+
+        ctx.say(format!("{} is no longer on the staff.", user.tag()))
+            .await?;
+    } else {
+        ctx.say(format!("{} isn't on the staff.", user.tag()))
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "list",
+    category = "Admin",
+    check = "support_check"
+)]
+/// List everyone on the bot's staff and their tier
+pub async fn staff_list(ctx: Context<'_>) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let lines = get_all_staff(&mut con)
+        .await?
+        .iter()
+        .map(|(user_id, tier)| format!("{tier}: <@{user_id}>"))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title("Bot staff")
+                .description(truncate_text(&lines, 4096))
+                .footer(|f| {
+                    f.text("Support can answer feedback, reviewers can also clear and whitelist users, admins can also import users and send announcements and owners can run everything")
+                })
+                .color(colours::css::POSITIVE)
+        })
+        .allowed_mentions(|m| m.empty_parse())
+    })
+    .await?;
+
+    Ok(())
+}
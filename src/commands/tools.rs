@@ -1,7 +1,7 @@
 use chrono::DateTime;
 use poise::serenity_prelude::{self as serenity, AttachmentType, RichInvite};
 use rusted_fbt_lib::{
-    checks::guild_auth_check,
+    checks::{guild_auth_check, owner_check},
     types::{Context, Error},
     utils::snowflake_to_unix,
 };
@@ -66,7 +66,7 @@ pub async fn creation_date(
 
 /// qmit
 #[instrument(skip(ctx))]
-#[poise::command(owners_only, slash_command, hide_in_help)]
+#[poise::command(check = "owner_check", slash_command, hide_in_help)]
 pub async fn bot_owner_tool_1(ctx: Context<'_>) -> Result<(), Error> {
     ctx.defer_ephemeral().await?;
 
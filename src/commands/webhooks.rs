@@ -0,0 +1,326 @@
+use poise::serenity_prelude::colours;
+use rusted_fbt_lib::checks::{bot_admin_check, guild_manager_check};
+use rusted_fbt_lib::enums::WebhookEvent;
+use rusted_fbt_lib::structs::{Webhook, WebhookPayload};
+use rusted_fbt_lib::types::{Context, Error};
+use rusted_fbt_lib::utils::open_redis_connection;
+use rusted_fbt_lib::webhooks::{
+    delete_webhook, generate_webhook_secret, get_all_webhooks, get_webhook, get_webhook_log,
+    resolve_webhook_url, save_webhook, send_webhook, WEBHOOK_SIGNATURE_HEADER,
+};
+use tracing::instrument;
+use tracing::{event, Level};
+
+/// Finds a webhook the user is allowed to manage, global webhooks are for bot admins only
+async fn find_webhook(ctx: Context<'_>, id: u64) -> Result<Option<Webhook>, Error> {
+    let is_admin = bot_admin_check(ctx).await?;
+    let mut con = open_redis_connection().await?;
+
+    let webhook = get_webhook(&mut con, id)
+        .await?
+        .filter(|webhook| match &webhook.guild_id {
+            Some(guild_id) => ctx.guild_id().map(|g| g.to_string()).as_ref() == Some(guild_id),
+            None => is_admin,
+        });
+
+    if webhook.is_none() {
+        ctx.say(format!("There's no webhook #{id} for this server."))
+            .await?;
+    }
+
+    Ok(webhook)
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    category = "Admin",
+    required_permissions = "MANAGE_GUILD",
+    check = "guild_manager_check",
+    guild_only,
+    subcommands(
+        "webhook_add",
+        "webhook_remove",
+        "webhook_list",
+        "webhook_log",
+        "webhook_test"
+    )
+)]
+/// Send blacklist events to your own tools
+pub async fn webhook(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say(format!("Webhooks get a JSON POST for every event they subscribe to, signed with their secret as a hex HMAC-SHA256 in the `{WEBHOOK_SIGNATURE_HEADER}` header. Start with `/webhook add`."))
+        .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "add",
+    category = "Admin",
+    required_permissions = "MANAGE_GUILD",
+    check = "guild_manager_check",
+    guild_only
+)]
+/// Subscribe a URL to events from this server
+pub async fn webhook_add(
+    ctx: Context<'_>,
+    #[description = "URL to POST events to"] url: String,
+    #[description = "Only send this event (Default: every event)"] event: Option<WebhookEvent>,
+    #[description = "Get events from every server, bot admins only (Default: false)"]
+    global: Option<bool>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    let global = global.unwrap_or(false);
+
+    if global && !bot_admin_check(ctx).await? {
+        ctx.say("Only bot admins can add global webhooks.").await?;
+        return Ok(());
+    }
+
+    if let Err(problem) = resolve_webhook_url(&url, global).await {
+        ctx.say(problem).await?;
+        return Ok(());
+    }
+
+    let mut con = open_redis_connection().await?;
+
+    let id: u64 = redis::cmd("INCR")
+        .arg("webhook-count")
+        .query_async(&mut con)
+        .await?;
+
+    let webhook = Webhook {
+        id,
+        url,
+        secret: generate_webhook_secret(),
+        guild_id: if global {
+            None
+        } else {
+            ctx.guild_id().map(|g| g.to_string())
+        },
+        events: event.into_iter().collect(),
+        created_by: ctx.author().id.to_string(),
+        created_at: chrono::Utc::now().timestamp(),
+    };
+
+    save_webhook(&mut con, &webhook).await?;
+
+    event!(
+        Level::INFO,
+        "Webhook added" = id,
+        "Global" = global,
+        "By" = ctx.author().id.as_u64()
+    );
+
+    ctx.send(|b| {
+        b.content(format!(
+            "Webhook #{id} added. Its secret is only shown this once:\n||`{}`||\nCheck the `{WEBHOOK_SIGNATURE_HEADER}` header against the HMAC-SHA256 of the body with it.",
+            webhook.secret
+        ))
+        .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "remove",
+    category = "Admin",
+    required_permissions = "MANAGE_GUILD",
+    check = "guild_manager_check",
+    guild_only
+)]
+/// Stop sending events to a webhook
+pub async fn webhook_remove(
+    ctx: Context<'_>,
+    #[description = "Webhook number from /webhook list"] id: u64,
+) -> Result<(), Error> {
+    if find_webhook(ctx, id).await?.is_none() {
+        return Ok(());
+    }
+
+    let mut con = open_redis_connection().await?;
+    delete_webhook(&mut con, id).await?;
+
+    ctx.say(format!("Webhook #{id} removed.")).await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "list",
+    category = "Admin",
+    required_permissions = "MANAGE_GUILD",
+    check = "guild_manager_check",
+    guild_only
+)]
+/// List this server's webhooks, bot admins also see global ones
+pub async fn webhook_list(ctx: Context<'_>) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let guild_id = ctx.guild_id().map(|g| g.to_string());
+    let is_admin = bot_admin_check(ctx).await?;
+
+    let webhooks: Vec<Webhook> = get_all_webhooks(&mut con)
+        .await?
+        .into_iter()
+        .filter(|w| w.guild_id == guild_id || (w.guild_id.is_none() && is_admin))
+        .collect();
+
+    if webhooks.is_empty() {
+        ctx.say("There are no webhooks here yet.").await?;
+        return Ok(());
+    }
+
+    let lines = webhooks
+        .iter()
+        .map(|w| {
+            let events = if w.events.is_empty() {
+                "every event".to_string()
+            } else {
+                w.events
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            };
+
+            format!(
+                "#{} {}`{}`: {events}",
+                w.id,
+                if w.guild_id.is_none() {
+                    "(global) "
+                } else {
+                    ""
+                },
+                w.url
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title("Webhooks")
+                .description(lines)
+                .color(colours::css::POSITIVE)
+        })
+        .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "log",
+    category = "Admin",
+    required_permissions = "MANAGE_GUILD",
+    check = "guild_manager_check",
+    guild_only
+)]
+/// Show a webhook's most recent delivery attempts
+pub async fn webhook_log(
+    ctx: Context<'_>,
+    #[description = "Webhook number from /webhook list"] id: u64,
+) -> Result<(), Error> {
+    if find_webhook(ctx, id).await?.is_none() {
+        return Ok(());
+    }
+
+    let mut con = open_redis_connection().await?;
+    let log = get_webhook_log(&mut con, id).await?;
+
+    if log.is_empty() {
+        ctx.say(format!("Nothing has been sent to webhook #{id} yet."))
+            .await?;
+        return Ok(());
+    }
+
+    let lines = log
+        .iter()
+        .take(20)
+        .map(|entry| {
+            let outcome = match (&entry.error, entry.retry_at) {
+                (None, _) => format!("Delivered ({})", entry.status.unwrap_or_default()),
+                (Some(error), Some(retry_at)) => {
+                    format!("Failed: {error}, retrying <t:{retry_at}:R>")
+                }
+                (Some(error), None) => format!("Failed: {error}, gave up"),
+            };
+
+            format!(
+                "<t:{}:f> {} attempt {}: {outcome}",
+                entry.at, entry.event, entry.attempt
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(format!("Webhook #{id} deliveries"))
+                .description(lines)
+                .color(colours::css::POSITIVE)
+        })
+        .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    rename = "test",
+    category = "Admin",
+    required_permissions = "MANAGE_GUILD",
+    check = "guild_manager_check",
+    guild_only
+)]
+/// Send a test event to a webhook straight away
+pub async fn webhook_test(
+    ctx: Context<'_>,
+    #[description = "Webhook number from /webhook list"] id: u64,
+) -> Result<(), Error> {
+    let Some(webhook) = find_webhook(ctx, id).await? else {
+        return Ok(());
+    };
+
+    ctx.defer_ephemeral().await?;
+
+    let payload = WebhookPayload {
+        event: webhook
+            .events
+            .first()
+            .copied()
+            .unwrap_or(WebhookEvent::OffenceAdded),
+        guild_id: ctx.guild_id().map(|g| g.to_string()),
+        sent_at: chrono::Utc::now().timestamp(),
+        data: serde_json::json!({ "test": true }),
+    };
+
+    let result = send_webhook(&webhook, &payload).await;
+
+    ctx.send(|b| {
+        b.content(match result {
+            Ok(status) => format!("Webhook #{id} answered with {status}."),
+            Err((_, error)) => format!("Webhook #{id} failed: {error}"),
+        })
+        .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
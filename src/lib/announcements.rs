@@ -0,0 +1,495 @@
+use crate::enums::AnnouncementStatus;
+use crate::settings::parse_guild_settings;
+use crate::structs::{
+    Announcement, AnnouncementDelivery, AnnouncementFailure, GuildSettings, GUILD_SETTINGS_VERSION,
+};
+use crate::types::Error;
+use crate::utils::{open_redis_connection, parse_duration};
+use crate::vars::{
+    ANNOUNCEMENT_AUTHOR_ICON, ANNOUNCEMENT_AUTHOR_NAME, ANNOUNCEMENT_CLAIM_SECS,
+    ANNOUNCEMENT_THUMBNAIL, ANNOUNCEMENT_TITLE_PREFIX,
+};
+use chrono::{NaiveDateTime, Utc};
+use poise::serenity_prelude::{ChannelId, Colour, CreateEmbed, Http};
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{event, instrument, Level};
+
+/// Fills in the placeholders an announcement can use for the server it's being sent to
+///
+/// Supports `{server_name}` and `{channel}`.
+#[must_use]
+pub fn render_body(body: &str, settings: &GuildSettings) -> String {
+    body.replace("{server_name}", &settings.server_name)
+        .replace("{channel}", &format!("<#{}>", settings.channel_id))
+}
+
+/// Works out when to send an announcement from either a delay (`2h`), a unix timestamp or a `YYYY-MM-DD HH:MM` UTC date
+#[must_use]
+pub fn parse_send_time(input: &str, now: i64) -> Option<i64> {
+    let input = input.trim();
+
+    let send_at = if let Some(delay) = parse_duration(input) {
+        now + delay.num_seconds()
+    } else if let Ok(timestamp) = input.parse::<i64>() {
+        timestamp
+    } else {
+        NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M")
+            .ok()?
+            .and_utc()
+            .timestamp()
+    };
+
+    (send_at > now).then_some(send_at)
+}
+
+/// Fills out the embed an announcement gets sent as
+pub fn announcement_embed<'a>(
+    e: &'a mut CreateEmbed,
+    announcement: &Announcement,
+    settings: &GuildSettings,
+) -> &'a mut CreateEmbed {
+    let title = if announcement.use_title_prefix {
+        format!("{ANNOUNCEMENT_TITLE_PREFIX}{}", announcement.title)
+    } else {
+        announcement.title.clone()
+    };
+
+    let colour = rand::thread_rng().gen_range(0..10_000_000);
+
+    e.title(title)
+        .description(render_body(&announcement.body, settings))
+        .color(Colour::new(colour))
+        .author(|a| {
+            a.icon_url(ANNOUNCEMENT_AUTHOR_ICON)
+                .name(ANNOUNCEMENT_AUTHOR_NAME)
+        })
+        .thumbnail(
+            announcement
+                .thumbnail
+                .clone()
+                .unwrap_or_else(|| ANNOUNCEMENT_THUMBNAIL.to_string()),
+        )
+}
+
+/// Reads an announcement from the DB
+#[instrument(skip(con))]
+pub async fn get_announcement(
+    con: &mut redis::aio::MultiplexedConnection,
+    id: u64,
+) -> Result<Option<Announcement>, Error> {
+    let json_in: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!("announcement:{id}"))
+        .clone()
+        .query_async(con)
+        .await?;
+
+    match json_in {
+        None => Ok(None),
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+    }
+}
+
+/// Writes an announcement to the DB
+#[instrument(skip(con, announcement))]
+pub async fn save_announcement(
+    con: &mut redis::aio::MultiplexedConnection,
+    announcement: &Announcement,
+) -> Result<(), Error> {
+    redis::cmd("JSON.SET")
+        .arg(format!("announcement:{}", announcement.id))
+        .arg("$".to_string())
+        .arg(serde_json::to_string(announcement)?)
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Locks an announcement so it can't be sent, edited or retracted anywhere else until [`release_announcement`] is called
+///
+/// Returns `false` if something else has it locked, like the scheduler while it's being sent.
+#[instrument(skip(con))]
+pub async fn lock_announcement(
+    con: &mut redis::aio::MultiplexedConnection,
+    id: u64,
+) -> Result<bool, Error> {
+    let locked: bool = redis::cmd("SET")
+        .arg(format!("announcement-sending:{id}"))
+        .arg(Utc::now().timestamp())
+        .arg("NX")
+        .arg("EX")
+        .arg(ANNOUNCEMENT_CLAIM_SECS)
+        .query_async(con)
+        .await?;
+
+    Ok(locked)
+}
+
+/// Claims an announcement for sending so the scheduler and `/announcement send` can't both send it
+///
+/// Gives back the announcement as it is now, `None` if it's already locked somewhere else or has been sent.
+/// Call [`release_announcement`] once it's been delivered.
+#[instrument(skip(con))]
+pub async fn claim_announcement(
+    con: &mut redis::aio::MultiplexedConnection,
+    id: u64,
+) -> Result<Option<Announcement>, Error> {
+    if !lock_announcement(con, id).await? {
+        return Ok(None);
+    }
+
+    // It could have been sent between being read and being claimed
+    let announcement = get_announcement(con, id).await?.filter(|announcement| {
+        !matches!(
+            announcement.status,
+            AnnouncementStatus::Sent | AnnouncementStatus::Retracted
+        )
+    });
+
+    if announcement.is_none() {
+        release_announcement(con, id).await?;
+    }
+
+    Ok(announcement)
+}
+
+/// Lets go of an announcement claimed with [`claim_announcement`] or locked with [`lock_announcement`]
+#[instrument(skip(con))]
+pub async fn release_announcement(
+    con: &mut redis::aio::MultiplexedConnection,
+    id: u64,
+) -> Result<(), Error> {
+    redis::cmd("DEL")
+        .arg(format!("announcement-sending:{id}"))
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Reads every announcement from the DB, oldest first
+#[instrument(skip(con))]
+pub async fn get_all_announcements(
+    con: &mut redis::aio::MultiplexedConnection,
+) -> Result<Vec<Announcement>, Error> {
+    let key_list: Vec<String> = redis::cmd("KEYS")
+        .arg("announcement:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    if key_list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut key_pipe = redis::pipe();
+
+    for key in key_list {
+        key_pipe.cmd("JSON.GET").arg(key);
+    }
+
+    let entries: Vec<String> = key_pipe.atomic().query_async(con).await?;
+
+    let mut announcements = Vec::new();
+    for entry in entries {
+        let announcement: Announcement = serde_json::from_str(&entry)?;
+        announcements.push(announcement);
+    }
+
+    announcements.sort_by_key(|a| a.id);
+
+    Ok(announcements)
+}
+
+/// Reads every guild's settings from the DB, keyed by guild ID
+///
+/// Settings that can't be read are logged and left out, `/settings_audit` lists them.
+#[instrument(skip(con))]
+pub async fn get_all_guild_settings(
+    con: &mut redis::aio::MultiplexedConnection,
+) -> Result<HashMap<String, GuildSettings>, Error> {
+    let key_list: Vec<String> = redis::cmd("KEYS")
+        .arg("guild-settings:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    if key_list.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut key_pipe = redis::pipe();
+
+    for key in &key_list {
+        key_pipe.cmd("JSON.GET").arg(key);
+    }
+
+    let setting_entries: Vec<String> = key_pipe.atomic().query_async(con).await?;
+
+    let mut guild_settings_collection = HashMap::new();
+    for (key, settings) in key_list.iter().zip(setting_entries) {
+        let guild_id = key.trim_start_matches("guild-settings:").to_string();
+
+        match parse_guild_settings(&settings) {
+            Ok((gs, _)) => {
+                guild_settings_collection.insert(guild_id, gs);
+            }
+            Err(error) => {
+                event!(
+                    Level::WARN,
+                    "Skipping invalid guild settings" = guild_id,
+                    error = %error
+                );
+            }
+        }
+    }
+
+    Ok(guild_settings_collection)
+}
+
+/// Sends an announcement to every setup guild that hasn't opted out, recording where it went and what failed
+///
+/// # Errors
+///
+/// This function will return an error if it can't read guild settings from or save the announcement to the DB,
+/// failing to send to a single guild is recorded in `announcement.failures` instead.
+#[instrument(skip(http, con, announcement))]
+pub async fn deliver_announcement(
+    http: &Http,
+    con: &mut redis::aio::MultiplexedConnection,
+    announcement: &mut Announcement,
+) -> Result<(), Error> {
+    announcement.deliveries.clear();
+    announcement.failures.clear();
+
+    for (guild_id, settings) in get_all_guild_settings(con).await? {
+        if settings.announcements_opt_out {
+            continue;
+        }
+
+        let channel = match settings.channel_id.parse::<u64>() {
+            Ok(id) => ChannelId(id),
+            Err(error) => {
+                announcement.failures.push(AnnouncementFailure {
+                    guild_id,
+                    server_name: settings.server_name.clone(),
+                    reason: format!("Invalid channel ID in settings: {error}"),
+                });
+                continue;
+            }
+        };
+
+        match channel
+            .send_message(http, |f| {
+                f.embed(|e| announcement_embed(e, announcement, &settings))
+            })
+            .await
+        {
+            Ok(msg) => {
+                announcement.deliveries.push(AnnouncementDelivery {
+                    guild_id,
+                    channel_id: msg.channel_id.to_string(),
+                    message_id: msg.id.to_string(),
+                });
+            }
+            Err(error) => {
+                event!(
+                    Level::INFO,
+                    "Failed to send announcement to a server because of" = ?error
+                );
+                announcement.failures.push(AnnouncementFailure {
+                    guild_id,
+                    server_name: settings.server_name.clone(),
+                    reason: error.to_string(),
+                });
+            }
+        }
+    }
+
+    announcement.status = AnnouncementStatus::Sent;
+    announcement.send_at = Some(Utc::now().timestamp());
+
+    save_announcement(con, announcement).await?;
+
+    Ok(())
+}
+
+/// Edits every copy of an already sent announcement to match its current title and body
+///
+/// Returns the guilds where the edit didn't work.
+#[instrument(skip(http, con, announcement))]
+pub async fn update_delivered_announcement(
+    http: &Http,
+    con: &mut redis::aio::MultiplexedConnection,
+    announcement: &Announcement,
+) -> Result<Vec<AnnouncementFailure>, Error> {
+    let all_settings = get_all_guild_settings(con).await?;
+    let mut failures = Vec::new();
+
+    for delivery in &announcement.deliveries {
+        // Guilds that have since been removed still get the edit, just without their name filled in
+        let settings = all_settings
+            .get(&delivery.guild_id)
+            .cloned()
+            .unwrap_or_else(|| GuildSettings {
+                version: GUILD_SETTINGS_VERSION,
+                channel_id: delivery.channel_id.clone(),
+                kick: false,
+                server_name: "your server".to_string(),
+                log_channel_id: None,
+                announcements_opt_out: false,
+                alert_min_score: 0,
+                kick_min_score: None,
+            });
+
+        let result = match (
+            delivery.channel_id.parse::<u64>(),
+            delivery.message_id.parse::<u64>(),
+        ) {
+            (Ok(channel_id), Ok(message_id)) => ChannelId(channel_id)
+                .edit_message(http, message_id, |m| {
+                    m.embed(|e| announcement_embed(e, announcement, &settings))
+                })
+                .await
+                .map(|_| ())
+                .map_err(|error| error.to_string()),
+            _ => Err("Invalid channel or message ID".to_string()),
+        };
+
+        if let Err(reason) = result {
+            failures.push(AnnouncementFailure {
+                guild_id: delivery.guild_id.clone(),
+                server_name: settings.server_name,
+                reason,
+            });
+        }
+    }
+
+    Ok(failures)
+}
+
+/// Deletes every copy of an already sent announcement and marks it as retracted
+///
+/// Returns the guilds where the message couldn't be deleted.
+#[instrument(skip(http, con, announcement))]
+pub async fn retract_announcement(
+    http: &Http,
+    con: &mut redis::aio::MultiplexedConnection,
+    announcement: &mut Announcement,
+) -> Result<Vec<AnnouncementFailure>, Error> {
+    let all_settings = get_all_guild_settings(con).await?;
+    let mut failures = Vec::new();
+
+    for delivery in &announcement.deliveries {
+        let result = match (
+            delivery.channel_id.parse::<u64>(),
+            delivery.message_id.parse::<u64>(),
+        ) {
+            (Ok(channel_id), Ok(message_id)) => ChannelId(channel_id)
+                .delete_message(http, message_id)
+                .await
+                .map_err(|error| error.to_string()),
+            _ => Err("Invalid channel or message ID".to_string()),
+        };
+
+        if let Err(reason) = result {
+            failures.push(AnnouncementFailure {
+                guild_id: delivery.guild_id.clone(),
+                server_name: all_settings
+                    .get(&delivery.guild_id)
+                    .map_or_else(|| "Unknown".to_string(), |s| s.server_name.clone()),
+                reason,
+            });
+        }
+    }
+
+    announcement.status = AnnouncementStatus::Retracted;
+
+    save_announcement(con, announcement).await?;
+
+    Ok(failures)
+}
+
+/// Sends any scheduled announcements that are due
+async fn send_due_announcements(http: &Http) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+    let now = Utc::now().timestamp();
+
+    for announcement in get_all_announcements(&mut con).await? {
+        if announcement.status == AnnouncementStatus::Scheduled
+            && announcement.send_at.is_some_and(|send_at| send_at <= now)
+        {
+            let Some(mut announcement) = claim_announcement(&mut con, announcement.id).await?
+            else {
+                continue;
+            };
+
+            let delivered = deliver_announcement(http, &mut con, &mut announcement).await;
+            release_announcement(&mut con, announcement.id).await?;
+            delivered?;
+
+            event!(
+                Level::INFO,
+                "Sent scheduled announcement" = announcement.id,
+                sent = announcement.deliveries.len(),
+                failed = announcement.failures.len()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Background task that checks for due scheduled announcements once a minute, runs forever
+pub async fn run_announcement_scheduler(http: Arc<Http>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+    loop {
+        interval.tick().await;
+
+        if let Err(error) = send_due_announcements(&http).await {
+            event!(Level::WARN, "Failed to send scheduled announcements" = ?error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod announcement_tests {
+    use super::*;
+
+    #[test]
+    fn render_body_test() {
+        let settings = GuildSettings {
+            version: GUILD_SETTINGS_VERSION,
+            channel_id: "123".to_string(),
+            kick: false,
+            server_name: "Test Server".to_string(),
+            log_channel_id: None,
+            announcements_opt_out: false,
+            alert_min_score: 0,
+            kick_min_score: None,
+        };
+
+        assert_eq!(
+            render_body("Hello {server_name}, see {channel}", &settings),
+            "Hello Test Server, see <#123>"
+        );
+    }
+
+    #[test]
+    fn parse_send_time_test() {
+        let now = 1_700_000_000;
+
+        assert_eq!(parse_send_time("2h", now), Some(now + 7200));
+        assert_eq!(parse_send_time("1700003600", now), Some(1_700_003_600));
+        assert_eq!(
+            parse_send_time("2023-11-15 00:00", now),
+            Some(1_700_006_400)
+        );
+        assert_eq!(parse_send_time("1600000000", now), None);
+        assert_eq!(parse_send_time("tomorrow", now), None);
+    }
+}
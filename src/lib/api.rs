@@ -0,0 +1,447 @@
+use crate::import::parse_discord_id;
+use crate::structs::{
+    ApiBulkLookup, ApiBulkLookupResult, ApiKey, ApiSubmission, Offense, UserInfo,
+};
+use crate::types::Error;
+use crate::utils::{add_offence, open_redis_connection};
+use crate::vars::{API_BIND_ADDR, API_BULK_LOOKUP_LIMIT};
+use crate::vrchat::{get_users_by_vrc_id, normalize_vrc_id};
+use axum::extract::Path;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use tracing::{event, instrument, Level};
+
+type ApiResult<T> = Result<Json<T>, Response>;
+
+/// Every generated key starts with this so they're easy to spot if one gets leaked
+pub const API_KEY_PREFIX: &str = "fbt_";
+
+/// Makes a new random API key, only its hash gets saved so it has to be given to the partner straight away
+#[must_use]
+pub fn generate_api_key() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+
+    format!("{API_KEY_PREFIX}{}", hex::encode(bytes))
+}
+
+/// Hashes an API key the same way it's stored in the DB
+#[must_use]
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Pulls the key out of an `Authorization: Bearer <key>` header
+#[must_use]
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get("authorization")?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+
+    (scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty()).then(|| token.trim())
+}
+
+/// Which one minute rate limit window a time falls in and how many seconds until the next one starts
+#[must_use]
+pub const fn rate_limit_window(now: i64) -> (i64, i64) {
+    (now / 60, 60 - now % 60)
+}
+
+/// Writes a partner's key to the DB under its hash
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con, key))]
+pub async fn save_api_key(
+    con: &mut redis::aio::MultiplexedConnection,
+    hash: &str,
+    key: &ApiKey,
+) -> Result<(), Error> {
+    redis::cmd("JSON.SET")
+        .arg(format!("api-key:{hash}"))
+        .arg("$".to_string())
+        .arg(serde_json::to_string(key)?)
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Reads every partner's key from the DB along with its hash, sorted by name
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn get_all_api_keys(
+    con: &mut redis::aio::MultiplexedConnection,
+) -> Result<Vec<(String, ApiKey)>, Error> {
+    let key_list: Vec<String> = redis::cmd("KEYS")
+        .arg("api-key:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    if key_list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut key_pipe = redis::pipe();
+
+    for key in &key_list {
+        key_pipe.cmd("JSON.GET").arg(key);
+    }
+
+    let entries: Vec<String> = key_pipe.atomic().query_async(con).await?;
+
+    let mut keys = Vec::new();
+    for (key, entry) in key_list.iter().zip(entries) {
+        let api_key: ApiKey = serde_json::from_str(&entry)?;
+        keys.push((key.trim_start_matches("api-key:").to_string(), api_key));
+    }
+
+    keys.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+
+    Ok(keys)
+}
+
+/// Deletes every key with the given name, returns how many were deleted
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn revoke_api_key(
+    con: &mut redis::aio::MultiplexedConnection,
+    name: &str,
+) -> Result<usize, Error> {
+    let mut revoked = 0;
+
+    for (hash, key) in get_all_api_keys(con).await? {
+        if key.name == name {
+            redis::cmd("DEL")
+                .arg(format!("api-key:{hash}"))
+                .query_async::<_, ()>(con)
+                .await?;
+            revoked += 1;
+        }
+    }
+
+    Ok(revoked)
+}
+
+/// Builds a JSON error response
+fn api_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// Logs an error without telling the partner what went wrong
+fn internal_error(error: impl std::fmt::Debug) -> Response {
+    event!(Level::ERROR, "API request failed" = ?error);
+    api_error(StatusCode::INTERNAL_SERVER_ERROR, "Internal error")
+}
+
+/// Looks up the request's API key and counts the request against its rate limit
+async fn authenticate(
+    con: &mut redis::aio::MultiplexedConnection,
+    headers: &HeaderMap,
+) -> Result<ApiKey, Response> {
+    let Some(token) = bearer_token(headers) else {
+        return Err(api_error(
+            StatusCode::UNAUTHORIZED,
+            "Missing `Authorization: Bearer <key>` header",
+        ));
+    };
+
+    let hash = hash_api_key(token);
+
+    let json_in: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!("api-key:{hash}"))
+        .clone()
+        .query_async(con)
+        .await
+        .map_err(internal_error)?;
+
+    let Some(json_in) = json_in else {
+        return Err(api_error(StatusCode::UNAUTHORIZED, "Unknown API key"));
+    };
+
+    let key: ApiKey = serde_json::from_str(&json_in).map_err(internal_error)?;
+
+    let (window, retry_after) = rate_limit_window(chrono::Utc::now().timestamp());
+    let counter = format!("api-rate:{hash}:{window}");
+
+    let (count,): (u32,) = redis::pipe()
+        .atomic()
+        .cmd("INCR")
+        .arg(&counter)
+        .cmd("EXPIRE")
+        .arg(&counter)
+        .arg(60)
+        .ignore()
+        .query_async(con)
+        .await
+        .map_err(internal_error)?;
+
+    if count > key.rate_limit_per_minute {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            [("retry-after", retry_after.to_string())],
+            Json(serde_json::json!({ "error": "Rate limit reached" })),
+        )
+            .into_response());
+    }
+
+    Ok(key)
+}
+
+/// Reads one user from the DB
+async fn get_user(
+    con: &mut redis::aio::MultiplexedConnection,
+    discord_id: &str,
+) -> Result<Option<UserInfo>, Response> {
+    let json_in: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!("user:{discord_id}"))
+        .clone()
+        .query_async(con)
+        .await
+        .map_err(internal_error)?;
+
+    json_in
+        .map(|json| serde_json::from_str(&json))
+        .transpose()
+        .map_err(internal_error)
+}
+
+/// `GET /api/v1/users/<DiscordUserID>`
+async fn lookup_discord_id(
+    headers: HeaderMap,
+    Path(discord_id): Path<String>,
+) -> ApiResult<UserInfo> {
+    let mut con = open_redis_connection().await.map_err(internal_error)?;
+    authenticate(&mut con, &headers).await?;
+
+    let Some(discord_id) = parse_discord_id(&discord_id) else {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "That isn't a Discord user ID",
+        ));
+    };
+
+    match get_user(&mut con, &discord_id).await? {
+        Some(user) => Ok(Json(user)),
+        None => Err(api_error(StatusCode::NOT_FOUND, "User isn't in the DB")),
+    }
+}
+
+/// `GET /api/v1/vrchat/<VRChatID>`, returns every user with that VRChat ID
+async fn lookup_vrchat_id(
+    headers: HeaderMap,
+    Path(vrc_id): Path<String>,
+) -> ApiResult<Vec<UserInfo>> {
+    let mut con = open_redis_connection().await.map_err(internal_error)?;
+    authenticate(&mut con, &headers).await?;
+
+    let Some(vrc_id) = normalize_vrc_id(&vrc_id) else {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "That isn't a VRChat user ID",
+        ));
+    };
+
+    let matches = get_users_by_vrc_id(&mut con, &vrc_id)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(matches))
+}
+
+/// `POST /api/v1/users/lookup` with `{"ids": [...]}`
+async fn bulk_lookup(
+    headers: HeaderMap,
+    Json(request): Json<ApiBulkLookup>,
+) -> ApiResult<ApiBulkLookupResult> {
+    let mut con = open_redis_connection().await.map_err(internal_error)?;
+    authenticate(&mut con, &headers).await?;
+
+    if request.ids.len() > API_BULK_LOOKUP_LIMIT {
+        return Err(api_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &format!("At most {API_BULK_LOOKUP_LIMIT} IDs can be looked up at once"),
+        ));
+    }
+
+    let mut result = ApiBulkLookupResult::default();
+    let mut valid_ids = Vec::new();
+
+    for id in request.ids {
+        match parse_discord_id(&id) {
+            Some(discord_id) => valid_ids.push(discord_id),
+            None => result.missing.push(id),
+        }
+    }
+
+    if !valid_ids.is_empty() {
+        let mut key_pipe = redis::pipe();
+
+        for discord_id in &valid_ids {
+            key_pipe.cmd("JSON.GET").arg(format!("user:{discord_id}"));
+        }
+
+        let entries: Vec<Option<String>> = key_pipe
+            .atomic()
+            .query_async(&mut con)
+            .await
+            .map_err(internal_error)?;
+
+        for (discord_id, entry) in valid_ids.into_iter().zip(entries) {
+            match entry {
+                Some(json) => result
+                    .found
+                    .push(serde_json::from_str(&json).map_err(internal_error)?),
+                None => result.missing.push(discord_id),
+            }
+        }
+    }
+
+    Ok(Json(result))
+}
+
+/// `POST /api/v1/users`, adds an offence the same way `/add` does
+async fn submit(
+    headers: HeaderMap,
+    Json(submission): Json<ApiSubmission>,
+) -> Result<(StatusCode, Json<UserInfo>), Response> {
+    let mut con = open_redis_connection().await.map_err(internal_error)?;
+    let key = authenticate(&mut con, &headers).await?;
+
+    let Some(guild_id) = key.guild_id.clone().filter(|_| key.can_submit) else {
+        return Err(api_error(
+            StatusCode::FORBIDDEN,
+            "This API key can't submit offences",
+        ));
+    };
+
+    let Some(uid) = parse_discord_id(&submission.discord_id).and_then(|id| id.parse::<u64>().ok())
+    else {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "That isn't a Discord user ID",
+        ));
+    };
+
+    if submission.reason.trim().is_empty() {
+        return Err(api_error(StatusCode::BAD_REQUEST, "A reason is needed"));
+    }
+
+    let vrc_id = match submission.vrc_id.as_deref().map(normalize_vrc_id) {
+        None => None,
+        Some(Some(vrc_id)) => Some(vrc_id),
+        Some(None) => {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                "That isn't a VRChat user ID or profile link",
+            ))
+        }
+    };
+
+    let offense = Offense {
+        guild_id,
+        reason: submission.reason,
+        image: submission.image.or_else(|| Some("N/A".to_string())),
+        extra: submission.extra.or_else(|| Some("N/A".to_string())),
+        category: submission.category,
+        provenance: None,
+        evidence: Vec::new(),
+        expires_at: None,
+        added_at: None,
+    };
+
+    add_offence(
+        &mut con,
+        uid,
+        submission
+            .username
+            .unwrap_or_else(|| "NoUsernameFoundInDB".to_string()),
+        vrc_id,
+        offense,
+        &format!("api:{}", key.name),
+    )
+    .await
+    .map_err(internal_error)?;
+
+    event!(
+        Level::INFO,
+        "Offence submitted through the API" = uid,
+        "Partner" = key.name
+    );
+
+    match get_user(&mut con, &uid.to_string()).await? {
+        Some(user) => Ok((StatusCode::CREATED, Json(user))),
+        None => Err(internal_error("Submitted user is missing from the DB")),
+    }
+}
+
+/// Runs the HTTP API partners use to query and submit to the blacklist
+///
+/// Spawn this once when the bot starts.
+///
+/// # Errors
+///
+/// This function will return an error if [`API_BIND_ADDR`] can't be bound.
+pub async fn run_api_server() -> Result<(), Error> {
+    let app = Router::new()
+        .route("/api/v1/users", post(submit))
+        .route("/api/v1/users/lookup", post(bulk_lookup))
+        .route("/api/v1/users/:discord_id", get(lookup_discord_id))
+        .route("/api/v1/vrchat/:vrc_id", get(lookup_vrchat_id));
+
+    let listener = tokio::net::TcpListener::bind(API_BIND_ADDR).await?;
+    event!(Level::INFO, "API server listening" = API_BIND_ADDR);
+
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod api_tests {
+    use super::*;
+
+    #[test]
+    fn api_key_test() {
+        let key = generate_api_key();
+
+        assert!(key.starts_with(API_KEY_PREFIX));
+        assert_ne!(key, generate_api_key());
+        assert_eq!(hash_api_key(&key), hash_api_key(&key));
+        assert_eq!(
+            hash_api_key("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn bearer_token_test() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(bearer_token(&headers), None);
+
+        headers.insert("authorization", "Bearer fbt_123".parse().unwrap());
+        assert_eq!(bearer_token(&headers), Some("fbt_123"));
+
+        headers.insert("authorization", "bearer  fbt_123 ".parse().unwrap());
+        assert_eq!(bearer_token(&headers), Some("fbt_123"));
+
+        headers.insert("authorization", "Basic abc".parse().unwrap());
+        assert_eq!(bearer_token(&headers), None);
+    }
+
+    #[test]
+    fn rate_limit_window_test() {
+        assert_eq!(rate_limit_window(120), (2, 60));
+        assert_eq!(rate_limit_window(179), (2, 1));
+    }
+}
@@ -1,6 +1,7 @@
-use crate::enums::{DebugLevel, LogDebugLevel};
+use crate::enums::{BackupFormat, DebugLevel, LogDebugLevel};
 use crate::vars::BOT_TOKEN;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 /// This is where CLI args are set
 #[derive(Parser, Debug)]
@@ -33,4 +34,32 @@ pub struct Args {
     /// emit debug information to both stdout and a file
     #[clap(value_enum, long, default_value = "most")]
     pub debug_log: LogDebugLevel,
+
+    /// Run a maintenance task instead of starting the bot
+    #[clap(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+/// Maintenance tasks that can be run from the command line
+#[derive(Subcommand, Debug, Clone)]
+pub enum CliCommand {
+    /// Save the blacklist, guild settings, auth lists, whitelist and feedback to a file
+    Export {
+        /// File to write the backup to
+        output: PathBuf,
+
+        /// Write one big JSON object or one JSON object per line
+        #[clap(value_enum, long, default_value = "json")]
+        format: BackupFormat,
+    },
+
+    /// Merge a backup file into the DB, nothing already in the DB gets removed
+    Import {
+        /// Backup file made by `export` or `/backup export`
+        input: PathBuf,
+
+        /// Only print what would change
+        #[clap(long)]
+        dry_run: bool,
+    },
 }
@@ -0,0 +1,170 @@
+use crate::enums::AuthLevel;
+use crate::types::Error;
+use std::collections::{HashMap, HashSet};
+use tracing::{event, instrument, Level};
+
+/// Field a user's access is stored under in `guild-auth:`
+#[must_use]
+pub fn user_grant(user_id: u64) -> String {
+    format!("user:{user_id}")
+}
+
+/// Field a role's access is stored under in `guild-auth:`
+#[must_use]
+pub fn role_grant(role_id: u64) -> String {
+    format!("role:{role_id}")
+}
+
+/// The highest level a member gets from their own access and any of their roles
+#[must_use]
+pub fn highest_level(
+    grants: &HashMap<String, AuthLevel>,
+    user_id: u64,
+    role_ids: &[u64],
+) -> Option<AuthLevel> {
+    std::iter::once(user_grant(user_id))
+        .chain(role_ids.iter().map(|role_id| role_grant(*role_id)))
+        .filter_map(|grant| grants.get(&grant).copied())
+        .max()
+}
+
+/// Reads everyone and every role with access in a server
+///
+/// Users authorized before levels existed are in `authed-server-users:` and count as managers.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn get_guild_grants(
+    con: &mut redis::aio::MultiplexedConnection,
+    guild_id: u64,
+) -> Result<HashMap<String, AuthLevel>, Error> {
+    let (stored, legacy): (HashMap<String, String>, HashSet<u64>) = redis::pipe()
+        .cmd("HGETALL")
+        .arg(format!("guild-auth:{guild_id}"))
+        .cmd("SMEMBERS")
+        .arg(format!("authed-server-users:{guild_id}"))
+        .query_async(con)
+        .await?;
+
+    let mut grants: HashMap<String, AuthLevel> = legacy
+        .into_iter()
+        .map(|user_id| (user_grant(user_id), AuthLevel::Manager))
+        .collect();
+
+    for (grant, level) in stored {
+        match level.parse() {
+            Ok(level) => {
+                grants.insert(grant, level);
+            }
+            Err(error) => event!(Level::WARN, "Invalid auth level" = grant, error = ?error),
+        }
+    }
+
+    Ok(grants)
+}
+
+/// Works out the level a member has in a server, `None` if they haven't been authorized
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn get_auth_level(
+    con: &mut redis::aio::MultiplexedConnection,
+    guild_id: u64,
+    user_id: u64,
+    role_ids: &[u64],
+) -> Result<Option<AuthLevel>, Error> {
+    let grants = get_guild_grants(con, guild_id).await?;
+
+    Ok(highest_level(&grants, user_id, role_ids))
+}
+
+/// Gives a user or role (see [`user_grant`] and [`role_grant`]) a level in a server, replacing any level it had
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn grant_access(
+    con: &mut redis::aio::MultiplexedConnection,
+    guild_id: u64,
+    grant: &str,
+    level: AuthLevel,
+) -> Result<(), Error> {
+    let mut pipe = redis::pipe();
+    pipe.atomic()
+        .cmd("HSET")
+        .arg(format!("guild-auth:{guild_id}"))
+        .arg(grant)
+        .arg(level.to_string())
+        .ignore();
+
+    // Drop the old flat entry so the new level is the only one
+    if let Some(user_id) = grant.strip_prefix("user:") {
+        pipe.cmd("SREM")
+            .arg(format!("authed-server-users:{guild_id}"))
+            .arg(user_id)
+            .ignore();
+    }
+
+    pipe.query_async::<_, ()>(con).await?;
+
+    Ok(())
+}
+
+/// Takes away a user or role's access in a server, returns `false` if it didn't have any
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn revoke_access(
+    con: &mut redis::aio::MultiplexedConnection,
+    guild_id: u64,
+    grant: &str,
+) -> Result<bool, Error> {
+    let removed: u64 = redis::cmd("HDEL")
+        .arg(format!("guild-auth:{guild_id}"))
+        .arg(grant)
+        .query_async(con)
+        .await?;
+
+    let removed_legacy: u64 = match grant.strip_prefix("user:") {
+        Some(user_id) => {
+            redis::cmd("SREM")
+                .arg(format!("authed-server-users:{guild_id}"))
+                .arg(user_id)
+                .query_async(con)
+                .await?
+        }
+        None => 0,
+    };
+
+    Ok(removed + removed_legacy > 0)
+}
+
+#[cfg(test)]
+mod auth_tests {
+    use super::*;
+
+    #[test]
+    fn highest_level_test() {
+        let grants = HashMap::from([
+            (user_grant(1), AuthLevel::Viewer),
+            (role_grant(10), AuthLevel::Submitter),
+            (role_grant(20), AuthLevel::Manager),
+        ]);
+
+        assert_eq!(highest_level(&grants, 1, &[]), Some(AuthLevel::Viewer));
+        assert_eq!(highest_level(&grants, 1, &[10]), Some(AuthLevel::Submitter));
+        assert_eq!(
+            highest_level(&grants, 2, &[10, 20]),
+            Some(AuthLevel::Manager)
+        );
+        assert_eq!(highest_level(&grants, 2, &[30]), None);
+        assert!(AuthLevel::Manager > AuthLevel::Submitter);
+    }
+}
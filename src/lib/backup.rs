@@ -0,0 +1,829 @@
+use crate::announcements::get_all_guild_settings;
+use crate::args::CliCommand;
+use crate::botban::{bot_ban_key, get_bot_bans};
+use crate::enums::{AuthLevel, BackupFormat, BotBanKind, StaffTier};
+use crate::expiry::get_archived_offences;
+use crate::feedback::get_all_feedback;
+use crate::search::queue_search_sync;
+use crate::structs::{
+    ArchivedOffence, Backup, BackupRecord, ClearedUser, Identity, RestoreReport, UserInfo,
+};
+use crate::types::Error;
+use crate::utils::open_redis_connection;
+use merge::Merge;
+use std::collections::{HashMap, HashSet};
+use tracing::instrument;
+
+/// The backup layout version written by this build, restores refuse anything newer
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Reads every key in a "folder" that holds JSON and returns them alongside the part of the key after the prefix
+async fn get_json_folder<T: serde::de::DeserializeOwned>(
+    con: &mut redis::aio::MultiplexedConnection,
+    prefix: &str,
+) -> Result<Vec<(String, T)>, Error> {
+    let key_list: Vec<String> = redis::cmd("KEYS")
+        .arg(format!("{prefix}*"))
+        .clone()
+        .query_async(con)
+        .await?;
+
+    if key_list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut key_pipe = redis::pipe();
+
+    for key in &key_list {
+        key_pipe.cmd("JSON.GET").arg(key);
+    }
+
+    let entries: Vec<String> = key_pipe.atomic().query_async(con).await?;
+
+    let mut out = Vec::new();
+    for (key, entry) in key_list.iter().zip(entries) {
+        out.push((
+            key.trim_start_matches(prefix).to_string(),
+            serde_json::from_str(&entry)?,
+        ));
+    }
+
+    Ok(out)
+}
+
+/// Reads everything that gets backed up out of the DB
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn collect_backup(con: &mut redis::aio::MultiplexedConnection) -> Result<Backup, Error> {
+    let users: Vec<(String, UserInfo)> = get_json_folder(con, "user:").await?;
+    let cleared_users: Vec<(String, ClearedUser)> = get_json_folder(con, "cleared-user:").await?;
+
+    let auth_keys: Vec<String> = redis::cmd("KEYS")
+        .arg("authed-server-users:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let mut authed_server_users = HashMap::new();
+    for key in auth_keys {
+        let members: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(&key)
+            .clone()
+            .query_async(con)
+            .await?;
+
+        authed_server_users.insert(
+            key.trim_start_matches("authed-server-users:").to_string(),
+            members,
+        );
+    }
+
+    let guild_auth_keys: Vec<String> = redis::cmd("KEYS")
+        .arg("guild-auth:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let mut guild_auth = HashMap::new();
+    for key in guild_auth_keys {
+        let grants: HashMap<String, String> = redis::cmd("HGETALL")
+            .arg(&key)
+            .clone()
+            .query_async(con)
+            .await?;
+
+        guild_auth.insert(
+            key.trim_start_matches("guild-auth:").to_string(),
+            grants
+                .into_iter()
+                .filter_map(|(grant, level)| Some((grant, level.parse::<AuthLevel>().ok()?)))
+                .collect(),
+        );
+    }
+
+    let kick_whitelist: Vec<String> = redis::cmd("SMEMBERS")
+        .arg("kick-whitelist")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    // Feedback from before it had a status is just a string
+    let feedback_keys: Vec<String> = redis::cmd("KEYS")
+        .arg("feedback:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let mut legacy_feedback = HashMap::new();
+    for key in feedback_keys {
+        let is_legacy = key
+            .strip_prefix("feedback:")
+            .is_some_and(|id| id.parse::<u64>().is_err());
+
+        if is_legacy {
+            let message: String = redis::cmd("GET").arg(&key).clone().query_async(con).await?;
+
+            legacy_feedback.insert(key, message);
+        }
+    }
+
+    let archive_keys: Vec<String> = redis::cmd("KEYS")
+        .arg("offence-archive:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let mut archived_offences = HashMap::new();
+    for key in archive_keys {
+        let discord_id = key.trim_start_matches("offence-archive:").to_string();
+        let archived = get_archived_offences(con, &discord_id).await?;
+
+        archived_offences.insert(discord_id, archived);
+    }
+
+    let identities: Vec<(String, Identity)> = get_json_folder(con, "identity:").await?;
+
+    let staff_tiers: HashMap<String, String> = redis::cmd("HGETALL")
+        .arg("staff-tiers")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let bot_bans = get_bot_bans(con).await?;
+
+    Ok(Backup {
+        version: BACKUP_VERSION,
+        created_at: chrono::Utc::now().timestamp(),
+        users: users.into_iter().map(|(_, user)| user).collect(),
+        cleared_users: cleared_users.into_iter().map(|(_, user)| user).collect(),
+        guild_settings: get_all_guild_settings(con).await?,
+        authed_server_users,
+        guild_auth,
+        kick_whitelist,
+        feedback: get_all_feedback(con).await?,
+        legacy_feedback,
+        archived_offences,
+        identities: identities
+            .into_iter()
+            .map(|(_, identity)| identity)
+            .collect(),
+        staff_tiers: staff_tiers
+            .into_iter()
+            .filter_map(|(user_id, tier)| Some((user_id, tier.parse::<StaffTier>().ok()?)))
+            .collect(),
+        bot_bans,
+    })
+}
+
+/// Writes a backup out as text in the chosen format
+///
+/// # Errors
+///
+/// This function will return an error if something in the backup can't be turned into JSON.
+pub fn write_backup(backup: &Backup, format: BackupFormat) -> Result<String, Error> {
+    if format == BackupFormat::Json {
+        return Ok(serde_json::to_string_pretty(backup)?);
+    }
+
+    let mut records = vec![BackupRecord::Header {
+        version: backup.version,
+        created_at: backup.created_at,
+    }];
+
+    records.extend(backup.users.iter().cloned().map(BackupRecord::User));
+    records.extend(
+        backup
+            .cleared_users
+            .iter()
+            .cloned()
+            .map(BackupRecord::ClearedUser),
+    );
+    records.extend(backup.guild_settings.iter().map(|(guild_id, settings)| {
+        BackupRecord::GuildSettings {
+            guild_id: guild_id.clone(),
+            settings: settings.clone(),
+        }
+    }));
+    records.extend(backup.authed_server_users.iter().map(|(guild_id, users)| {
+        BackupRecord::AuthedServerUsers {
+            guild_id: guild_id.clone(),
+            users: users.clone(),
+        }
+    }));
+    records.extend(
+        backup
+            .guild_auth
+            .iter()
+            .map(|(guild_id, grants)| BackupRecord::GuildAuth {
+                guild_id: guild_id.clone(),
+                grants: grants.clone(),
+            }),
+    );
+    records.extend(
+        backup
+            .kick_whitelist
+            .iter()
+            .cloned()
+            .map(BackupRecord::KickWhitelist),
+    );
+    records.extend(backup.feedback.iter().cloned().map(BackupRecord::Feedback));
+    records.extend(backup.legacy_feedback.iter().map(|(key, message)| {
+        BackupRecord::LegacyFeedback {
+            key: key.clone(),
+            message: message.clone(),
+        }
+    }));
+    records.extend(
+        backup
+            .archived_offences
+            .iter()
+            .map(|(discord_id, offences)| BackupRecord::ArchivedOffences {
+                discord_id: discord_id.clone(),
+                offences: offences.clone(),
+            }),
+    );
+    records.extend(
+        backup
+            .identities
+            .iter()
+            .cloned()
+            .map(BackupRecord::Identity),
+    );
+    records.extend(
+        backup
+            .staff_tiers
+            .iter()
+            .map(|(user_id, tier)| BackupRecord::StaffTier {
+                user_id: user_id.clone(),
+                tier: *tier,
+            }),
+    );
+    records.extend(backup.bot_bans.iter().cloned().map(BackupRecord::BotBan));
+
+    let mut out = String::new();
+    for record in records {
+        out.push_str(&serde_json::to_string(&record)?);
+        out.push('\n');
+    }
+
+    Ok(out)
+}
+
+/// Reads a backup made by [`write_backup`] in either format
+///
+/// # Errors
+///
+/// This function will return an error if the file isn't a backup or was made by a newer version of the bot.
+pub fn read_backup(content: &str) -> Result<Backup, Error> {
+    let backup = if content.trim_start().starts_with("{\"kind\"") {
+        let mut backup = Backup::default();
+
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record: BackupRecord = serde_json::from_str(line)
+                .map_err(|error| format!("Line {} of the backup is invalid: {error}", i + 1))?;
+
+            match record {
+                BackupRecord::Header {
+                    version,
+                    created_at,
+                } => {
+                    backup.version = version;
+                    backup.created_at = created_at;
+                }
+                BackupRecord::User(user) => backup.users.push(user),
+                BackupRecord::ClearedUser(user) => backup.cleared_users.push(user),
+                BackupRecord::GuildSettings { guild_id, settings } => {
+                    backup.guild_settings.insert(guild_id, settings);
+                }
+                BackupRecord::AuthedServerUsers { guild_id, users } => {
+                    backup.authed_server_users.insert(guild_id, users);
+                }
+                BackupRecord::GuildAuth { guild_id, grants } => {
+                    backup.guild_auth.insert(guild_id, grants);
+                }
+                BackupRecord::KickWhitelist(user_id) => backup.kick_whitelist.push(user_id),
+                BackupRecord::Feedback(feedback) => backup.feedback.push(feedback),
+                BackupRecord::LegacyFeedback { key, message } => {
+                    backup.legacy_feedback.insert(key, message);
+                }
+                BackupRecord::ArchivedOffences {
+                    discord_id,
+                    offences,
+                } => {
+                    backup.archived_offences.insert(discord_id, offences);
+                }
+                BackupRecord::Identity(identity) => backup.identities.push(identity),
+                BackupRecord::StaffTier { user_id, tier } => {
+                    backup.staff_tiers.insert(user_id, tier);
+                }
+                BackupRecord::BotBan(ban) => backup.bot_bans.push(ban),
+            }
+        }
+
+        backup
+    } else {
+        serde_json::from_str(content)?
+    };
+
+    if backup.version == 0 {
+        return Err("That file doesn't have a backup version, is it really a backup?".into());
+    }
+
+    if backup.version > BACKUP_VERSION {
+        return Err(format!(
+            "That backup is version {} but this bot only understands up to version {BACKUP_VERSION}, update the bot first",
+            backup.version
+        )
+        .into());
+    }
+
+    Ok(backup)
+}
+
+/// Combines a user from a backup with what's already in the DB
+///
+/// Details already in the DB are kept and offences from both are kept, without doubling up ones that are in both.
+#[must_use]
+pub fn merge_user(existing: Option<UserInfo>, incoming: UserInfo) -> UserInfo {
+    let Some(mut user) = existing else {
+        return incoming;
+    };
+
+    // "N/A" is how missing details get stored so let the backup fill them in
+    for (field, new) in [
+        (&mut user.vrc_id, &incoming.vrc_id),
+        (&mut user.username, &incoming.username),
+    ] {
+        if field.as_deref() == Some("N/A") && new.as_deref() != Some("N/A") {
+            *field = None;
+        }
+    }
+
+    user.merge(incoming);
+
+    let mut seen = HashSet::new();
+    user.offences.retain(|offence| seen.insert(offence.clone()));
+
+    user
+}
+
+/// Merges a backup into the DB, nothing already in the DB gets removed or overwritten
+///
+/// Users are combined with [`merge_user`], everything else is only added if it's missing. With `dry_run` nothing is written.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con, backup))]
+pub async fn restore_backup(
+    con: &mut redis::aio::MultiplexedConnection,
+    backup: &Backup,
+    dry_run: bool,
+) -> Result<RestoreReport, Error> {
+    let mut report = RestoreReport::default();
+    let mut pipe = redis::pipe();
+
+    let existing_users: HashMap<String, UserInfo> =
+        get_json_folder(con, "user:").await?.into_iter().collect();
+    let mut changed_ids = Vec::new();
+
+    for incoming in &backup.users {
+        let Some(discord_id) = incoming.discord_id.clone() else {
+            continue;
+        };
+
+        let existing = existing_users.get(&discord_id).cloned();
+        let merged = merge_user(existing.clone(), incoming.clone());
+
+        match existing {
+            None => report.users_added += 1,
+            Some(existing) if existing == merged => continue,
+            Some(_) => report.users_merged += 1,
+        }
+
+        pipe.cmd("JSON.SET")
+            .arg(format!("user:{discord_id}"))
+            .arg("$".to_string())
+            .arg(serde_json::to_string(&merged)?)
+            .ignore();
+        changed_ids.push(discord_id);
+    }
+
+    let mut add_json_if_missing = |key: String, json: String| {
+        pipe.cmd("JSON.SET")
+            .arg(key)
+            .arg("$")
+            .arg(json)
+            .arg("NX")
+            .ignore();
+    };
+
+    let existing_cleared: HashSet<String> = get_json_folder::<ClearedUser>(con, "cleared-user:")
+        .await?
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+
+    for cleared in &backup.cleared_users {
+        if !existing_cleared.contains(&cleared.user_id) {
+            report.cleared_users_added += 1;
+            add_json_if_missing(
+                format!("cleared-user:{}", cleared.user_id),
+                serde_json::to_string(cleared)?,
+            );
+        }
+    }
+
+    let existing_settings = get_all_guild_settings(con).await?;
+
+    for (guild_id, settings) in &backup.guild_settings {
+        if !existing_settings.contains_key(guild_id) {
+            report.guild_settings_added += 1;
+            add_json_if_missing(
+                format!("guild-settings:{guild_id}"),
+                serde_json::to_string(settings)?,
+            );
+        }
+    }
+
+    let existing_feedback: HashSet<u64> = get_all_feedback(con)
+        .await?
+        .into_iter()
+        .map(|feedback| feedback.id)
+        .collect();
+
+    for feedback in &backup.feedback {
+        if !existing_feedback.contains(&feedback.id) {
+            report.feedback_added += 1;
+            add_json_if_missing(
+                format!("feedback:{}", feedback.id),
+                serde_json::to_string(feedback)?,
+            );
+        }
+    }
+
+    for (guild_id, users) in &backup.authed_server_users {
+        let key = format!("authed-server-users:{guild_id}");
+        let existing: HashSet<String> = redis::cmd("SMEMBERS")
+            .arg(&key)
+            .clone()
+            .query_async(con)
+            .await?;
+
+        let missing: Vec<&String> = users.iter().filter(|u| !existing.contains(*u)).collect();
+
+        if !missing.is_empty() {
+            report.authed_users_added += missing.len();
+            pipe.cmd("SADD").arg(&key).arg(missing).ignore();
+        }
+    }
+
+    for (guild_id, grants) in &backup.guild_auth {
+        let existing: HashMap<String, String> = redis::cmd("HGETALL")
+            .arg(format!("guild-auth:{guild_id}"))
+            .clone()
+            .query_async(con)
+            .await?;
+
+        for (grant, level) in grants.iter().filter(|(g, _)| !existing.contains_key(*g)) {
+            report.authed_users_added += 1;
+            pipe.cmd("HSETNX")
+                .arg(format!("guild-auth:{guild_id}"))
+                .arg(grant)
+                .arg(level.to_string())
+                .ignore();
+        }
+    }
+
+    let existing_whitelist: HashSet<String> = redis::cmd("SMEMBERS")
+        .arg("kick-whitelist")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let missing: Vec<&String> = backup
+        .kick_whitelist
+        .iter()
+        .filter(|u| !existing_whitelist.contains(*u))
+        .collect();
+
+    if !missing.is_empty() {
+        report.kick_whitelist_added = missing.len();
+        pipe.cmd("SADD").arg("kick-whitelist").arg(missing).ignore();
+    }
+
+    for (discord_id, offences) in &backup.archived_offences {
+        let existing: HashSet<ArchivedOffence> = get_archived_offences(con, discord_id)
+            .await?
+            .into_iter()
+            .collect();
+
+        for archived in offences.iter().filter(|a| !existing.contains(*a)) {
+            report.archived_offences_added += 1;
+            pipe.cmd("RPUSH")
+                .arg(format!("offence-archive:{discord_id}"))
+                .arg(serde_json::to_string(archived)?)
+                .ignore();
+        }
+    }
+
+    let existing_identities: HashSet<u64> = get_json_folder::<Identity>(con, "identity:")
+        .await?
+        .into_iter()
+        .map(|(_, identity)| identity.id)
+        .collect();
+
+    // Identities whose number is already taken are skipped whole so accounts aren't pointed at the wrong one
+    for identity in backup
+        .identities
+        .iter()
+        .filter(|identity| !existing_identities.contains(&identity.id))
+    {
+        report.identities_added += 1;
+        pipe.cmd("JSON.SET")
+            .arg(format!("identity:{}", identity.id))
+            .arg("$")
+            .arg(serde_json::to_string(identity)?)
+            .arg("NX")
+            .ignore();
+
+        for account in std::iter::once(&identity.primary)
+            .chain(identity.accounts.iter().map(|linked| &linked.account))
+        {
+            pipe.cmd("SET")
+                .arg(format!("identity-of:{account}"))
+                .arg(identity.id)
+                .arg("NX")
+                .ignore();
+        }
+    }
+
+    let existing_staff: HashMap<String, String> = redis::cmd("HGETALL")
+        .arg("staff-tiers")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    for (user_id, tier) in backup
+        .staff_tiers
+        .iter()
+        .filter(|(user_id, _)| !existing_staff.contains_key(*user_id))
+    {
+        report.staff_added += 1;
+        pipe.cmd("HSETNX")
+            .arg("staff-tiers")
+            .arg(user_id)
+            .arg(tier.to_string())
+            .ignore();
+    }
+
+    let existing_bans: HashSet<String> = redis::cmd("KEYS")
+        .arg("bot-ban:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let existing_legacy_bans: HashSet<String> = redis::cmd("SMEMBERS")
+        .arg("user-lists:banned-from-bot")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let now = chrono::Utc::now().timestamp();
+
+    // Bans that ran out since the backup was made stay lifted
+    for ban in backup.bot_bans.iter().filter(|ban| {
+        !existing_bans.contains(&bot_ban_key(ban.kind, &ban.id))
+            && (ban.kind != BotBanKind::User || !existing_legacy_bans.contains(&ban.id))
+            && ban.expires_at.is_none_or(|expires_at| expires_at > now)
+    }) {
+        report.bot_bans_added += 1;
+        pipe.cmd("SET")
+            .arg(bot_ban_key(ban.kind, &ban.id))
+            .arg(serde_json::to_string(ban)?)
+            .arg("NX");
+
+        if let Some(expires_at) = ban.expires_at {
+            pipe.arg("EXAT").arg(expires_at);
+        }
+
+        pipe.ignore();
+    }
+
+    for (key, message) in &backup.legacy_feedback {
+        pipe.cmd("SET").arg(key).arg(message).arg("NX").ignore();
+    }
+
+    // Keep new feedback from reusing a restored feedback number
+    if let Some(highest) = backup.feedback.iter().map(|feedback| feedback.id).max() {
+        let current: Option<u64> = redis::cmd("GET")
+            .arg("feedback-count")
+            .clone()
+            .query_async(con)
+            .await?;
+
+        if current.unwrap_or(0) < highest {
+            pipe.cmd("SET").arg("feedback-count").arg(highest).ignore();
+        }
+    }
+
+    // Same for identity numbers
+    if let Some(highest) = backup.identities.iter().map(|identity| identity.id).max() {
+        let current: Option<u64> = redis::cmd("GET")
+            .arg("identity-count")
+            .clone()
+            .query_async(con)
+            .await?;
+
+        if current.unwrap_or(0) < highest {
+            pipe.cmd("SET").arg("identity-count").arg(highest).ignore();
+        }
+    }
+
+    if dry_run {
+        return Ok(report);
+    }
+
+    pipe.atomic().query_async::<_, ()>(con).await?;
+
+    queue_search_sync(con, &changed_ids).await?;
+
+    Ok(report)
+}
+
+/// Turns a restore report into a few readable lines
+#[must_use]
+pub fn describe_restore(report: &RestoreReport) -> String {
+    format!(
+        "Users added: {}\nUsers merged: {}\nCleared users added: {}\nGuild settings added: {}\nAuthed users added: {}\nKick whitelist users added: {}\nFeedback added: {}\nArchived offences added: {}\nIdentities added: {}\nStaff added: {}\nBot bans added: {}",
+        report.users_added,
+        report.users_merged,
+        report.cleared_users_added,
+        report.guild_settings_added,
+        report.authed_users_added,
+        report.kick_whitelist_added,
+        report.feedback_added,
+        report.archived_offences_added,
+        report.identities_added,
+        report.staff_added,
+        report.bot_bans_added
+    )
+}
+
+/// Runs the `export` and `import` CLI subcommands
+///
+/// # Errors
+///
+/// This function will return an error if the file or DB can't be read or written.
+pub async fn run_backup_cli(command: CliCommand) -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    match command {
+        CliCommand::Export { output, format } => {
+            let backup = collect_backup(&mut con).await?;
+            std::fs::write(&output, write_backup(&backup, format)?)?;
+
+            println!("Saved {} users to {}", backup.users.len(), output.display());
+        }
+        CliCommand::Import { input, dry_run } => {
+            let backup = read_backup(&std::fs::read_to_string(&input)?)?;
+            let report = restore_backup(&mut con, &backup, dry_run).await?;
+
+            if dry_run {
+                println!("Dry run, nothing was written.");
+            }
+            println!("{}", describe_restore(&report));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod backup_tests {
+    use super::*;
+    use crate::structs::{
+        ArchivedOffence, BotBan, GuildSettings, LinkedAccount, Offense, GUILD_SETTINGS_VERSION,
+    };
+
+    fn offence(reason: &str) -> Offense {
+        Offense {
+            guild_id: "1".to_string(),
+            reason: reason.to_string(),
+            image: Some("N/A".to_string()),
+            extra: Some("N/A".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn backup() -> Backup {
+        Backup {
+            version: BACKUP_VERSION,
+            created_at: 1_700_000_000,
+            users: vec![UserInfo {
+                vrc_id: Some("N/A".to_string()),
+                username: Some("Ripper".to_string()),
+                discord_id: Some("000000000000000001".to_string()),
+                offences: vec![offence("Ripping")],
+            }],
+            guild_settings: HashMap::from([(
+                "2".to_string(),
+                GuildSettings {
+                    version: GUILD_SETTINGS_VERSION,
+                    channel_id: "3".to_string(),
+                    kick: true,
+                    server_name: "Server".to_string(),
+                    log_channel_id: None,
+                    announcements_opt_out: false,
+                    alert_min_score: 0,
+                    kick_min_score: None,
+                },
+            )]),
+            authed_server_users: HashMap::from([("2".to_string(), vec!["4".to_string()])]),
+            guild_auth: HashMap::from([(
+                "2".to_string(),
+                HashMap::from([("role:6".to_string(), AuthLevel::Submitter)]),
+            )]),
+            kick_whitelist: vec!["5".to_string()],
+            legacy_feedback: HashMap::from([(
+                "feedback:1-1-user#0001".to_string(),
+                "Nice bot".to_string(),
+            )]),
+            archived_offences: HashMap::from([(
+                "000000000000000001".to_string(),
+                vec![ArchivedOffence {
+                    offence: offence("Old ripping"),
+                    archived_at: 1_600_000_000,
+                }],
+            )]),
+            identities: vec![Identity {
+                id: 1,
+                primary: "000000000000000001".to_string(),
+                accounts: vec![LinkedAccount {
+                    account: "000000000000000007".to_string(),
+                    evidence: "Same avatar".to_string(),
+                    linked_by: "4".to_string(),
+                    linked_at: 1_700_000_000,
+                }],
+                created_at: 1_700_000_000,
+            }],
+            staff_tiers: HashMap::from([("8".to_string(), StaffTier::Reviewer)]),
+            bot_bans: vec![BotBan {
+                kind: BotBanKind::Guild,
+                id: "9".to_string(),
+                reason: "Raiding".to_string(),
+                banned_by: "8".to_string(),
+                banned_at: 1_700_000_000,
+                expires_at: Some(1_800_000_000),
+            }],
+            ..Backup::default()
+        }
+    }
+
+    #[test]
+    fn backup_round_trip_test() {
+        for format in [BackupFormat::Json, BackupFormat::Jsonl] {
+            let written = write_backup(&backup(), format).unwrap();
+            assert_eq!(read_backup(&written).unwrap(), backup());
+        }
+
+        let mut future = backup();
+        future.version = BACKUP_VERSION + 1;
+        let written = write_backup(&future, BackupFormat::Json).unwrap();
+        assert!(read_backup(&written).is_err());
+        assert!(read_backup("{}").is_err());
+    }
+
+    #[test]
+    fn merge_user_test() {
+        let incoming = UserInfo {
+            vrc_id: Some("usr_1".to_string()),
+            username: Some("Ripper".to_string()),
+            discord_id: Some("000000000000000001".to_string()),
+            offences: vec![offence("Ripping"), offence("Leaking")],
+        };
+        let existing = UserInfo {
+            vrc_id: Some("N/A".to_string()),
+            username: Some("Ripper2".to_string()),
+            discord_id: Some("000000000000000001".to_string()),
+            offences: vec![offence("Ripping")],
+        };
+
+        let merged = merge_user(Some(existing), incoming.clone());
+
+        assert_eq!(merged.vrc_id.as_deref(), Some("usr_1"));
+        assert_eq!(merged.username.as_deref(), Some("Ripper2"));
+        assert_eq!(
+            merged.offences,
+            vec![offence("Ripping"), offence("Leaking")]
+        );
+        assert_eq!(merge_user(None, incoming.clone()), incoming);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::enums::BotBanKind;
+use crate::structs::BotBan;
+use crate::types::Error;
+use tracing::instrument;
+
+/// Key a bot ban is stored under
+#[must_use]
+pub fn bot_ban_key(kind: BotBanKind, id: &str) -> String {
+    match kind {
+        BotBanKind::User => format!("bot-ban:user:{id}"),
+        BotBanKind::Guild => format!("bot-ban:guild:{id}"),
+    }
+}
+
+/// Users added to `user-lists:banned-from-bot` before bans had reasons, they never expire
+#[must_use]
+pub fn legacy_bot_ban(user_id: String) -> BotBan {
+    BotBan {
+        kind: BotBanKind::User,
+        id: user_id,
+        reason: "No reason was recorded".to_string(),
+        banned_by: String::new(),
+        banned_at: 0,
+        expires_at: None,
+    }
+}
+
+/// What a blocked user is told when they try to run a command
+#[must_use]
+pub fn bot_ban_notice(ban: &BotBan) -> String {
+    let who = match ban.kind {
+        BotBanKind::User => "You are",
+        BotBanKind::Guild => "This server is",
+    };
+
+    let until = ban.expires_at.map_or_else(
+        || "This ban doesn't expire".to_string(),
+        |expires_at| format!("This ban ends <t:{expires_at}:R>"),
+    );
+
+    format!(
+        "{who} blocked from using this bot.\nReason: {}\n{until}, reach out through the FBT Discord if you think this is a mistake.",
+        ban.reason
+    )
+}
+
+/// Finds the ban stopping someone from using the bot, their own ban comes first then the server's
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or a ban is invalid.
+#[instrument(skip(con))]
+pub async fn get_bot_ban(
+    con: &mut redis::aio::MultiplexedConnection,
+    user_id: u64,
+    guild_id: Option<u64>,
+) -> Result<Option<BotBan>, Error> {
+    let (user_ban, guild_ban, legacy): (Option<String>, Option<String>, bool) = redis::pipe()
+        .cmd("GET")
+        .arg(bot_ban_key(BotBanKind::User, &user_id.to_string()))
+        .cmd("GET")
+        .arg(bot_ban_key(
+            BotBanKind::Guild,
+            &guild_id.unwrap_or_default().to_string(),
+        ))
+        .cmd("SISMEMBER")
+        .arg("user-lists:banned-from-bot")
+        .arg(user_id)
+        .query_async(con)
+        .await?;
+
+    if let Some(ban) = user_ban.or(guild_ban) {
+        return Ok(Some(serde_json::from_str(&ban)?));
+    }
+
+    Ok(legacy.then(|| legacy_bot_ban(user_id.to_string())))
+}
+
+/// Saves a bot ban, Redis drops it by itself once it expires
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn add_bot_ban(
+    con: &mut redis::aio::MultiplexedConnection,
+    ban: &BotBan,
+) -> Result<(), Error> {
+    let key = bot_ban_key(ban.kind, &ban.id);
+
+    let mut pipe = redis::pipe();
+    pipe.atomic()
+        .cmd("SET")
+        .arg(&key)
+        .arg(serde_json::to_string(ban)?)
+        .ignore();
+
+    if let Some(expires_at) = ban.expires_at {
+        pipe.cmd("EXPIREAT").arg(&key).arg(expires_at).ignore();
+    }
+
+    pipe.query_async::<_, ()>(con).await?;
+
+    Ok(())
+}
+
+/// Lifts a bot ban, returns `false` if there wasn't one
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn remove_bot_ban(
+    con: &mut redis::aio::MultiplexedConnection,
+    kind: BotBanKind,
+    id: &str,
+) -> Result<bool, Error> {
+    let removed: u64 = redis::cmd("DEL")
+        .arg(bot_ban_key(kind, id))
+        .query_async(con)
+        .await?;
+
+    let removed_legacy: u64 = match kind {
+        BotBanKind::User => {
+            redis::cmd("SREM")
+                .arg("user-lists:banned-from-bot")
+                .arg(id)
+                .query_async(con)
+                .await?
+        }
+        BotBanKind::Guild => 0,
+    };
+
+    Ok(removed + removed_legacy > 0)
+}
+
+/// Reads every bot ban that hasn't expired, servers first
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or a ban is invalid.
+#[instrument(skip(con))]
+pub async fn get_bot_bans(
+    con: &mut redis::aio::MultiplexedConnection,
+) -> Result<Vec<BotBan>, Error> {
+    let key_list: Vec<String> = redis::cmd("KEYS").arg("bot-ban:*").query_async(con).await?;
+
+    let mut bans = Vec::new();
+
+    if !key_list.is_empty() {
+        let entries: Vec<Option<String>> =
+            redis::cmd("MGET").arg(&key_list).query_async(con).await?;
+
+        // Anything that expired between KEYS and MGET comes back as nil
+        for entry in entries.into_iter().flatten() {
+            bans.push(serde_json::from_str::<BotBan>(&entry)?);
+        }
+    }
+
+    let legacy: Vec<String> = redis::cmd("SMEMBERS")
+        .arg("user-lists:banned-from-bot")
+        .query_async(con)
+        .await?;
+
+    let legacy: Vec<BotBan> = legacy
+        .into_iter()
+        .filter(|user_id| {
+            !bans
+                .iter()
+                .any(|ban| ban.kind == BotBanKind::User && &ban.id == user_id)
+        })
+        .map(legacy_bot_ban)
+        .collect();
+
+    bans.extend(legacy);
+
+    bans.sort_by_key(|ban| (ban.kind == BotBanKind::User, ban.banned_at));
+
+    Ok(bans)
+}
+
+#[cfg(test)]
+mod botban_tests {
+    use super::*;
+
+    #[test]
+    fn bot_ban_notice_test() {
+        let mut ban = legacy_bot_ban("1".to_string());
+        assert_eq!(bot_ban_key(ban.kind, &ban.id), "bot-ban:user:1");
+        assert!(bot_ban_notice(&ban).starts_with("You are blocked"));
+        assert!(bot_ban_notice(&ban).contains("doesn't expire"));
+
+        ban.kind = BotBanKind::Guild;
+        ban.expires_at = Some(1_700_000_000);
+        assert_eq!(bot_ban_key(ban.kind, &ban.id), "bot-ban:guild:1");
+        assert!(bot_ban_notice(&ban).starts_with("This server is blocked"));
+        assert!(bot_ban_notice(&ban).contains("<t:1700000000:R>"));
+    }
+}
@@ -1,31 +1,67 @@
+#[cfg(feature = "database")]
+use crate::enums::AuthLevel;
+use crate::enums::StaffTier;
 use crate::types::{Context, Error};
-use crate::vars::BOT_ADMINS;
-use std::collections::HashSet;
 
-/// Check if command user is in the `BOT_ADMINS` list
+/// Check if command user is on the bot's staff with at least `tier`
+///
+/// Without the DB only the `BOT_OWNERS` list is known so every tier needs an owner.
 ///
 /// # Errors
 ///
-/// This function will never return an error.
-#[allow(clippy::unused_async, clippy::missing_errors_doc)] // async is used by command checks but clippy can't tell
-pub async fn bot_admin_check(ctx: Context<'_>) -> Result<bool, Error> {
-    // ? The bellow commented out code is for quick testing, automatic fails on my ID
-    // match ctx.author().id.as_u64() {
-    //     383507911160233985 => Ok(false),
-    //     _ => {
-    //         match BOT_ADMINS.contains(ctx.author().id.as_u64()) {
-    //             true => Ok(true),
-    //             false => Ok(false),
-    //         }
-    //     }
-    // }
-    if BOT_ADMINS.contains(ctx.author().id.as_u64()) {
-        Ok(true)
-    } else {
-        Ok(false)
+/// This function will return an error if unable to connet to or query DB.
+#[allow(clippy::unused_async)] // async is used by command checks but clippy can't tell
+pub async fn staff_check(ctx: Context<'_>, tier: StaffTier) -> Result<bool, Error> {
+    #[cfg(feature = "database")]
+    {
+        let mut con = crate::utils::open_redis_connection().await?;
+
+        crate::staff::has_staff_tier(&mut con, *ctx.author().id.as_u64(), tier).await
+    }
+
+    #[cfg(not(feature = "database"))]
+    {
+        let _ = tier;
+        Ok(crate::vars::BOT_OWNERS.contains(ctx.author().id.as_u64()))
     }
 }
 
+/// Check if command user is a bot owner
+///
+/// # Errors
+///
+/// This function will return an error if unable to connet to or query DB.
+pub async fn owner_check(ctx: Context<'_>) -> Result<bool, Error> {
+    staff_check(ctx, StaffTier::Owner).await
+}
+
+/// Check if command user is a bot admin or owner
+///
+/// # Errors
+///
+/// This function will return an error if unable to connet to or query DB.
+pub async fn bot_admin_check(ctx: Context<'_>) -> Result<bool, Error> {
+    staff_check(ctx, StaffTier::Admin).await
+}
+
+/// Check if command user is at least a reviewer
+///
+/// # Errors
+///
+/// This function will return an error if unable to connet to or query DB.
+pub async fn reviewer_check(ctx: Context<'_>) -> Result<bool, Error> {
+    staff_check(ctx, StaffTier::Reviewer).await
+}
+
+/// Check if command user is on the bot's staff at all
+///
+/// # Errors
+///
+/// This function will return an error if unable to connet to or query DB.
+pub async fn support_check(ctx: Context<'_>) -> Result<bool, Error> {
+    staff_check(ctx, StaffTier::Support).await
+}
+
 // ? This might not be needed, I thinik it's a left over from before we dud guild based authing
 // ! Remove the _ if put into use!
 #[cfg(feature = "database")]
@@ -59,13 +95,16 @@ pub async fn _bot_auth_check(_ctx: Context<'_>) -> Result<bool, Error> {
     Ok(false)
 }
 
-/// Checks if a user is authorised to use the bot in the current server
+/// Checks if a user has at least `level` in the current server, from their own access or one of their roles
+///
+/// Bot admins pass every level.
 ///
 /// # Errors
 ///
 /// This function will return an error if unable to connet to or query DB.
 #[cfg(feature = "database")]
-pub async fn guild_auth_check(ctx: Context<'_>) -> Result<bool, Error> {
+pub async fn guild_level_check(ctx: Context<'_>, level: AuthLevel) -> Result<bool, Error> {
+    use crate::auth::get_auth_level;
     use crate::utils::open_redis_connection;
 
     if let Ok(res) = bot_admin_check(ctx).await {
@@ -74,27 +113,68 @@ pub async fn guild_auth_check(ctx: Context<'_>) -> Result<bool, Error> {
         }
     }
 
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(false);
+    };
+
+    let role_ids: Vec<u64> = ctx
+        .author_member()
+        .await
+        .map(|member| member.roles.iter().map(|role| role.0).collect())
+        .unwrap_or_default();
+
     let mut con = open_redis_connection().await?;
 
-    let key_list: Option<HashSet<String>> = redis::cmd("SMEMBERS")
-        .arg(format!(
-            "authed-server-users:{}",
-            ctx.guild_id()
-                .unwrap_or(poise::serenity_prelude::GuildId(0))
-                .as_u64()
-        ))
-        .clone()
-        .query_async(&mut con)
-        .await?;
-
-    match key_list {
-        None => Ok(false),
-        Some(list) if !list.contains(&format!("{}", ctx.author().id.as_u64())) => {
-            Ok({
-                ctx.say("You are not authorized to use this command! Please contact a bot admin or Azuki!").await?;
-                false
-            })
+    let user_level = get_auth_level(
+        &mut con,
+        *guild_id.as_u64(),
+        *ctx.author().id.as_u64(),
+        &role_ids,
+    )
+    .await?;
+
+    match user_level {
+        Some(user_level) if user_level >= level => Ok(true),
+        Some(user_level) => {
+            ctx.say(format!("You need {level} access to use this command but you only have {user_level}! Ask a manager in this server or a bot admin.")).await?;
+            Ok(false)
+        }
+        None => {
+            ctx.say(
+                "You are not authorized to use this command! Please contact a bot admin or Azuki!",
+            )
+            .await?;
+            Ok(false)
         }
-        Some(_list) => Ok(true),
     }
 }
+
+/// Checks if a user is authorised to use the bot in the current server, any level will do
+///
+/// # Errors
+///
+/// This function will return an error if unable to connet to or query DB.
+#[cfg(feature = "database")]
+pub async fn guild_auth_check(ctx: Context<'_>) -> Result<bool, Error> {
+    guild_level_check(ctx, AuthLevel::Viewer).await
+}
+
+/// Checks if a user can add to the DB from the current server
+///
+/// # Errors
+///
+/// This function will return an error if unable to connet to or query DB.
+#[cfg(feature = "database")]
+pub async fn guild_submitter_check(ctx: Context<'_>) -> Result<bool, Error> {
+    guild_level_check(ctx, AuthLevel::Submitter).await
+}
+
+/// Checks if a user can manage who's authorized and the settings in the current server
+///
+/// # Errors
+///
+/// This function will return an error if unable to connet to or query DB.
+#[cfg(feature = "database")]
+pub async fn guild_manager_check(ctx: Context<'_>) -> Result<bool, Error> {
+    guild_level_check(ctx, AuthLevel::Manager).await
+}
@@ -1,4 +1,6 @@
 use poise::serenity_prelude::{self as serenity};
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Debug, poise::ChoiceParameter)]
 pub enum WaifuTypes {
@@ -55,3 +57,226 @@ pub enum CloseTicketFail {
     IncorrectCategory,
     SerenityError(serenity::Error),
 }
+
+/// The kinds of moderation action that get logged as a case
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ModAction {
+    Kick,
+    Timeout,
+    Unban,
+    Warn,
+    Softban,
+    Ban,
+}
+
+impl fmt::Display for ModAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Kick => "Kick",
+            Self::Timeout => "Timeout",
+            Self::Unban => "Unban",
+            Self::Warn => "Warn",
+            Self::Softban => "Softban",
+            Self::Ban => "Ban",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Where an announcement is at in its life
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum AnnouncementStatus {
+    Draft,
+    Scheduled,
+    Sent,
+    Retracted,
+}
+
+impl fmt::Display for AnnouncementStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Draft => "Draft",
+            Self::Scheduled => "Scheduled",
+            Self::Sent => "Sent",
+            Self::Retracted => "Retracted",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Where a piece of feedback is at in being looked at by staff
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum FeedbackStatus {
+    New,
+    Acknowledged,
+    Done,
+}
+
+/// Where a server's request to use the bot is at
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum OnboardingStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+impl fmt::Display for OnboardingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Pending => "Pending",
+            Self::Approved => "Approved",
+            Self::Denied => "Denied",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// What kind of offence someone was added to the DB for
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum OffenceCategory {
+    Ripping,
+    Leaking,
+    Harassment,
+    Scam,
+    Raid,
+    Other,
+}
+
+/// How much someone can do in a server they've been authorized in, each level can do everything the ones before it can
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Serialize,
+    Deserialize,
+    poise::ChoiceParameter,
+)]
+pub enum AuthLevel {
+    /// Can use `search` and `footprint_lookup`
+    Viewer,
+    /// Can also `add` users
+    Submitter,
+    /// Can also authorize others and change the server's settings
+    Manager,
+}
+
+/// How much of the bot someone on the bot's staff can run, each tier can do everything the ones before it can
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Serialize,
+    Deserialize,
+    poise::ChoiceParameter,
+)]
+pub enum StaffTier {
+    /// Can reply to feedback and message users through the bot
+    Support,
+    /// Can also clear users, whitelist them and link alts
+    Reviewer,
+    /// Can also import users, send announcements and grant tiers below admin
+    Admin,
+    /// Can run everything, including backups and the search engine
+    Owner,
+}
+
+/// What a bot ban applies to
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum BotBanKind {
+    /// Just that user, wherever they use the bot
+    User,
+    /// Everyone in that server, the bot ignores commands run there
+    Guild,
+}
+
+/// Rough band a user's risk score falls in
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for RiskLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Low => "Low",
+            Self::Medium => "Medium",
+            Self::High => "High",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// The kinds of files `/excel` can import users from
+#[derive(Debug, Clone, Copy, Eq, PartialEq, poise::ChoiceParameter)]
+pub enum ImportFormat {
+    #[name = "CSV"]
+    Csv,
+    #[name = "JSON list"]
+    Json,
+    #[name = "List of IDs"]
+    IdList,
+    #[name = "DiscordChatExporter JSON (or .zip of them)"]
+    DiscordChatExporter,
+}
+
+/// How a DB backup gets written out
+#[derive(Debug, Clone, Copy, Eq, PartialEq, clap::ValueEnum, poise::ChoiceParameter)]
+pub enum BackupFormat {
+    /// One JSON object holding everything
+    #[name = "JSON"]
+    Json,
+    /// One JSON object per line, easier to stream and diff
+    #[name = "JSONL"]
+    Jsonl,
+}
+
+/// What happens to entries received from a federation peer
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum PeerTrust {
+    /// Merged straight into the DB
+    Accept,
+    /// Held until a bot owner reviews them with `/federation review`
+    Quarantine,
+    /// Thrown away
+    Ignore,
+}
+
+/// Things that can happen which webhooks can subscribe to
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    #[name = "Offence added"]
+    OffenceAdded,
+    #[name = "User cleared"]
+    UserCleared,
+    #[name = "Blacklisted user joined"]
+    BlacklistedJoin,
+    #[name = "Alt kicked"]
+    AltKicked,
+}
@@ -1,4 +1,4 @@
-use crate::structs::{GuildSettings, UserInfo, WaybackResponse, WaybackStatus};
+use crate::structs::{UserInfo, WaybackResponse, WaybackStatus};
 use crate::utils::snowflake_to_unix;
 use crate::vars::FBT_GUILD_ID;
 // use chrono::NaiveDateTime;
@@ -15,9 +15,7 @@ const DM_CHANNEL_ID: u64 = 0000000000000000000;
 
 /// If enabled on a server it will warn them on black listed users joining
 ///
-/// # Panics
-///
-/// Panics if unable to parse channel ID from DB to u64.
+/// Users under the server's `alert_min_score` aren't warned about and ones at its `kick_min_score` get kicked.
 ///
 /// # Errors
 ///
@@ -41,29 +39,138 @@ pub async fn bl_warner(
         .query_async(&mut con)
         .await?;
 
-    let if_on_bl: Option<String> = redis::cmd("JSON.GET")
-        .arg(format!("user:{}", member.user.id.as_u64()))
-        .clone()
-        .query_async(&mut con)
-        .await?;
+    // Anyone linked to the member counts, their own entry is checked first then the main account's then the other alts'
+    let member_id = member.user.id.to_string();
+    let identity = crate::identity::get_identity_of(&mut con, &member_id).await?;
+    let alt_of = identity
+        .as_ref()
+        .and_then(|identity| crate::identity::alt_of(identity, &member_id).map(str::to_string));
+
+    let mut accounts = vec![member_id.clone()];
+    if let Some(identity) = &identity {
+        for account in std::iter::once(&identity.primary)
+            .chain(identity.accounts.iter().map(|linked| &linked.account))
+        {
+            if !account.starts_with("usr_") && !accounts.contains(account) {
+                accounts.push(account.clone());
+            }
+        }
+    }
+
+    let mut entry_pipe = redis::pipe();
+    for account in &accounts {
+        entry_pipe.cmd("JSON.GET").arg(format!("user:{account}"));
+    }
+
+    let entries: Vec<Option<String>> = entry_pipe.query_async(&mut con).await?;
+
+    // Users whose offences have all expired aren't warned about
+    let now = chrono::Utc::now().timestamp();
+    let mut if_on_bl = None;
+    for (account, entry) in accounts.into_iter().zip(entries) {
+        let Some(user_json) = entry else {
+            continue;
+        };
+
+        let user = serde_json::from_str::<UserInfo>(&user_json)?;
+
+        if crate::expiry::active_offence_count(&user, now) > 0 {
+            if_on_bl = Some((account, user));
+            break;
+        }
+    }
 
     match if_on_bl {
         None => {}
-        Some(user_json) => {
+        Some((entry_id, user)) => {
+            let offence_count = crate::expiry::active_offence_count(&user, now);
+
+            let linked_note = if entry_id != member_id && alt_of.as_ref() != Some(&entry_id) {
+                format!(" (linked to <@{entry_id}>/{entry_id})")
+            } else {
+                alt_of
+                    .as_ref()
+                    .map(|p| format!(" (alt of <@{p}>/{p})"))
+                    .unwrap_or_default()
+            };
+
+            let risk = crate::risk::get_risk_score(&mut con, &entry_id, &user).await?;
+
+            // A webhook problem shouldn't stop the server from being warned
+            if let Err(error) = crate::webhooks::fire_webhook_event(
+                &mut con,
+                crate::enums::WebhookEvent::BlacklistedJoin,
+                Some(*member.guild_id.as_u64()),
+                serde_json::json!({
+                    "discord_id": member_id,
+                    "username": member.user.tag(),
+                    "offence_count": offence_count,
+                    "risk_score": risk.score,
+                    "risk_level": risk.level,
+                    "alt_of": alt_of,
+                    "flagged_account": entry_id,
+                }),
+            )
+            .await
+            {
+                event!(Level::WARN, "Failed to fire join webhook" = ?error);
+            }
+
             match guild_settings_json_in {
                 None => {} // Do nothing
                 // Check guild settings
                 Some(server_json) => {
-                    let settings: GuildSettings = serde_json::from_str(&server_json)?;
-                    let user: UserInfo = serde_json::from_str(&user_json)?;
+                    let (settings, _) = crate::settings::parse_guild_settings(&server_json)?;
 
-                    ChannelId::from(settings.channel_id.parse::<u64>().unwrap())
+                    // Guilds can ignore anyone under their score threshold
+                    if risk.score < settings.alert_min_score {
+                        return Ok(());
+                    }
+
+                    let kicked = match settings.kick_min_score {
+                        Some(kick_min_score) if risk.score >= kick_min_score => {
+                            match member
+                                .kick_with_reason(
+                                    ctx,
+                                    &format!("Blacklisted with a risk score of {}", risk.score),
+                                )
+                                .await
+                            {
+                                Ok(()) => true,
+                                Err(error) => {
+                                    event!(Level::WARN, "Failed to kick blacklisted user" = ?error);
+                                    false
+                                }
+                            }
+                        }
+                        _ => false,
+                    };
+
+                    // `settings_audit` lists servers whose alert channel is broken
+                    let Ok(channel_id) = settings.channel_id.parse::<u64>() else {
+                        event!(
+                            Level::WARN,
+                            "Guild has no usable alert channel" = member.guild_id.as_u64(),
+                            "Channel" = settings.channel_id
+                        );
+                        return Ok(());
+                    };
+
+                    ChannelId::from(channel_id)
                         .say(
                             ctx,
                             format!(
-                                "<@{}>/{0} Just joined your server with {} offenses on record",
-                                user.discord_id.unwrap(),
-                                user.offences.len()
+                                "<@{}>/{0}{} Just joined your server with {} offenses on record, risk score {} ({}){}",
+                                member_id,
+                                linked_note,
+                                offence_count,
+                                risk.score,
+                                risk.level,
+                                if kicked {
+                                    ", they've been kicked by your server's risk policy"
+                                } else {
+                                    ""
+                                }
                             ),
                         )
                         .await?;
@@ -117,7 +224,7 @@ pub async fn alt_kicker(
         None => {} // Do nothing
         // Check guild settings
         Some(json_in) => {
-            let settings: GuildSettings = serde_json::from_str(&json_in)?;
+            let (settings, _) = crate::settings::parse_guild_settings(&json_in)?;
             // Is kicking enabled?
             if settings.kick {
                 let uid = *member.user.id.as_u64();
@@ -154,6 +261,21 @@ pub async fn alt_kicker(
                         )
                         .await?;
 
+                    if let Err(error) = crate::webhooks::fire_webhook_event(
+                        &mut con,
+                        crate::enums::WebhookEvent::AltKicked,
+                        Some(*member.guild_id.as_u64()),
+                        serde_json::json!({
+                            "discord_id": uid.to_string(),
+                            "username": member.user.tag(),
+                            "account_age_days": age,
+                        }),
+                    )
+                    .await
+                    {
+                        event!(Level::WARN, "Failed to fire alt kick webhook" = ?error);
+                    }
+
                     let colour = &mut rand::thread_rng().gen_range(0..10_000_000);
 
                     ChannelId(settings.channel_id.parse::<u64>()?)
@@ -0,0 +1,323 @@
+use crate::search::queue_search_sync;
+use crate::structs::{Evidence, UserInfo};
+use crate::types::Error;
+use crate::vars::{
+    EVIDENCE_DIR, EVIDENCE_PUBLIC_URL, EVIDENCE_S3_ACCESS_KEY, EVIDENCE_S3_BUCKET,
+    EVIDENCE_S3_ENDPOINT, EVIDENCE_S3_REGION, EVIDENCE_S3_SECRET_KEY,
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use tracing::{event, instrument, Level};
+
+/// Hashes a file the way the evidence store names it
+#[must_use]
+pub fn hash_evidence(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Name a file is kept under in the evidence store, its hash plus whatever extension it was uploaded with
+#[must_use]
+pub fn evidence_key(sha256: &str, file_name: &str) -> String {
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_lowercase())
+        .filter(|ext| {
+            !ext.is_empty() && ext.len() <= 8 && ext.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+
+    match extension {
+        Some(extension) => format!("{sha256}.{extension}"),
+        None => sha256.to_string(),
+    }
+}
+
+/// Pulls the hash out of an evidence link, file name or hash
+#[must_use]
+pub fn parse_evidence_hash(input: &str) -> Option<String> {
+    let name = input.trim().rsplit('/').next()?;
+    let hash = name.split('.').next()?.to_lowercase();
+
+    (hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())).then_some(hash)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Works out the AWS Signature Version 4 signing key for one day, region and service
+#[must_use]
+pub fn sigv4_signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac_sha256(format!("AWS4{secret}").as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+
+    hmac_sha256(&key, b"aws4_request")
+}
+
+/// Sends a signed request for one object to the S3-compatible evidence bucket
+async fn s3_request(
+    method: reqwest::Method,
+    key: &str,
+    body: Vec<u8>,
+) -> Result<reqwest::Response, Error> {
+    let url = reqwest::Url::parse(&format!(
+        "{}/{EVIDENCE_S3_BUCKET}/{key}",
+        EVIDENCE_S3_ENDPOINT.trim_end_matches('/')
+    ))?;
+
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_string(),
+        (None, _) => return Err("The evidence S3 endpoint has no host".into()),
+    };
+
+    let now = chrono::Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = hash_evidence(&body);
+    let scope = format!("{date}/{EVIDENCE_S3_REGION}/s3/aws4_request");
+
+    let canonical_request = format!(
+        "{method}\n{}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\nhost;x-amz-content-sha256;x-amz-date\n{payload_hash}",
+        url.path()
+    );
+
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hash_evidence(canonical_request.as_bytes())
+    );
+
+    let signature = hex::encode(hmac_sha256(
+        &sigv4_signing_key(EVIDENCE_S3_SECRET_KEY, &date, EVIDENCE_S3_REGION, "s3"),
+        string_to_sign.as_bytes(),
+    ));
+
+    let response = reqwest::Client::new()
+        .request(method, url)
+        .header("x-amz-date", amz_date)
+        .header("x-amz-content-sha256", payload_hash)
+        .header(
+            "authorization",
+            format!("AWS4-HMAC-SHA256 Credential={EVIDENCE_S3_ACCESS_KEY}/{scope}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={signature}"),
+        )
+        .body(body)
+        .send()
+        .await?;
+
+    Ok(response)
+}
+
+/// Copies a file into the evidence store, on disk or in S3 depending on `EVIDENCE_S3_ENDPOINT`
+///
+/// Files are named after their hash so uploading the same file twice only keeps one copy.
+///
+/// # Errors
+///
+/// This function will return an error if the file couldn't be written to the store.
+#[instrument(skip(data))]
+pub async fn store_evidence(
+    data: Vec<u8>,
+    file_name: &str,
+    added_by: &str,
+) -> Result<Evidence, Error> {
+    let sha256 = hash_evidence(&data);
+    let key = evidence_key(&sha256, file_name);
+    let size = data.len() as u64;
+
+    if EVIDENCE_S3_ENDPOINT.is_empty() {
+        let path = std::path::Path::new(EVIDENCE_DIR).join(&key);
+
+        if !path.exists() {
+            tokio::fs::create_dir_all(EVIDENCE_DIR).await?;
+            tokio::fs::write(&path, data).await?;
+        }
+    } else {
+        let response = s3_request(reqwest::Method::PUT, &key, data).await?;
+
+        if !response.status().is_success() {
+            return Err(format!("The evidence bucket answered with {}", response.status()).into());
+        }
+    }
+
+    Ok(Evidence {
+        sha256,
+        file_name: file_name.to_string(),
+        size,
+        url: format!("{}/{key}", EVIDENCE_PUBLIC_URL.trim_end_matches('/')),
+        added_by: added_by.to_string(),
+        added_at: chrono::Utc::now().timestamp(),
+    })
+}
+
+/// Deletes a file from the evidence store, a file that's already gone isn't an error
+async fn delete_evidence_file(key: &str) -> Result<(), Error> {
+    if EVIDENCE_S3_ENDPOINT.is_empty() {
+        match tokio::fs::remove_file(std::path::Path::new(EVIDENCE_DIR).join(key)).await {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    } else {
+        let response = s3_request(reqwest::Method::DELETE, key, Vec::new()).await?;
+
+        if response.status().is_success() || response.status() == reqwest::StatusCode::NOT_FOUND {
+            Ok(())
+        } else {
+            Err(format!("The evidence bucket answered with {}", response.status()).into())
+        }
+    }
+}
+
+/// Records which user's offences use a piece of evidence so it can be purged later
+pub fn track_evidence(pipe: &mut redis::Pipeline, sha256: &str, discord_id: &str) {
+    pipe.cmd("SADD")
+        .arg(format!("evidence:{sha256}"))
+        .arg(discord_id)
+        .ignore();
+}
+
+/// Every name a piece of evidence could be stored under that can be worked out without an offence pointing at it
+///
+/// That's the bare hash, the file name from `link` if it has one and, when files are kept on disk, anything in `EVIDENCE_DIR` starting with the hash.
+async fn unreferenced_evidence_keys(sha256: &str, link: &str) -> Vec<String> {
+    let mut keys = vec![sha256.to_string()];
+
+    if let Some(name) = link.trim().rsplit('/').next() {
+        if parse_evidence_hash(name).as_deref() == Some(sha256) {
+            keys.push(evidence_key(sha256, name));
+        }
+    }
+
+    if EVIDENCE_S3_ENDPOINT.is_empty() {
+        if let Ok(mut dir) = tokio::fs::read_dir(EVIDENCE_DIR).await {
+            while let Ok(Some(entry)) = dir.next_entry().await {
+                let name = entry.file_name().to_string_lossy().to_string();
+
+                if name.starts_with(sha256) {
+                    keys.push(name);
+                }
+            }
+        }
+    }
+
+    keys
+}
+
+/// Deletes a piece of evidence from the store and takes it off every offence it was attached to
+///
+/// The file is deleted even if no offence uses it anymore, like after a failed `/add` or a restore, `link` is what was given to find it by.
+/// Returns the Discord IDs of the users whose offences were changed.
+///
+/// # Errors
+///
+/// This function will return an error if the DB or evidence store can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn purge_evidence(
+    con: &mut redis::aio::MultiplexedConnection,
+    sha256: &str,
+    link: &str,
+) -> Result<Vec<String>, Error> {
+    let discord_ids: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(format!("evidence:{sha256}"))
+        .query_async(con)
+        .await?;
+
+    let mut changed = Vec::new();
+    let mut keys = Vec::new();
+    let mut pipe = redis::pipe();
+
+    for discord_id in discord_ids {
+        let json_in: Option<String> = redis::cmd("JSON.GET")
+            .arg(format!("user:{discord_id}"))
+            .clone()
+            .query_async(con)
+            .await?;
+
+        let Some(json) = json_in else {
+            continue;
+        };
+
+        let mut user: UserInfo = serde_json::from_str(&json)?;
+        let mut removed = false;
+
+        for offense in &mut user.offences {
+            offense.evidence.retain(|evidence| {
+                let keep = evidence.sha256 != sha256;
+
+                if !keep {
+                    removed = true;
+                    keys.push(evidence_key(&evidence.sha256, &evidence.file_name));
+                }
+
+                keep
+            });
+        }
+
+        if removed {
+            pipe.cmd("JSON.SET")
+                .arg(format!("user:{discord_id}"))
+                .arg("$".to_string())
+                .arg(serde_json::to_string(&user)?)
+                .ignore();
+
+            changed.push(discord_id);
+        }
+    }
+
+    pipe.cmd("DEL").arg(format!("evidence:{sha256}")).ignore();
+    pipe.atomic().query_async::<_, ()>(con).await?;
+
+    keys.extend(unreferenced_evidence_keys(sha256, link).await);
+
+    // The same file can have been uploaded under different extensions
+    keys.sort();
+    keys.dedup();
+
+    for key in keys {
+        if let Err(error) = delete_evidence_file(&key).await {
+            event!(Level::WARN, "Failed to delete evidence file" = key, error = ?error);
+        }
+    }
+
+    queue_search_sync(con, &changed).await?;
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod evidence_tests {
+    use super::*;
+
+    #[test]
+    fn evidence_key_test() {
+        let hash = hash_evidence(b"ripped model");
+
+        assert_eq!(hash.len(), 64);
+        assert_eq!(evidence_key(&hash, "Proof.PNG"), format!("{hash}.png"));
+        assert_eq!(evidence_key(&hash, "no_extension"), hash);
+        assert_eq!(evidence_key(&hash, "weird.ex/../t"), hash);
+
+        assert_eq!(
+            parse_evidence_hash(&format!("https://evidence.example.com/{hash}.png")),
+            Some(hash.clone())
+        );
+        assert_eq!(parse_evidence_hash(&hash.to_uppercase()), Some(hash));
+        assert_eq!(parse_evidence_hash("proof.png"), None);
+    }
+
+    #[test]
+    fn sigv4_signing_key_test() {
+        // Example from the AWS Signature Version 4 documentation
+        assert_eq!(
+            hex::encode(sigv4_signing_key(
+                "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+                "20150830",
+                "us-east-1",
+                "iam"
+            )),
+            "c4afb1cc5771d871763a393e44b703571b55cc28424d1a5e86da6ed3c154a4b9"
+        );
+    }
+}
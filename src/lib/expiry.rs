@@ -0,0 +1,179 @@
+use crate::search::queue_search_sync;
+use crate::structs::{ArchivedOffence, Offense, UserInfo};
+use crate::types::Error;
+use crate::utils::open_redis_connection;
+use crate::vars::OFFENCE_EXPIRY_INTERVAL_SECS;
+use std::time::Duration;
+use tracing::{event, instrument, Level};
+
+/// Checks if an offence still counts at `now`
+#[must_use]
+pub fn is_active(offense: &Offense, now: i64) -> bool {
+    offense.expires_at.is_none_or(|expires_at| expires_at > now)
+}
+
+/// How many of a user's offences still count at `now`
+#[must_use]
+pub fn active_offence_count(user: &UserInfo, now: i64) -> usize {
+    user.offences
+        .iter()
+        .filter(|offense| is_active(offense, now))
+        .count()
+}
+
+/// Splits offences into the ones that still count and the ones that have expired
+#[must_use]
+pub fn split_expired(offences: Vec<Offense>, now: i64) -> (Vec<Offense>, Vec<Offense>) {
+    offences
+        .into_iter()
+        .partition(|offense| is_active(offense, now))
+}
+
+/// Reads every offence that's been archived for a user, oldest first
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn get_archived_offences(
+    con: &mut redis::aio::MultiplexedConnection,
+    discord_id: &str,
+) -> Result<Vec<ArchivedOffence>, Error> {
+    let entries: Vec<String> = redis::cmd("LRANGE")
+        .arg(format!("offence-archive:{discord_id}"))
+        .arg(0)
+        .arg(-1)
+        .query_async(con)
+        .await?;
+
+    let mut archived = Vec::new();
+    for entry in entries {
+        archived.push(serde_json::from_str(&entry)?);
+    }
+
+    Ok(archived)
+}
+
+/// Moves every expired offence in the DB into `offence-archive:`
+///
+/// Returns the Discord IDs of the users that had offences archived.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn archive_expired_offences(
+    con: &mut redis::aio::MultiplexedConnection,
+) -> Result<Vec<String>, Error> {
+    let key_list: Vec<String> = redis::cmd("KEYS")
+        .arg("user:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    if key_list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut key_pipe = redis::pipe();
+
+    for key in &key_list {
+        key_pipe.cmd("JSON.GET").arg(key);
+    }
+
+    let entries: Vec<Option<String>> = key_pipe.atomic().query_async(con).await?;
+
+    let now = chrono::Utc::now().timestamp();
+    let mut archived_users = Vec::new();
+    let mut pipe = redis::pipe();
+
+    for (key, entry) in key_list.iter().zip(entries) {
+        let Some(entry) = entry else {
+            continue;
+        };
+
+        let user: UserInfo = serde_json::from_str(&entry)?;
+
+        if user.offences.iter().all(|offense| is_active(offense, now)) {
+            continue;
+        }
+
+        let (_, expired) = split_expired(user.offences, now);
+
+        let discord_id = key.trim_start_matches("user:").to_string();
+
+        // Deleted by filter so offences added since the entry was read are left alone
+        pipe.cmd("JSON.DEL")
+            .arg(key)
+            .arg(format!("$.offences[?(@.expires_at<={now})]"))
+            .ignore();
+
+        for offence in expired {
+            pipe.cmd("RPUSH")
+                .arg(format!("offence-archive:{discord_id}"))
+                .arg(serde_json::to_string(&ArchivedOffence {
+                    offence,
+                    archived_at: now,
+                })?)
+                .ignore();
+        }
+
+        archived_users.push(discord_id);
+    }
+
+    if archived_users.is_empty() {
+        return Ok(archived_users);
+    }
+
+    pipe.atomic().query_async::<_, ()>(con).await?;
+    queue_search_sync(con, &archived_users).await?;
+
+    Ok(archived_users)
+}
+
+/// Archives expired offences every [`OFFENCE_EXPIRY_INTERVAL_SECS`] forever
+///
+/// Spawn this once when the bot starts.
+pub async fn run_offence_expiry_worker() {
+    loop {
+        let result = async {
+            let mut con = open_redis_connection().await?;
+            archive_expired_offences(&mut con).await
+        }
+        .await;
+
+        match result {
+            Ok(users) if !users.is_empty() => {
+                event!(Level::INFO, "Archived expired offences" = users.len());
+            }
+            Ok(_) => {}
+            Err(error) => event!(Level::WARN, "Failed to archive expired offences" = ?error),
+        }
+
+        tokio::time::sleep(Duration::from_secs(OFFENCE_EXPIRY_INTERVAL_SECS)).await;
+    }
+}
+
+#[cfg(test)]
+mod expiry_tests {
+    use super::*;
+
+    fn offence(expires_at: Option<i64>) -> Offense {
+        Offense {
+            expires_at,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn split_expired_test() {
+        let (active, expired) = split_expired(
+            vec![offence(None), offence(Some(50)), offence(Some(150))],
+            100,
+        );
+
+        assert_eq!(active, vec![offence(None), offence(Some(150))]);
+        assert_eq!(expired, vec![offence(Some(50))]);
+        assert!(!is_active(&offence(Some(100)), 100));
+    }
+}
@@ -0,0 +1,769 @@
+use crate::backup::merge_user;
+use crate::enums::PeerTrust;
+use crate::import::parse_discord_id;
+use crate::search::queue_search_sync;
+use crate::structs::{
+    FederationBatch, FederationPeer, FederationReceipt, Provenance, QuarantinedUser, UserInfo,
+};
+use crate::types::Error;
+use crate::utils::{open_redis_connection, sign_body};
+use crate::vars::{
+    FEDERATION_BIND_ADDR, FEDERATION_INSTANCE_ID, FEDERATION_MAX_BATCH_AGE_SECS,
+    FEDERATION_SYNC_INTERVAL_SECS,
+};
+use crate::vrchat::{has_vrc_id, index_vrc_id, normalize_vrc_id};
+use axum::body::Bytes;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::{Json, Router};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::time::Duration;
+use tracing::{event, instrument, Level};
+
+/// Header holding the sending instance's ID
+pub const FEDERATION_PEER_HEADER: &str = "x-fbt-peer";
+
+/// Header holding the hex HMAC-SHA256 of the request body, signed with the peer's shared secret
+pub const FEDERATION_SIGNATURE_HEADER: &str = "x-fbt-signature";
+
+/// Path peers send batches to
+pub const FEDERATION_BATCH_PATH: &str = "/federation/v1/users";
+
+/// Checks a request body was signed with a peer's shared secret, in constant time
+#[must_use]
+pub fn verify_body(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Strips a user down to what gets shared with peers, only offences added on this instance are sent so entries never bounce between peers
+#[must_use]
+pub fn outgoing_user(mut user: UserInfo) -> Option<UserInfo> {
+    user.offences.retain(|offence| offence.provenance.is_none());
+
+    if user.offences.is_empty() {
+        None
+    } else {
+        Some(user)
+    }
+}
+
+/// Marks every offence on a received user as coming from `peer`
+///
+/// Returns `None` if the user doesn't have a valid Discord ID or any offences.
+#[must_use]
+pub fn tag_incoming_user(mut user: UserInfo, peer: &str, received_at: i64) -> Option<UserInfo> {
+    user.discord_id = Some(parse_discord_id(user.discord_id.as_deref()?)?);
+
+    for offence in &mut user.offences {
+        offence.provenance = Some(Provenance {
+            peer: peer.to_string(),
+            received_at,
+        });
+    }
+
+    if user.offences.is_empty() {
+        None
+    } else {
+        Some(user)
+    }
+}
+
+/// Merges a received user into an existing one, skipping offences that are already there from the same server with the same reason
+///
+/// Returns the merged user and how many offences were new.
+#[must_use]
+pub fn merge_federated_user(
+    existing: Option<UserInfo>,
+    mut incoming: UserInfo,
+) -> (UserInfo, usize) {
+    let known: HashSet<(String, String)> = existing
+        .iter()
+        .flat_map(|user| &user.offences)
+        .map(|offence| (offence.guild_id.clone(), offence.reason.clone()))
+        .collect();
+
+    incoming
+        .offences
+        .retain(|offence| !known.contains(&(offence.guild_id.clone(), offence.reason.clone())));
+
+    let new_offences = incoming.offences.len();
+
+    (merge_user(existing, incoming), new_offences)
+}
+
+/// Reads a peer from the DB
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or the entry is invalid.
+#[instrument(skip(con))]
+pub async fn get_peer(
+    con: &mut redis::aio::MultiplexedConnection,
+    id: &str,
+) -> Result<Option<FederationPeer>, Error> {
+    let json_in: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!("federation-peer:{id}"))
+        .clone()
+        .query_async(con)
+        .await?;
+
+    match json_in {
+        None => Ok(None),
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+    }
+}
+
+/// Writes a peer to the DB
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con, peer))]
+pub async fn save_peer(
+    con: &mut redis::aio::MultiplexedConnection,
+    peer: &FederationPeer,
+) -> Result<(), Error> {
+    redis::cmd("JSON.SET")
+        .arg(format!("federation-peer:{}", peer.id))
+        .arg("$".to_string())
+        .arg(serde_json::to_string(peer)?)
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Reads every peer from the DB, sorted by ID
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn get_all_peers(
+    con: &mut redis::aio::MultiplexedConnection,
+) -> Result<Vec<FederationPeer>, Error> {
+    let key_list: Vec<String> = redis::cmd("KEYS")
+        .arg("federation-peer:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    if key_list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut key_pipe = redis::pipe();
+
+    for key in key_list {
+        key_pipe.cmd("JSON.GET").arg(key);
+    }
+
+    let entries: Vec<String> = key_pipe.atomic().query_async(con).await?;
+
+    let mut peers = Vec::new();
+    for entry in entries {
+        let peer: FederationPeer = serde_json::from_str(&entry)?;
+        peers.push(peer);
+    }
+
+    peers.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(peers)
+}
+
+/// Removes a peer along with anything queued for it or received from it that's still in quarantine
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn remove_peer(
+    con: &mut redis::aio::MultiplexedConnection,
+    id: &str,
+) -> Result<(), Error> {
+    let quarantined: Vec<String> = redis::cmd("KEYS")
+        .arg(format!("federation-quarantine:{id}:*"))
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let mut pipe = redis::pipe();
+    pipe.cmd("DEL")
+        .arg(format!("federation-peer:{id}"))
+        .arg(format!("federation-outbox:{id}"))
+        .ignore();
+
+    for key in quarantined {
+        pipe.cmd("DEL").arg(key).ignore();
+    }
+
+    pipe.atomic().query_async::<_, ()>(con).await?;
+
+    Ok(())
+}
+
+/// Queues users to be sent to every peer on the federation worker's next run
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn queue_federation_sync(
+    con: &mut redis::aio::MultiplexedConnection,
+    discord_ids: &[String],
+) -> Result<(), Error> {
+    if discord_ids.is_empty() {
+        return Ok(());
+    }
+
+    let peers = get_all_peers(con).await?;
+
+    if peers.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+
+    for peer in peers {
+        pipe.cmd("RPUSH")
+            .arg(format!("federation-outbox:{}", peer.id))
+            .arg(discord_ids)
+            .ignore();
+    }
+
+    pipe.atomic().query_async::<_, ()>(con).await?;
+
+    Ok(())
+}
+
+/// Reads every user waiting for review, optionally only from one peer
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn get_quarantine(
+    con: &mut redis::aio::MultiplexedConnection,
+    peer: Option<&str>,
+) -> Result<Vec<QuarantinedUser>, Error> {
+    let key_list: Vec<String> = redis::cmd("KEYS")
+        .arg(format!("federation-quarantine:{}:*", peer.unwrap_or("*")))
+        .clone()
+        .query_async(con)
+        .await?;
+
+    if key_list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut key_pipe = redis::pipe();
+
+    for key in key_list {
+        key_pipe.cmd("JSON.GET").arg(key);
+    }
+
+    let entries: Vec<String> = key_pipe.atomic().query_async(con).await?;
+
+    let mut quarantine = Vec::new();
+    for entry in entries {
+        let item: QuarantinedUser = serde_json::from_str(&entry)?;
+        quarantine.push(item);
+    }
+
+    quarantine.sort_by_key(|item| item.received_at);
+
+    Ok(quarantine)
+}
+
+/// Accepts or rejects a quarantined user, accepted users are merged into the DB
+///
+/// Returns `false` if nothing from that peer is waiting for that user.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn review_quarantined(
+    con: &mut redis::aio::MultiplexedConnection,
+    peer: &str,
+    discord_id: &str,
+    accept: bool,
+) -> Result<bool, Error> {
+    let key = format!("federation-quarantine:{peer}:{discord_id}");
+
+    let json_in: Option<String> = redis::cmd("JSON.GET")
+        .arg(&key)
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let Some(json_in) = json_in else {
+        return Ok(false);
+    };
+
+    if accept {
+        let quarantined: QuarantinedUser = serde_json::from_str(&json_in)?;
+        merge_into_db(con, quarantined.user).await?;
+    }
+
+    redis::cmd("DEL")
+        .arg(&key)
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(true)
+}
+
+/// Merges a received user into `user:`, returns `true` if anything changed
+///
+/// Their VRChat ID is normalized and indexed like a local one, anything that isn't a VRChat ID is dropped.
+async fn merge_into_db(
+    con: &mut redis::aio::MultiplexedConnection,
+    mut user: UserInfo,
+) -> Result<bool, Error> {
+    let Some(discord_id) = user.discord_id.clone() else {
+        return Ok(false);
+    };
+
+    user.vrc_id = user.vrc_id.as_deref().and_then(normalize_vrc_id);
+
+    let existing: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!("user:{discord_id}"))
+        .clone()
+        .query_async(con)
+        .await?;
+    let existing: Option<UserInfo> = existing
+        .map(|json| serde_json::from_str(&json))
+        .transpose()?;
+
+    let is_new = existing.is_none();
+    let (merged, new_offences) = merge_federated_user(existing, user);
+
+    if !is_new && new_offences == 0 {
+        return Ok(false);
+    }
+
+    let mut pipe = redis::pipe();
+    pipe.atomic()
+        .cmd("JSON.SET")
+        .arg(format!("user:{discord_id}"))
+        .arg("$".to_string())
+        .arg(serde_json::to_string(&merged)?)
+        .ignore();
+
+    if let Some(vrc_id) = merged.vrc_id.as_deref().filter(|id| has_vrc_id(Some(id))) {
+        index_vrc_id(&mut pipe, vrc_id, &discord_id);
+    }
+
+    pipe.query_async::<_, ()>(con).await?;
+
+    queue_search_sync(con, &[discord_id]).await?;
+
+    Ok(true)
+}
+
+/// Puts a received user in quarantine, merged with anything from the same peer already waiting
+async fn quarantine_user(
+    con: &mut redis::aio::MultiplexedConnection,
+    peer: &str,
+    user: UserInfo,
+    received_at: i64,
+) -> Result<(), Error> {
+    let discord_id = user.discord_id.clone().unwrap_or_default();
+    let key = format!("federation-quarantine:{peer}:{discord_id}");
+
+    let existing: Option<String> = redis::cmd("JSON.GET")
+        .arg(&key)
+        .clone()
+        .query_async(con)
+        .await?;
+    let existing: Option<QuarantinedUser> = existing
+        .map(|json| serde_json::from_str(&json))
+        .transpose()?;
+
+    let (user, _) = merge_federated_user(existing.map(|q| q.user), user);
+
+    let quarantined = QuarantinedUser {
+        peer: peer.to_string(),
+        received_at,
+        user,
+    };
+
+    redis::cmd("JSON.SET")
+        .arg(&key)
+        .arg("$".to_string())
+        .arg(serde_json::to_string(&quarantined)?)
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Handles a batch from a peer according to how much it's trusted
+///
+/// Users who have been cleared on this instance always go to quarantine, even from trusted peers.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con, peer, batch))]
+pub async fn process_batch(
+    con: &mut redis::aio::MultiplexedConnection,
+    peer: &mut FederationPeer,
+    batch: FederationBatch,
+) -> Result<FederationReceipt, Error> {
+    let now = chrono::Utc::now().timestamp();
+    let mut receipt = FederationReceipt::default();
+
+    for user in batch.users {
+        let Some(user) = tag_incoming_user(user, &peer.id, now) else {
+            receipt.ignored += 1;
+            continue;
+        };
+
+        if peer.trust == PeerTrust::Ignore {
+            receipt.ignored += 1;
+            continue;
+        }
+
+        let cleared: bool = redis::cmd("EXISTS")
+            .arg(format!(
+                "cleared-user:{}",
+                user.discord_id.clone().unwrap_or_default()
+            ))
+            .query_async(con)
+            .await?;
+
+        if peer.trust == PeerTrust::Accept && !cleared {
+            if merge_into_db(con, user).await? {
+                receipt.accepted += 1;
+            } else {
+                receipt.ignored += 1;
+            }
+        } else {
+            quarantine_user(con, &peer.id, user, now).await?;
+            receipt.quarantined += 1;
+        }
+    }
+
+    peer.last_received = Some(now);
+    save_peer(con, peer).await?;
+
+    event!(
+        Level::INFO,
+        "Federation batch received" = peer.id,
+        "Accepted" = receipt.accepted,
+        "Quarantined" = receipt.quarantined,
+        "Ignored" = receipt.ignored
+    );
+
+    Ok(receipt)
+}
+
+/// Logs an error while handling a batch without telling the peer what went wrong
+fn internal_error(error: impl std::fmt::Debug) -> (StatusCode, String) {
+    event!(Level::ERROR, "Failed to process federation batch" = ?error);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal error".to_string(),
+    )
+}
+
+/// Checks the signature and age of a request to [`FEDERATION_BATCH_PATH`] and processes it
+async fn receive_batch(
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<FederationReceipt>, (StatusCode, String)> {
+    let unauthorized = |reason: &str| (StatusCode::UNAUTHORIZED, reason.to_string());
+
+    let peer_id = headers
+        .get(FEDERATION_PEER_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| unauthorized("Missing peer ID"))?;
+    let signature = headers
+        .get(FEDERATION_SIGNATURE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| unauthorized("Missing signature"))?;
+
+    let mut con = open_redis_connection().await.map_err(internal_error)?;
+
+    let Some(mut peer) = get_peer(&mut con, peer_id).await.map_err(internal_error)? else {
+        return Err(unauthorized("Unknown peer"));
+    };
+
+    if !verify_body(&peer.secret, &body, signature) {
+        return Err(unauthorized("Invalid signature"));
+    }
+
+    let batch: FederationBatch = serde_json::from_slice(&body)
+        .map_err(|error| (StatusCode::BAD_REQUEST, error.to_string()))?;
+
+    if batch.instance_id != peer.id {
+        return Err(unauthorized("Batch is from a different instance"));
+    }
+
+    if (chrono::Utc::now().timestamp() - batch.sent_at).abs() > FEDERATION_MAX_BATCH_AGE_SECS {
+        return Err(unauthorized("Batch is too old"));
+    }
+
+    let receipt = process_batch(&mut con, &mut peer, batch)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(receipt))
+}
+
+/// Runs the HTTP server peers send batches to
+///
+/// Spawn this once when the bot starts.
+///
+/// # Errors
+///
+/// This function will return an error if [`FEDERATION_BIND_ADDR`] can't be bound.
+pub async fn run_federation_server() -> Result<(), Error> {
+    let app = Router::new().route(FEDERATION_BATCH_PATH, post(receive_batch));
+
+    let listener = tokio::net::TcpListener::bind(FEDERATION_BIND_ADDR).await?;
+    event!(
+        Level::INFO,
+        "Federation server listening" = FEDERATION_BIND_ADDR
+    );
+
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+/// Sends the next batch from a peer's outbox, putting it back if the peer couldn't be reached
+async fn send_queued(
+    con: &mut redis::aio::MultiplexedConnection,
+    client: &reqwest::Client,
+    peer: &mut FederationPeer,
+) -> Result<(), Error> {
+    let outbox = format!("federation-outbox:{}", peer.id);
+
+    let queued: Option<Vec<String>> = redis::cmd("LPOP")
+        .arg(&outbox)
+        .arg(100)
+        .query_async(con)
+        .await?;
+
+    let Some(queued) = queued else {
+        return Ok(());
+    };
+
+    let discord_ids: Vec<String> = queued
+        .iter()
+        .cloned()
+        .collect::<HashSet<String>>()
+        .into_iter()
+        .collect();
+
+    let mut key_pipe = redis::pipe();
+
+    for discord_id in &discord_ids {
+        key_pipe.cmd("JSON.GET").arg(format!("user:{discord_id}"));
+    }
+
+    let entries: Vec<Option<String>> = match key_pipe.atomic().query_async(con).await {
+        Ok(entries) => entries,
+        Err(error) => {
+            requeue_outbox(con, &outbox, &queued).await?;
+            return Err(error.into());
+        }
+    };
+
+    let mut users = Vec::new();
+    for (discord_id, json) in discord_ids.iter().zip(entries) {
+        let Some(json) = json else {
+            continue;
+        };
+
+        // A broken entry won't get better by retrying so it's left out rather than holding up the rest
+        match serde_json::from_str(&json) {
+            Ok(user) => users.extend(outgoing_user(user)),
+            Err(error) => {
+                event!(Level::WARN, "Skipped invalid user for federation" = discord_id, error = ?error);
+            }
+        }
+    }
+
+    if users.is_empty() {
+        return Ok(());
+    }
+
+    let batch = FederationBatch {
+        instance_id: FEDERATION_INSTANCE_ID.to_string(),
+        sent_at: chrono::Utc::now().timestamp(),
+        users,
+    };
+    let body = serde_json::to_vec(&batch)?;
+
+    let response = client
+        .post(format!(
+            "{}{FEDERATION_BATCH_PATH}",
+            peer.url.trim_end_matches('/')
+        ))
+        .header(FEDERATION_PEER_HEADER, FEDERATION_INSTANCE_ID)
+        .header(FEDERATION_SIGNATURE_HEADER, sign_body(&peer.secret, &body))
+        .header("content-type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status);
+
+    let response = match response {
+        Ok(response) => response,
+        Err(error) => {
+            requeue_outbox(con, &outbox, &queued).await?;
+            return Err(error.into());
+        }
+    };
+
+    // The peer took the batch so it isn't sent again even if the receipt can't be read
+    peer.last_sent = Some(batch.sent_at);
+    save_peer(con, peer).await?;
+
+    match response.json::<FederationReceipt>().await {
+        Ok(receipt) => event!(
+            Level::DEBUG,
+            "Federation batch sent" = peer.id,
+            "Accepted" = receipt.accepted,
+            "Quarantined" = receipt.quarantined,
+            "Ignored" = receipt.ignored
+        ),
+        Err(error) => event!(
+            Level::WARN,
+            "Federation batch sent but the receipt couldn't be read" = peer.id,
+            error = ?error
+        ),
+    }
+
+    Ok(())
+}
+
+/// Puts a batch taken off a peer's outbox back so it's sent next time
+async fn requeue_outbox(
+    con: &mut redis::aio::MultiplexedConnection,
+    outbox: &str,
+    queued: &[String],
+) -> Result<(), Error> {
+    redis::cmd("RPUSH")
+        .arg(outbox)
+        .arg(queued)
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Sends every peer what's in its outbox forever
+///
+/// Spawn this once when the bot starts.
+pub async fn run_federation_worker() {
+    let client = reqwest::Client::new();
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(FEDERATION_SYNC_INTERVAL_SECS)).await;
+
+        let mut con = match open_redis_connection().await {
+            Ok(con) => con,
+            Err(error) => {
+                event!(Level::WARN, "Federation worker couldn't reach the DB" = ?error);
+                continue;
+            }
+        };
+
+        let peers = match get_all_peers(&mut con).await {
+            Ok(peers) => peers,
+            Err(error) => {
+                event!(Level::WARN, "Federation worker couldn't read peers" = ?error);
+                continue;
+            }
+        };
+
+        for mut peer in peers {
+            if let Err(error) = send_queued(&mut con, &client, &mut peer).await {
+                event!(Level::WARN, "Failed to send federation batch" = peer.id, error = ?error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod federation_tests {
+    use super::*;
+    use crate::structs::Offense;
+
+    fn offence(guild_id: &str, provenance: Option<Provenance>) -> Offense {
+        Offense {
+            guild_id: guild_id.to_string(),
+            reason: "Ripping".to_string(),
+            provenance,
+            ..Default::default()
+        }
+    }
+
+    fn user(offences: Vec<Offense>) -> UserInfo {
+        UserInfo {
+            vrc_id: None,
+            username: Some("Ripper".to_string()),
+            discord_id: Some("000000000000000001".to_string()),
+            offences,
+        }
+    }
+
+    #[test]
+    fn signature_test() {
+        let signature = sign_body("secret", b"body");
+
+        assert!(verify_body("secret", b"body", &signature));
+        assert!(!verify_body("secret", b"other body", &signature));
+        assert!(!verify_body("other secret", b"body", &signature));
+        assert!(!verify_body("secret", b"body", "not hex"));
+    }
+
+    #[test]
+    fn federated_user_test() {
+        let from_peer = Some(Provenance {
+            peer: "peer".to_string(),
+            received_at: 1,
+        });
+
+        let outgoing = outgoing_user(user(vec![
+            offence("1", None),
+            offence("2", from_peer.clone()),
+        ]));
+        assert_eq!(outgoing, Some(user(vec![offence("1", None)])));
+        assert_eq!(
+            outgoing_user(user(vec![offence("2", from_peer.clone())])),
+            None
+        );
+
+        let incoming = tag_incoming_user(
+            user(vec![offence("1", None), offence("2", None)]),
+            "peer",
+            1,
+        )
+        .unwrap();
+        assert!(incoming.offences.iter().all(|o| o.provenance == from_peer));
+
+        let (merged, new_offences) =
+            merge_federated_user(Some(user(vec![offence("1", None)])), incoming);
+        assert_eq!(new_offences, 1);
+        assert_eq!(
+            merged.offences,
+            vec![offence("1", None), offence("2", from_peer)]
+        );
+    }
+}
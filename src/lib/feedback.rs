@@ -0,0 +1,263 @@
+use crate::enums::{FeedbackStatus, StaffTier};
+use crate::staff::has_staff_tier;
+use crate::structs::Feedback;
+use crate::types::Error;
+use crate::vars::FEEDBACK_CHANNEL_ID;
+use poise::serenity_prelude::{
+    self as serenity, colours, ButtonStyle, ChannelId, Colour, CreateComponents, CreateEmbed,
+    InteractionResponseType, MessageComponentInteraction,
+};
+use tracing::{event, instrument, Level};
+
+/// Every status button's custom ID starts with this so the event handler knows it's ours
+pub const FEEDBACK_BUTTON_PREFIX: &str = "feedback-status:";
+
+/// Fills out the embed a piece of feedback gets posted to the feedback channel as
+pub fn feedback_embed<'a>(e: &'a mut CreateEmbed, feedback: &Feedback) -> &'a mut CreateEmbed {
+    let colour = match feedback.status {
+        FeedbackStatus::New => Colour::GOLD,
+        FeedbackStatus::Acknowledged => Colour::BLURPLE,
+        FeedbackStatus::Done => colours::css::POSITIVE,
+    };
+
+    e.title(format!("Feedback #{}", feedback.id))
+        .description(&feedback.message)
+        .color(colour)
+        .author(|a| {
+            a.icon_url(&feedback.avatar_url)
+                .name(format!("{} ({})", feedback.username, feedback.user_id))
+        })
+        .thumbnail("https://media.giphy.com/media/U4sfHXAALLYBQzPcWk/giphy.gif")
+        .field("Status:", feedback.status, true)
+        .field("Replies:", feedback.replies.len(), true)
+        .field("Sent:", format!("<t:{}:R>", feedback.created_at), true)
+}
+
+/// Adds the buttons staff use to move feedback between statuses, the current status' button is disabled
+pub fn feedback_buttons<'a>(
+    c: &'a mut CreateComponents,
+    feedback: &Feedback,
+) -> &'a mut CreateComponents {
+    c.create_action_row(|r| {
+        for (status, label, style) in [
+            (FeedbackStatus::New, "Reopen", ButtonStyle::Secondary),
+            (
+                FeedbackStatus::Acknowledged,
+                "Acknowledge",
+                ButtonStyle::Primary,
+            ),
+            (FeedbackStatus::Done, "Done", ButtonStyle::Success),
+        ] {
+            r.create_button(|b| {
+                b.custom_id(format!("{FEEDBACK_BUTTON_PREFIX}{}:{status}", feedback.id))
+                    .label(label)
+                    .style(style)
+                    .disabled(feedback.status == status)
+            });
+        }
+        r
+    })
+}
+
+/// Pulls the feedback number and new status out of a status button's custom ID
+#[must_use]
+pub fn parse_feedback_button(custom_id: &str) -> Option<(u64, FeedbackStatus)> {
+    let (id, status) = custom_id
+        .strip_prefix(FEEDBACK_BUTTON_PREFIX)?
+        .split_once(':')?;
+
+    let status = match status {
+        "New" => FeedbackStatus::New,
+        "Acknowledged" => FeedbackStatus::Acknowledged,
+        "Done" => FeedbackStatus::Done,
+        _ => return None,
+    };
+
+    Some((id.parse().ok()?, status))
+}
+
+/// Reads a piece of feedback from the DB
+#[instrument(skip(con))]
+pub async fn get_feedback(
+    con: &mut redis::aio::MultiplexedConnection,
+    id: u64,
+) -> Result<Option<Feedback>, Error> {
+    let json_in: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!("feedback:{id}"))
+        .clone()
+        .query_async(con)
+        .await?;
+
+    match json_in {
+        None => Ok(None),
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+    }
+}
+
+/// Writes a piece of feedback to the DB
+#[instrument(skip(con, feedback))]
+pub async fn save_feedback(
+    con: &mut redis::aio::MultiplexedConnection,
+    feedback: &Feedback,
+) -> Result<(), Error> {
+    redis::cmd("JSON.SET")
+        .arg(format!("feedback:{}", feedback.id))
+        .arg("$".to_string())
+        .arg(serde_json::to_string(feedback)?)
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Reads all feedback from the DB, oldest first
+///
+/// Feedback from before it had a status is stored as plain text under `feedback:<timestamp>-<id>-<tag>` and gets skipped.
+#[instrument(skip(con))]
+pub async fn get_all_feedback(
+    con: &mut redis::aio::MultiplexedConnection,
+) -> Result<Vec<Feedback>, Error> {
+    let key_list: Vec<String> = redis::cmd("KEYS")
+        .arg("feedback:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let key_list = key_list
+        .into_iter()
+        .filter(|k| {
+            k.strip_prefix("feedback:")
+                .is_some_and(|id| id.parse::<u64>().is_ok())
+        })
+        .collect::<Vec<String>>();
+
+    if key_list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut key_pipe = redis::pipe();
+
+    for key in key_list {
+        key_pipe.cmd("JSON.GET").arg(key);
+    }
+
+    let entries: Vec<String> = key_pipe.atomic().query_async(con).await?;
+
+    let mut feedback = Vec::new();
+    for entry in entries {
+        let item: Feedback = serde_json::from_str(&entry)?;
+        feedback.push(item);
+    }
+
+    feedback.sort_by_key(|f| f.id);
+
+    Ok(feedback)
+}
+
+/// Updates the feedback channel's post for a piece of feedback to match the DB
+///
+/// # Errors
+///
+/// This function will return an error if the post can't be edited.
+#[instrument(skip(http, feedback))]
+pub async fn update_feedback_post(http: &serenity::Http, feedback: &Feedback) -> Result<(), Error> {
+    if let Some(message_id) = feedback
+        .message_id
+        .as_ref()
+        .and_then(|id| id.parse::<u64>().ok())
+    {
+        ChannelId(FEEDBACK_CHANNEL_ID)
+            .edit_message(http, message_id, |m| {
+                m.embed(|e| feedback_embed(e, feedback))
+                    .components(|c| feedback_buttons(c, feedback))
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Handles staff pressing one of the status buttons on a feedback post
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or discord rejects the response.
+#[instrument(skip(ctx, press))]
+pub async fn handle_feedback_button(
+    ctx: &serenity::Context,
+    press: &MessageComponentInteraction,
+) -> Result<(), Error> {
+    let Some((id, status)) = parse_feedback_button(&press.data.custom_id) else {
+        return Ok(());
+    };
+
+    let mut con = crate::utils::open_redis_connection().await?;
+
+    if !has_staff_tier(&mut con, *press.user.id.as_u64(), StaffTier::Support).await? {
+        press
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| {
+                        d.content("Only bot staff can change the status of feedback!")
+                            .ephemeral(true)
+                    })
+            })
+            .await?;
+        return Ok(());
+    }
+
+    let Some(mut feedback) = get_feedback(&mut con, id).await? else {
+        press
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| {
+                        d.content(format!("Feedback #{id} isn't in the DB anymore."))
+                            .ephemeral(true)
+                    })
+            })
+            .await?;
+        return Ok(());
+    };
+
+    feedback.status = status;
+    save_feedback(&mut con, &feedback).await?;
+
+    event!(
+        Level::INFO,
+        "Feedback status changed" = id,
+        "To" = %status,
+        "By" = press.user.id.as_u64()
+    );
+
+    press
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| {
+                    d.embed(|e| feedback_embed(e, &feedback))
+                        .components(|c| feedback_buttons(c, &feedback))
+                })
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod feedback_tests {
+    use super::*;
+
+    #[test]
+    fn parse_feedback_button_test() {
+        assert_eq!(
+            parse_feedback_button("feedback-status:12:Done"),
+            Some((12, FeedbackStatus::Done))
+        );
+        assert_eq!(
+            parse_feedback_button("feedback-status:3:Acknowledged"),
+            Some((3, FeedbackStatus::Acknowledged))
+        );
+        assert_eq!(parse_feedback_button("feedback-status:3:Maybe"), None);
+        assert_eq!(parse_feedback_button("feedback-status:abc:New"), None);
+        assert_eq!(parse_feedback_button("123456789:ban_all"), None);
+    }
+}
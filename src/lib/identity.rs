@@ -0,0 +1,279 @@
+use crate::structs::{Identity, LinkedAccount};
+use crate::types::Error;
+use std::collections::{hash_map::Entry, HashMap};
+use tracing::instrument;
+
+/// Finds who an account is an alt of, `None` if it's the primary account or not in the identity at all
+#[must_use]
+pub fn alt_of<'a>(identity: &'a Identity, account: &str) -> Option<&'a str> {
+    (identity.primary != account && identity.accounts.iter().any(|a| a.account == account))
+        .then_some(identity.primary.as_str())
+}
+
+/// Takes an account out of an identity, the oldest Discord alt takes over if the primary is removed
+///
+/// Gives `None` once there's nothing left linked, the identity should be deleted then.
+#[must_use]
+pub fn remove_account(mut identity: Identity, account: &str) -> Option<Identity> {
+    identity.accounts.retain(|a| a.account != account);
+
+    if identity.primary == account {
+        let next = identity
+            .accounts
+            .iter()
+            .position(|a| !a.account.starts_with("usr_"))?;
+
+        identity.primary = identity.accounts.remove(next).account;
+    }
+
+    (!identity.accounts.is_empty()).then_some(identity)
+}
+
+/// Reads an identity from the DB
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or the entry is invalid.
+#[instrument(skip(con))]
+pub async fn get_identity(
+    con: &mut redis::aio::MultiplexedConnection,
+    id: u64,
+) -> Result<Option<Identity>, Error> {
+    let json_in: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!("identity:{id}"))
+        .clone()
+        .query_async(con)
+        .await?;
+
+    match json_in {
+        None => Ok(None),
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+    }
+}
+
+/// Reads the identity an account (Discord ID or `usr_` VRChat ID) belongs to
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or the entry is invalid.
+#[instrument(skip(con))]
+pub async fn get_identity_of(
+    con: &mut redis::aio::MultiplexedConnection,
+    account: &str,
+) -> Result<Option<Identity>, Error> {
+    let id: Option<u64> = redis::cmd("GET")
+        .arg(format!("identity-of:{account}"))
+        .query_async(con)
+        .await?;
+
+    match id {
+        None => Ok(None),
+        Some(id) => get_identity(con, id).await,
+    }
+}
+
+/// Reads the identities for a list of accounts at once, accounts that aren't linked are left out
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con, accounts))]
+pub async fn get_identities_of(
+    con: &mut redis::aio::MultiplexedConnection,
+    accounts: &[String],
+) -> Result<HashMap<String, Identity>, Error> {
+    let mut found = HashMap::new();
+
+    if accounts.is_empty() {
+        return Ok(found);
+    }
+
+    let mut id_pipe = redis::pipe();
+
+    for account in accounts {
+        id_pipe.cmd("GET").arg(format!("identity-of:{account}"));
+    }
+
+    let ids: Vec<Option<u64>> = id_pipe.query_async(con).await?;
+
+    let mut identities: HashMap<u64, Identity> = HashMap::new();
+
+    for id in ids.iter().flatten() {
+        if let Entry::Vacant(entry) = identities.entry(*id) {
+            if let Some(identity) = get_identity(con, *id).await? {
+                entry.insert(identity);
+            }
+        }
+    }
+
+    for (account, id) in accounts.iter().zip(ids) {
+        if let Some(identity) = id.and_then(|id| identities.get(&id)) {
+            found.insert(account.clone(), identity.clone());
+        }
+    }
+
+    Ok(found)
+}
+
+/// Links an account to `primary`, making a new identity if `primary` isn't in one yet
+///
+/// Check the account isn't already linked somewhere else first, it gets moved over otherwise.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn link_account(
+    con: &mut redis::aio::MultiplexedConnection,
+    primary: &str,
+    account: &str,
+    evidence: &str,
+    linked_by: &str,
+) -> Result<Identity, Error> {
+    let now = chrono::Utc::now().timestamp();
+
+    let mut identity = match get_identity_of(con, primary).await? {
+        Some(identity) => identity,
+        None => {
+            let id: u64 = redis::cmd("INCR")
+                .arg("identity-count")
+                .query_async(con)
+                .await?;
+
+            Identity {
+                id,
+                primary: primary.to_string(),
+                accounts: Vec::new(),
+                created_at: now,
+            }
+        }
+    };
+
+    identity.accounts.retain(|a| a.account != account);
+    identity.accounts.push(LinkedAccount {
+        account: account.to_string(),
+        evidence: evidence.to_string(),
+        linked_by: linked_by.to_string(),
+        linked_at: now,
+    });
+
+    redis::pipe()
+        .atomic()
+        .cmd("JSON.SET")
+        .arg(format!("identity:{}", identity.id))
+        .arg("$".to_string())
+        .arg(serde_json::to_string(&identity)?)
+        .ignore()
+        .cmd("SET")
+        .arg(format!("identity-of:{primary}"))
+        .arg(identity.id)
+        .ignore()
+        .cmd("SET")
+        .arg(format!("identity-of:{account}"))
+        .arg(identity.id)
+        .ignore()
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(identity)
+}
+
+/// Unlinks an account from its identity, deleting the identity once nothing else is linked
+///
+/// Returns what's left of the identity, `None` if it was deleted or the account wasn't linked.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn unlink_account(
+    con: &mut redis::aio::MultiplexedConnection,
+    account: &str,
+) -> Result<Option<Identity>, Error> {
+    let Some(identity) = get_identity_of(con, account).await? else {
+        return Ok(None);
+    };
+
+    let id = identity.id;
+    let before = identity.clone();
+
+    let mut pipe = redis::pipe();
+    pipe.atomic()
+        .cmd("DEL")
+        .arg(format!("identity-of:{account}"))
+        .ignore();
+
+    let remaining = remove_account(identity, account);
+
+    match &remaining {
+        Some(identity) => {
+            pipe.cmd("JSON.SET")
+                .arg(format!("identity:{id}"))
+                .arg("$".to_string())
+                .arg(serde_json::to_string(identity)?)
+                .ignore();
+        }
+        None => {
+            pipe.cmd("DEL").arg(format!("identity:{id}")).ignore();
+
+            for left in std::iter::once(before.primary.as_str())
+                .chain(before.accounts.iter().map(|a| a.account.as_str()))
+            {
+                pipe.cmd("DEL").arg(format!("identity-of:{left}")).ignore();
+            }
+        }
+    }
+
+    pipe.query_async::<_, ()>(con).await?;
+
+    Ok(remaining)
+}
+
+#[cfg(test)]
+mod identity_tests {
+    use super::*;
+
+    fn linked(account: &str) -> LinkedAccount {
+        LinkedAccount {
+            account: account.to_string(),
+            evidence: "Same avatar".to_string(),
+            linked_by: "1".to_string(),
+            linked_at: 0,
+        }
+    }
+
+    fn identity() -> Identity {
+        Identity {
+            id: 1,
+            primary: "100".to_string(),
+            accounts: vec![
+                linked("usr_c1644b5b-3ca4-45b4-97c6-a2a0de70d469"),
+                linked("200"),
+                linked("300"),
+            ],
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn alt_of_test() {
+        let identity = identity();
+
+        assert_eq!(alt_of(&identity, "200"), Some("100"));
+        assert_eq!(alt_of(&identity, "100"), None);
+        assert_eq!(alt_of(&identity, "400"), None);
+    }
+
+    #[test]
+    fn remove_account_test() {
+        let removed = remove_account(identity(), "300").unwrap();
+        assert_eq!(removed.accounts.len(), 2);
+
+        let promoted = remove_account(identity(), "100").unwrap();
+        assert_eq!(promoted.primary, "200");
+        assert_eq!(promoted.accounts.len(), 2);
+
+        let only_vrchat = remove_account(promoted, "300").unwrap();
+        assert_eq!(remove_account(only_vrchat, "200"), None);
+    }
+}
@@ -0,0 +1,804 @@
+use crate::enums::{ImportFormat, OffenceCategory, WebhookEvent};
+use crate::search::queue_search_sync;
+use crate::structs::{ColumnMapping, DceExport, ImportPlan, ImportRecord, Offense, UserInfo};
+use crate::types::Error;
+use crate::vars::{
+    BOT_IDS, IMPORT_ZIP_MAX_ENTRIES, IMPORT_ZIP_MAX_FILE_BYTES, IMPORT_ZIP_MAX_TOTAL_BYTES,
+};
+use crate::vrchat::{has_vrc_id, index_vrc_id, normalize_vrc_id};
+use crate::webhooks::fire_webhook_events;
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read};
+use std::str::FromStr;
+use tracing::{event, instrument, Level};
+
+/// Checks a value from an import file looks like a Discord ID and cleans it up
+#[must_use]
+pub fn parse_discord_id(value: &str) -> Option<String> {
+    let value = value.trim().trim_matches('"');
+
+    ((17..=20).contains(&value.len()) && value.chars().all(|c| c.is_ascii_digit()))
+        .then(|| value.to_string())
+}
+
+/// Guesses what kind of import file was uploaded from its name, peeking at JSON files to spot DiscordChatExporter exports
+#[must_use]
+pub fn detect_import_format(filename: &str, content: &[u8]) -> ImportFormat {
+    let filename = filename.to_lowercase();
+
+    if filename.ends_with(".csv") {
+        ImportFormat::Csv
+    } else if filename.ends_with(".zip") || content.starts_with(b"PK\x03\x04") {
+        ImportFormat::DiscordChatExporter
+    } else if filename.ends_with(".json") {
+        let is_dce = serde_json::from_slice::<serde_json::Value>(content).is_ok_and(|json| {
+            json.get("messages")
+                .is_some_and(serde_json::Value::is_array)
+        });
+
+        if is_dce {
+            ImportFormat::DiscordChatExporter
+        } else {
+            ImportFormat::Json
+        }
+    } else {
+        ImportFormat::IdList
+    }
+}
+
+/// Turns the optional parts of a row into a record, adding a warning if the category or VRChat ID isn't valid
+fn build_record(
+    discord_id: String,
+    username: Option<String>,
+    reason: Option<String>,
+    category: Option<String>,
+    vrc_id: Option<String>,
+    row: &str,
+    errors: &mut Vec<String>,
+) -> ImportRecord {
+    let non_empty = |value: Option<String>| {
+        value
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+
+    let category = non_empty(category).and_then(|category| {
+        OffenceCategory::from_str(&category).map_or_else(
+            |_| {
+                errors.push(format!(
+                    "{row}: unknown category `{category}`, it was left blank"
+                ));
+                None
+            },
+            Some,
+        )
+    });
+
+    let vrc_id = non_empty(vrc_id).and_then(|vrc_id| {
+        normalize_vrc_id(&vrc_id).or_else(|| {
+            errors.push(format!(
+                "{row}: `{vrc_id}` isn't a VRChat ID or profile link, it was left blank"
+            ));
+            None
+        })
+    });
+
+    ImportRecord {
+        discord_id,
+        username: non_empty(username),
+        reason: non_empty(reason),
+        category,
+        vrc_id,
+        avatar_url: None,
+        is_bot: false,
+    }
+}
+
+/// Reads users out of a CSV using `mapping` to find the right columns
+///
+/// Rows that can't be read are skipped and explained in the returned list of errors.
+///
+/// # Errors
+///
+/// This function will return an error if the CSV has no header or the ID column is missing.
+pub fn parse_csv_import(
+    content: &str,
+    mapping: &ColumnMapping,
+) -> Result<(Vec<ImportRecord>, Vec<String>), Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.as_bytes());
+
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name))
+    };
+
+    let Some(id_column) = column(&mapping.id) else {
+        return Err(format!(
+            "There is no `{}` column, the columns in this file are: {}",
+            mapping.id,
+            headers.iter().collect::<Vec<&str>>().join(", ")
+        )
+        .into());
+    };
+    let name_column = column(&mapping.name);
+    let reason_column = mapping.reason.as_deref().and_then(column);
+    let category_column = mapping.category.as_deref().and_then(column);
+    let vrc_column = mapping.vrc_id.as_deref().and_then(column);
+
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+
+    for (i, row) in reader.records().enumerate() {
+        // +2 since the header is line 1
+        let line = format!("Line {}", i + 2);
+
+        let row = match row {
+            Ok(row) => row,
+            Err(err) => {
+                errors.push(format!("{line}: {err}"));
+                continue;
+            }
+        };
+
+        let get = |column: Option<usize>| {
+            column
+                .and_then(|c| row.get(c))
+                .map(std::string::ToString::to_string)
+        };
+
+        let Some(discord_id) = row.get(id_column).and_then(parse_discord_id) else {
+            errors.push(format!(
+                "{line}: `{}` isn't a Discord ID",
+                row.get(id_column).unwrap_or_default()
+            ));
+            continue;
+        };
+
+        records.push(build_record(
+            discord_id,
+            get(name_column),
+            get(reason_column),
+            get(category_column),
+            get(vrc_column),
+            &line,
+            &mut errors,
+        ));
+    }
+
+    Ok((records, errors))
+}
+
+/// Reads users out of a JSON array of IDs, or of objects using `mapping` for the key names
+///
+/// # Errors
+///
+/// This function will return an error if the file isn't JSON or isn't an array.
+pub fn parse_json_import(
+    content: &str,
+    mapping: &ColumnMapping,
+) -> Result<(Vec<ImportRecord>, Vec<String>), Error> {
+    let json: serde_json::Value = serde_json::from_str(content)?;
+
+    let Some(items) = json.as_array() else {
+        return Err("The JSON file needs to be a list of IDs or a list of objects".into());
+    };
+
+    let text = |value: Option<&serde_json::Value>| match value {
+        Some(serde_json::Value::String(s)) => Some(s.clone()),
+        Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    };
+
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+
+    for (i, item) in items.iter().enumerate() {
+        let entry = format!("Entry {}", i + 1);
+
+        let (id, object) = match item {
+            serde_json::Value::Object(object) => (text(object.get(&mapping.id)), Some(object)),
+            value => (text(Some(value)), None),
+        };
+
+        let Some(discord_id) = id.as_deref().and_then(parse_discord_id) else {
+            errors.push(format!("{entry}: `{item}` doesn't have a Discord ID"));
+            continue;
+        };
+
+        let field =
+            |key: Option<&String>| object.and_then(|object| text(key.and_then(|k| object.get(k))));
+
+        records.push(build_record(
+            discord_id,
+            field(Some(&mapping.name)),
+            field(mapping.reason.as_ref()),
+            field(mapping.category.as_ref()),
+            field(mapping.vrc_id.as_ref()),
+            &entry,
+            &mut errors,
+        ));
+    }
+
+    Ok((records, errors))
+}
+
+/// Reads users out of a plain list with one ID per line, anything after the ID is used as their name
+///
+/// Blank lines and lines starting with `#` are ignored.
+#[must_use]
+pub fn parse_id_list_import(content: &str) -> (Vec<ImportRecord>, Vec<String>) {
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (id, name) = line
+            .split_once(|c: char| c.is_whitespace() || c == ',')
+            .unwrap_or((line, ""));
+
+        match parse_discord_id(id) {
+            Some(discord_id) => records.push(ImportRecord {
+                discord_id,
+                username: Some(name.trim().to_string()).filter(|n| !n.is_empty()),
+                reason: None,
+                category: None,
+                vrc_id: None,
+                avatar_url: None,
+                is_bot: false,
+            }),
+            None => errors.push(format!("Line {}: `{id}` isn't a Discord ID", i + 1)),
+        }
+    }
+
+    (records, errors)
+}
+
+/// Reads every unique message author out of a DiscordChatExporter JSON export
+///
+/// # Errors
+///
+/// This function will return an error if the file isn't a DiscordChatExporter JSON export.
+pub fn parse_dce_export(content: &[u8]) -> Result<Vec<ImportRecord>, Error> {
+    let export: DceExport = serde_json::from_slice(content)?;
+
+    let mut seen = HashSet::new();
+    let mut records = Vec::new();
+
+    for author in export.messages.into_iter().map(|message| message.author) {
+        if !seen.insert(author.id.clone()) {
+            continue;
+        }
+
+        let Some(discord_id) = parse_discord_id(&author.id) else {
+            continue;
+        };
+
+        // Users on the new username system have a discriminator of 0000
+        let username = match author.discriminator.as_deref() {
+            None | Some("0" | "0000") => author.name,
+            Some(discriminator) => format!("{}#{discriminator}", author.name),
+        };
+
+        records.push(ImportRecord {
+            discord_id,
+            username: Some(username),
+            reason: None,
+            category: None,
+            vrc_id: None,
+            avatar_url: author.avatar_url,
+            is_bot: author.is_bot,
+        });
+    }
+
+    Ok(records)
+}
+
+/// Reads a DiscordChatExporter export that's either a single JSON file or a `.zip` of one JSON file per channel
+///
+/// Files in the zip that can't be read are skipped and explained in the returned list of errors.
+///
+/// # Errors
+///
+/// This function will return an error if a single JSON file can't be read or the zip is broken.
+pub fn parse_dce_import(content: &[u8]) -> Result<(Vec<ImportRecord>, Vec<String>), Error> {
+    if !content.starts_with(b"PK\x03\x04") {
+        return Ok((parse_dce_export(content)?, Vec::new()));
+    }
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(content))?;
+
+    if archive.len() > IMPORT_ZIP_MAX_ENTRIES {
+        return Err(format!(
+            "That zip has {} files, only {IMPORT_ZIP_MAX_ENTRIES} can be imported at once",
+            archive.len()
+        )
+        .into());
+    }
+
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+    let mut total_bytes = 0;
+
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+
+        if !file.name().to_lowercase().ends_with(".json") {
+            continue;
+        }
+
+        let name = file.name().to_string();
+
+        // The size in the zip can be faked so the read is capped too
+        if file.size() > IMPORT_ZIP_MAX_FILE_BYTES {
+            errors.push(format!(
+                "{name}: Bigger than {}MB once unzipped",
+                IMPORT_ZIP_MAX_FILE_BYTES / 1024 / 1024
+            ));
+            continue;
+        }
+
+        let mut json = Vec::new();
+        file.take(IMPORT_ZIP_MAX_FILE_BYTES + 1)
+            .read_to_end(&mut json)?;
+
+        if json.len() as u64 > IMPORT_ZIP_MAX_FILE_BYTES {
+            errors.push(format!(
+                "{name}: Bigger than {}MB once unzipped",
+                IMPORT_ZIP_MAX_FILE_BYTES / 1024 / 1024
+            ));
+            continue;
+        }
+
+        total_bytes += json.len() as u64;
+
+        if total_bytes > IMPORT_ZIP_MAX_TOTAL_BYTES {
+            return Err(format!(
+                "That zip is bigger than {}MB once unzipped, split it into smaller ones",
+                IMPORT_ZIP_MAX_TOTAL_BYTES / 1024 / 1024
+            )
+            .into());
+        }
+
+        match parse_dce_export(&json) {
+            Ok(mut channel) => records.append(&mut channel),
+            Err(error) => errors.push(format!("{name}: {error}")),
+        }
+    }
+
+    if records.is_empty() && errors.is_empty() {
+        return Err("There are no DiscordChatExporter JSON files in that zip".into());
+    }
+
+    Ok((records, errors))
+}
+
+/// Builds the offence an imported user gets, the file's reason and category win over the ones given to the command
+#[must_use]
+pub fn import_offence(
+    record: &ImportRecord,
+    guild_id: &str,
+    reason: &str,
+    category: Option<OffenceCategory>,
+) -> Offense {
+    Offense {
+        guild_id: guild_id.to_string(),
+        reason: record.reason.clone().unwrap_or_else(|| reason.to_string()),
+        image: None,
+        extra: record
+            .avatar_url
+            .as_ref()
+            .map(|avatar| format!("Avatar when found: {avatar}")),
+        category: record.category.or(category),
+        provenance: None,
+        evidence: Vec::new(),
+        expires_at: None,
+        added_at: None,
+    }
+}
+
+/// Sorts imported users into who would be added, who would get another offence and who gets skipped
+///
+/// Users are only imported once even if they're in the file more than once. Users who already have this exact offence are put in `already_listed` so importing the same file twice doesn't double them up.
+/// Users already in the DB without a VRChat ID also go in `vrc_ids_filled` if the import has one for them.
+#[must_use]
+pub fn plan_import(
+    records: Vec<ImportRecord>,
+    existing: &HashMap<String, UserInfo>,
+    whitelist: &HashSet<u64>,
+    guild_id: &str,
+    reason: &str,
+) -> ImportPlan {
+    let mut plan = ImportPlan::default();
+    let mut seen = HashSet::new();
+
+    for record in records {
+        if !seen.insert(record.discord_id.clone()) {
+            continue;
+        }
+
+        let id = record.discord_id.parse::<u64>().unwrap_or_default();
+
+        if record.is_bot || BOT_IDS.contains(&id) {
+            plan.skipped_bots.push(record);
+        } else if whitelist.contains(&id) {
+            plan.skipped_whitelisted.push(record);
+        } else if let Some(user) = existing.get(&record.discord_id) {
+            let offence = import_offence(&record, guild_id, reason, None);
+
+            if record.vrc_id.is_some() && !has_vrc_id(user.vrc_id.as_deref()) {
+                plan.vrc_ids_filled.push(record.clone());
+            }
+
+            if user
+                .offences
+                .iter()
+                .any(|o| o.guild_id == offence.guild_id && o.reason == offence.reason)
+            {
+                plan.already_listed.push(record);
+            } else {
+                plan.existing.push(record);
+            }
+        } else {
+            plan.new.push(record);
+        }
+    }
+
+    plan
+}
+
+/// Reads the DB entries of everyone in an import that's already in the DB
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con, records))]
+pub async fn get_existing_users(
+    con: &mut redis::aio::MultiplexedConnection,
+    records: &[ImportRecord],
+) -> Result<HashMap<String, UserInfo>, Error> {
+    if records.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut key_pipe = redis::pipe();
+
+    for record in records {
+        key_pipe
+            .cmd("JSON.GET")
+            .arg(format!("user:{}", record.discord_id));
+    }
+
+    let entries: Vec<Option<String>> = key_pipe.atomic().query_async(con).await?;
+
+    let mut existing = HashMap::new();
+    for (record, entry) in records.iter().zip(entries) {
+        if let Some(json) = entry {
+            let user: UserInfo = serde_json::from_str(&json)?;
+            existing.insert(record.discord_id.clone(), user);
+        }
+    }
+
+    Ok(existing)
+}
+
+/// Writes an import plan to the DB, new users get an entry and existing users get the offence added on
+///
+/// Every offence gets now as its `added_at` like [`crate::utils::add_offence`] does.
+/// Returns how many users were written. An `OffenceAdded` webhook event is fired for each of them, `added_by` goes in it.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con, plan))]
+pub async fn commit_import(
+    con: &mut redis::aio::MultiplexedConnection,
+    plan: &ImportPlan,
+    guild_id: &str,
+    reason: &str,
+    category: Option<OffenceCategory>,
+    added_by: &str,
+) -> Result<usize, Error> {
+    let added_at = chrono::Utc::now().timestamp();
+    let offence = |record: &ImportRecord| Offense {
+        added_at: Some(added_at),
+        ..import_offence(record, guild_id, reason, category)
+    };
+
+    let mut pipe = redis::pipe();
+
+    for record in &plan.new {
+        if let Some(vrc_id) = &record.vrc_id {
+            index_vrc_id(&mut pipe, vrc_id, &record.discord_id);
+        }
+
+        let user = UserInfo {
+            vrc_id: record.vrc_id.clone(),
+            username: record.username.clone(),
+            discord_id: Some(record.discord_id.clone()),
+            offences: vec![offence(record)],
+        };
+
+        pipe.cmd("JSON.SET")
+            .arg(format!("user:{}", record.discord_id))
+            .arg("$".to_string())
+            .arg(serde_json::to_string(&user)?)
+            .ignore();
+    }
+
+    for record in &plan.existing {
+        pipe.cmd("JSON.ARRAPPEND")
+            .arg(format!("user:{}", record.discord_id))
+            .arg("$.offences".to_string())
+            .arg(serde_json::to_string(&offence(record))?)
+            .ignore();
+    }
+
+    for record in &plan.vrc_ids_filled {
+        if let Some(vrc_id) = &record.vrc_id {
+            index_vrc_id(&mut pipe, vrc_id, &record.discord_id);
+
+            pipe.cmd("JSON.SET")
+                .arg(format!("user:{}", record.discord_id))
+                .arg("$.vrc_id")
+                .arg(serde_json::to_string(vrc_id)?)
+                .ignore();
+        }
+    }
+
+    // Upload all entries at once
+    pipe.atomic().query_async::<_, ()>(con).await?;
+
+    let changed_ids: Vec<String> = plan
+        .new
+        .iter()
+        .chain(plan.existing.iter())
+        .chain(plan.vrc_ids_filled.iter())
+        .map(|record| record.discord_id.clone())
+        .collect::<HashSet<String>>()
+        .into_iter()
+        .collect();
+
+    queue_search_sync(con, &changed_ids).await?;
+
+    #[cfg(feature = "federation")]
+    crate::federation::queue_federation_sync(con, &changed_ids).await?;
+
+    let webhook_events = plan
+        .new
+        .iter()
+        .chain(plan.existing.iter())
+        .map(|record| {
+            (
+                guild_id.parse::<u64>().ok(),
+                serde_json::json!({
+                    "discord_id": record.discord_id,
+                    "username": record.username,
+                    "vrc_id": record.vrc_id,
+                    "offence": offence(record),
+                    "added_by": added_by,
+                }),
+            )
+        })
+        .collect();
+
+    // Everything is already saved so a webhook problem shouldn't make it look like it wasn't
+    if let Err(error) = fire_webhook_events(con, WebhookEvent::OffenceAdded, webhook_events).await {
+        event!(Level::WARN, "Failed to fire import webhooks" = %error);
+    }
+
+    Ok(changed_ids.len())
+}
+
+#[cfg(test)]
+mod import_tests {
+    use super::*;
+
+    #[test]
+    fn parse_csv_import_test() {
+        let csv = "AuthorID,Author,Date,Content\n\
+                   000000000000000001,Ripper#0001,2022,hi\n\
+                   000000000000000002,\"Leaker, the\",2022,hello\n\
+                   not an id,Someone,2022,hey\n";
+
+        let (records, errors) = parse_csv_import(csv, &ColumnMapping::default()).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].username.as_deref(), Some("Leaker, the"));
+        assert_eq!(errors.len(), 1);
+
+        let mapping = ColumnMapping {
+            id: "id".to_string(),
+            name: "name".to_string(),
+            reason: Some("why".to_string()),
+            category: Some("type".to_string()),
+            vrc_id: None,
+        };
+        let csv = "ID,Name,Why,Type\n000000000000000003,Someone,Ripped a model,ripping\n";
+        let (records, errors) = parse_csv_import(csv, &mapping).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(records[0].reason.as_deref(), Some("Ripped a model"));
+        assert_eq!(records[0].category, Some(OffenceCategory::Ripping));
+
+        assert!(parse_csv_import("UserID\n1", &ColumnMapping::default()).is_err());
+    }
+
+    #[test]
+    fn parse_json_import_test() {
+        let json = r#"["000000000000000001", 100000000000000002, {"AuthorID": "000000000000000003", "Author": "Someone"}, "nope"]"#;
+
+        let (records, errors) = parse_json_import(json, &ColumnMapping::default()).unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].discord_id, "100000000000000002");
+        assert_eq!(records[2].username.as_deref(), Some("Someone"));
+        assert_eq!(errors.len(), 1);
+        assert!(parse_json_import("{}", &ColumnMapping::default()).is_err());
+    }
+
+    #[test]
+    fn parse_id_list_import_test() {
+        let (records, errors) = parse_id_list_import(
+            "# rippers\n000000000000000001\n000000000000000002 Leaker\n\n12\n",
+        );
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].username.as_deref(), Some("Leaker"));
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn plan_import_test() {
+        let record = |id: &str| ImportRecord {
+            discord_id: id.to_string(),
+            username: None,
+            reason: None,
+            category: None,
+            vrc_id: None,
+            avatar_url: None,
+            is_bot: false,
+        };
+
+        let mut existing = HashMap::new();
+        existing.insert(
+            "000000000000000002".to_string(),
+            UserInfo {
+                vrc_id: None,
+                username: None,
+                discord_id: Some("000000000000000002".to_string()),
+                offences: vec![],
+            },
+        );
+        existing.insert(
+            "000000000000000003".to_string(),
+            UserInfo {
+                vrc_id: Some("N/A".to_string()),
+                username: None,
+                discord_id: Some("000000000000000003".to_string()),
+                offences: vec![import_offence(&record("0"), "1", "Leaks", None)],
+            },
+        );
+
+        let with_vrc_id = ImportRecord {
+            vrc_id: Some("usr_c1644b5b-3ca4-45b4-97c6-a2a0de70d469".to_string()),
+            ..record("000000000000000003")
+        };
+
+        let plan = plan_import(
+            vec![
+                record("000000000000000001"),
+                record("000000000000000001"),
+                record("000000000000000002"),
+                with_vrc_id.clone(),
+                record(&BOT_IDS[0].to_string()),
+                record("000000000000000004"),
+            ],
+            &existing,
+            &HashSet::from([4]),
+            "1",
+            "Leaks",
+        );
+
+        assert_eq!(plan.new, vec![record("000000000000000001")]);
+        assert_eq!(plan.existing, vec![record("000000000000000002")]);
+        assert_eq!(plan.already_listed, vec![with_vrc_id.clone()]);
+        assert_eq!(plan.vrc_ids_filled, vec![with_vrc_id]);
+        assert_eq!(plan.skipped_bots.len(), 1);
+        assert_eq!(plan.skipped_whitelisted, vec![record("000000000000000004")]);
+    }
+
+    const DCE_EXPORT: &str = r#"{
+        "guild": {"id": "1", "name": "Leaks"},
+        "messages": [
+            {"id": "1", "author": {"id": "000000000000000001", "name": "Ripper", "discriminator": "0001", "isBot": false, "avatarUrl": "https://cdn.discordapp.com/avatars/1.png"}},
+            {"id": "2", "author": {"id": "000000000000000001", "name": "Ripper", "discriminator": "0001", "isBot": false}},
+            {"id": "3", "author": {"id": "000000000000000002", "name": "leaker", "discriminator": "0000", "isBot": false}},
+            {"id": "4", "author": {"id": "000000000000000003", "name": "Some Bot", "discriminator": "1234", "isBot": true}}
+        ]
+    }"#;
+
+    #[test]
+    fn parse_dce_export_test() {
+        let records = parse_dce_export(DCE_EXPORT.as_bytes()).unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].username.as_deref(), Some("Ripper#0001"));
+        assert_eq!(
+            records[0].avatar_url.as_deref(),
+            Some("https://cdn.discordapp.com/avatars/1.png")
+        );
+        assert_eq!(records[1].username.as_deref(), Some("leaker"));
+        assert!(records[2].is_bot);
+        assert_eq!(
+            detect_import_format("export.json", DCE_EXPORT.as_bytes()),
+            ImportFormat::DiscordChatExporter
+        );
+        assert_eq!(
+            detect_import_format("ids.json", b"[\"000000000000000001\"]"),
+            ImportFormat::Json
+        );
+    }
+
+    #[test]
+    fn parse_dce_zip_test() {
+        use std::io::Write;
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default();
+        zip.start_file("general.json", options).unwrap();
+        zip.write_all(DCE_EXPORT.as_bytes()).unwrap();
+        zip.start_file("general_Files/image.png", options).unwrap();
+        zip.write_all(b"not a json file").unwrap();
+        zip.start_file("broken.json", options).unwrap();
+        zip.write_all(b"{").unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        assert_eq!(
+            detect_import_format("export.zip", &bytes),
+            ImportFormat::DiscordChatExporter
+        );
+
+        let (records, errors) = parse_dce_import(&bytes).unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn parse_dce_zip_limits_test() {
+        use std::io::Write;
+
+        let options = zip::write::FileOptions::default();
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("general.json", options).unwrap();
+        zip.write_all(DCE_EXPORT.as_bytes()).unwrap();
+        zip.start_file("huge.json", options).unwrap();
+        zip.write_all(&vec![b' '; IMPORT_ZIP_MAX_FILE_BYTES as usize + 1])
+            .unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let (records, errors) = parse_dce_import(&bytes).unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("huge.json"));
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..=IMPORT_ZIP_MAX_ENTRIES {
+            zip.start_file(format!("{i}.txt"), options).unwrap();
+        }
+        let bytes = zip.finish().unwrap().into_inner();
+
+        assert!(parse_dce_import(&bytes).is_err());
+    }
+}
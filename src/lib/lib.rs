@@ -1,9 +1,45 @@
+#[cfg(feature = "database")]
+pub mod announcements;
+#[cfg(feature = "api")]
+pub mod api;
 pub mod args;
+#[cfg(feature = "database")]
+pub mod auth;
+#[cfg(feature = "database")]
+pub mod backup;
+#[cfg(feature = "database")]
+pub mod botban;
 pub mod checks;
 pub mod enums;
 pub mod event_handlers;
+#[cfg(feature = "database")]
+pub mod evidence;
+#[cfg(feature = "database")]
+pub mod expiry;
+#[cfg(feature = "federation")]
+pub mod federation;
+#[cfg(feature = "database")]
+pub mod feedback;
+#[cfg(feature = "database")]
+pub mod identity;
+#[cfg(feature = "database")]
+pub mod import;
 pub mod memes;
+#[cfg(feature = "database")]
+pub mod onboarding;
+#[cfg(feature = "database")]
+pub mod risk;
+#[cfg(feature = "database")]
+pub mod search;
+#[cfg(feature = "database")]
+pub mod settings;
+#[cfg(feature = "database")]
+pub mod staff;
 pub mod structs;
 pub mod types;
 pub mod utils;
 pub mod vars;
+#[cfg(feature = "database")]
+pub mod vrchat;
+#[cfg(feature = "database")]
+pub mod webhooks;
@@ -0,0 +1,349 @@
+use crate::auth::{grant_access, user_grant};
+use crate::enums::{AuthLevel, OnboardingStatus, StaffTier};
+use crate::staff::has_staff_tier;
+use crate::structs::OnboardingRequest;
+use crate::types::Error;
+use crate::utils::setup_guild_settings;
+use crate::vars::{ONBOARDING_CHANNEL_ID, ONBOARDING_REVIEW_CLAIM_SECS};
+use chrono::Utc;
+use poise::serenity_prelude::{
+    self as serenity, colours, ButtonStyle, ChannelId, Colour, CreateComponents, CreateEmbed,
+    InteractionResponseType, MessageComponentInteraction,
+};
+use tracing::{event, instrument, Level};
+
+/// Every approve and deny button's custom ID starts with this so the event handler knows it's ours
+pub const ONBOARDING_BUTTON_PREFIX: &str = "onboarding:";
+
+/// Fills out the embed a request gets posted to the onboarding channel as
+pub fn onboarding_embed<'a>(
+    e: &'a mut CreateEmbed,
+    request: &OnboardingRequest,
+) -> &'a mut CreateEmbed {
+    let colour = match request.status {
+        OnboardingStatus::Pending => Colour::GOLD,
+        OnboardingStatus::Approved => colours::css::POSITIVE,
+        OnboardingStatus::Denied => colours::css::DANGER,
+    };
+
+    e.title(format!("{} wants to be set up", request.guild_name))
+        .color(colour)
+        .field("Server ID:", &request.guild_id, true)
+        .field("Owner:", format!("<@{}>", request.owner_id), true)
+        .field("Members:", request.member_count, true)
+        .field(
+            "Server made:",
+            format!("<t:{}:R>", request.guild_created_at),
+            true,
+        )
+        .field(
+            "Requested by:",
+            format!("<@{}>", request.requested_by),
+            true,
+        )
+        .field(
+            "Requested:",
+            format!("<t:{}:R>", request.requested_at),
+            true,
+        )
+        .field("Channel:", format!("<#{}>", request.channel_id), true)
+        .field("Alt protection:", request.alt_protection, true)
+        .field(
+            "Status:",
+            request.reviewed_by.as_ref().map_or_else(
+                || request.status.to_string(),
+                |by| format!("{} by <@{by}>", request.status),
+            ),
+            true,
+        )
+}
+
+/// Adds the approve and deny buttons, they're disabled once the request has been looked at
+pub fn onboarding_buttons<'a>(
+    c: &'a mut CreateComponents,
+    request: &OnboardingRequest,
+) -> &'a mut CreateComponents {
+    c.create_action_row(|r| {
+        for (status, label, style) in [
+            (OnboardingStatus::Approved, "Approve", ButtonStyle::Success),
+            (OnboardingStatus::Denied, "Deny", ButtonStyle::Danger),
+        ] {
+            r.create_button(|b| {
+                b.custom_id(format!(
+                    "{ONBOARDING_BUTTON_PREFIX}{}:{status}",
+                    request.guild_id
+                ))
+                .label(label)
+                .style(style)
+                .disabled(request.status != OnboardingStatus::Pending)
+            });
+        }
+        r
+    })
+}
+
+/// Pulls the guild ID and decision out of an approve or deny button's custom ID
+#[must_use]
+pub fn parse_onboarding_button(custom_id: &str) -> Option<(u64, OnboardingStatus)> {
+    let (guild_id, status) = custom_id
+        .strip_prefix(ONBOARDING_BUTTON_PREFIX)?
+        .split_once(':')?;
+
+    let status = match status {
+        "Approved" => OnboardingStatus::Approved,
+        "Denied" => OnboardingStatus::Denied,
+        _ => return None,
+    };
+
+    Some((guild_id.parse().ok()?, status))
+}
+
+/// Reads a server's setup request from the DB
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or the request is invalid.
+#[instrument(skip(con))]
+pub async fn get_onboarding_request(
+    con: &mut redis::aio::MultiplexedConnection,
+    guild_id: u64,
+) -> Result<Option<OnboardingRequest>, Error> {
+    let json_in: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!("onboarding-request:{guild_id}"))
+        .query_async(con)
+        .await?;
+
+    match json_in {
+        None => Ok(None),
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+    }
+}
+
+/// Writes a server's setup request to the DB, replacing any older one
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con, request))]
+pub async fn save_onboarding_request(
+    con: &mut redis::aio::MultiplexedConnection,
+    request: &OnboardingRequest,
+) -> Result<(), Error> {
+    redis::cmd("JSON.SET")
+        .arg(format!("onboarding-request:{}", request.guild_id))
+        .arg("$")
+        .arg(serde_json::to_string(request)?)
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Posts a request to the onboarding channel for staff to look at
+///
+/// # Errors
+///
+/// This function will return an error if the message can't be sent.
+#[instrument(skip(http, request))]
+pub async fn post_onboarding_request(
+    http: &serenity::Http,
+    request: &OnboardingRequest,
+) -> Result<(), Error> {
+    ChannelId(ONBOARDING_CHANNEL_ID)
+        .send_message(http, |m| {
+            m.embed(|e| onboarding_embed(e, request))
+                .components(|c| onboarding_buttons(c, request))
+                .allowed_mentions(|a| a.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Claims a request for review so two reviewers pressing at once can't both act on it
+///
+/// Returns `false` if someone else has it claimed. Call [`release_onboarding_review`] once it's been saved.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn claim_onboarding_review(
+    con: &mut redis::aio::MultiplexedConnection,
+    guild_id: u64,
+) -> Result<bool, Error> {
+    let claimed: bool = redis::cmd("SET")
+        .arg(format!("onboarding-reviewing:{guild_id}"))
+        .arg(Utc::now().timestamp())
+        .arg("NX")
+        .arg("EX")
+        .arg(ONBOARDING_REVIEW_CLAIM_SECS)
+        .query_async(con)
+        .await?;
+
+    Ok(claimed)
+}
+
+/// Lets go of a request claimed with [`claim_onboarding_review`]
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn release_onboarding_review(
+    con: &mut redis::aio::MultiplexedConnection,
+    guild_id: u64,
+) -> Result<(), Error> {
+    redis::cmd("DEL")
+        .arg(format!("onboarding-reviewing:{guild_id}"))
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Handles staff pressing approve or deny on a request
+///
+/// Approving sets the server up with what was asked for and makes the person who asked a manager there.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or discord rejects the response.
+#[instrument(skip(ctx, press))]
+pub async fn handle_onboarding_button(
+    ctx: &serenity::Context,
+    press: &MessageComponentInteraction,
+) -> Result<(), Error> {
+    let Some((guild_id, status)) = parse_onboarding_button(&press.data.custom_id) else {
+        return Ok(());
+    };
+
+    let mut con = crate::utils::open_redis_connection().await?;
+
+    let is_reviewer =
+        has_staff_tier(&mut con, *press.user.id.as_u64(), StaffTier::Reviewer).await?;
+
+    let request = if !is_reviewer {
+        Err("Only reviewers and up can approve or deny servers!".to_string())
+    } else if !claim_onboarding_review(&mut con, guild_id).await? {
+        Err("Someone else is reviewing this request right now.".to_string())
+    } else {
+        let request = match get_onboarding_request(&mut con, guild_id).await? {
+            None => Err(format!(
+                "The request for `{guild_id}` isn't in the DB anymore."
+            )),
+            Some(request) if request.status != OnboardingStatus::Pending => Err(format!(
+                "This request has already been {}.",
+                request.status.to_string().to_lowercase()
+            )),
+            Some(request) => Ok(request),
+        };
+
+        if request.is_err() {
+            release_onboarding_review(&mut con, guild_id).await?;
+        }
+
+        request
+    };
+
+    let mut request = match request {
+        Ok(request) => request,
+        Err(problem) => {
+            press
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|d| d.content(problem).ephemeral(true))
+                })
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if status == OnboardingStatus::Approved {
+        setup_guild_settings(
+            &mut con,
+            guild_id,
+            request.channel_id.clone(),
+            request.guild_name.clone(),
+            request.alt_protection,
+        )
+        .await?;
+
+        if let Ok(requester) = request.requested_by.parse::<u64>() {
+            grant_access(
+                &mut con,
+                guild_id,
+                &user_grant(requester),
+                AuthLevel::Manager,
+            )
+            .await?;
+        }
+    }
+
+    request.status = status;
+    request.reviewed_by = Some(press.user.id.to_string());
+    save_onboarding_request(&mut con, &request).await?;
+    release_onboarding_review(&mut con, guild_id).await?;
+
+    event!(
+        Level::INFO,
+        "Onboarding request reviewed" = guild_id,
+        "Status" = %status,
+        "By" = press.user.id.as_u64()
+    );
+
+    let notice = match status {
+        OnboardingStatus::Approved => format!(
+            "<@{}> your server has been approved! Kick messages and bot announcements will go to this channel and alt protection is {}. You can now use `/authorize` to let others use the bot here.",
+            request.requested_by,
+            if request.alt_protection { "on" } else { "off" }
+        ),
+        _ => format!(
+            "<@{}> sorry, your request to set up this server was denied. Reach out through the FBT Discord if you have any questions.",
+            request.requested_by
+        ),
+    };
+
+    if let Ok(channel_id) = request.channel_id.parse::<u64>() {
+        // The server might have kicked the bot or locked the channel since asking, staff still get the result
+        if let Err(error) = ChannelId(channel_id).say(&ctx.http, notice).await {
+            event!(
+                Level::WARN,
+                "Failed to tell server about onboarding" = guild_id,
+                error = %error
+            );
+        }
+    }
+
+    press
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| {
+                    d.embed(|e| onboarding_embed(e, &request))
+                        .components(|c| onboarding_buttons(c, &request))
+                })
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod onboarding_tests {
+    use super::*;
+
+    #[test]
+    fn parse_onboarding_button_test() {
+        assert_eq!(
+            parse_onboarding_button("onboarding:123:Approved"),
+            Some((123, OnboardingStatus::Approved))
+        );
+        assert_eq!(
+            parse_onboarding_button("onboarding:123:Denied"),
+            Some((123, OnboardingStatus::Denied))
+        );
+        assert_eq!(parse_onboarding_button("onboarding:123:Pending"), None);
+        assert_eq!(parse_onboarding_button("onboarding:abc:Approved"), None);
+        assert_eq!(parse_onboarding_button("feedback-status:12:Done"), None);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::enums::{OffenceCategory, RiskLevel};
+use crate::expiry::is_active;
+use crate::structs::{RiskScore, UserInfo};
+use crate::types::Error;
+use std::collections::HashSet;
+use tracing::instrument;
+
+/// Scores from here up are [`RiskLevel::Medium`]
+pub const MEDIUM_RISK_SCORE: u32 = 10;
+/// Scores from here up are [`RiskLevel::High`]
+pub const HIGH_RISK_SCORE: u32 = 25;
+
+/// How much one offence of a category counts before recency and the other factors
+#[must_use]
+pub const fn category_weight(category: Option<OffenceCategory>) -> f64 {
+    match category {
+        Some(OffenceCategory::Ripping | OffenceCategory::Leaking) => 10.0,
+        Some(OffenceCategory::Scam) => 8.0,
+        Some(OffenceCategory::Raid) => 6.0,
+        Some(OffenceCategory::Harassment) => 5.0,
+        Some(OffenceCategory::Other) | None => 3.0,
+    }
+}
+
+/// How much an offence still counts for its age, halving every year down to a quarter
+///
+/// Offences added before `added_at` was tracked count for half.
+#[must_use]
+pub fn recency_factor(added_at: Option<i64>, now: i64) -> f64 {
+    added_at.map_or(0.5, |added_at| {
+        #[allow(clippy::cast_precision_loss)]
+        let age_days = (now - added_at).max(0) as f64 / 86_400.0;
+
+        0.5_f64.powf(age_days / 365.0).max(0.25)
+    })
+}
+
+/// Extra weight for being reported by more than one guild, each extra guild adds half up to triple
+#[must_use]
+pub fn guild_factor(distinct_guilds: usize) -> f64 {
+    #[allow(clippy::cast_precision_loss)]
+    let extra = distinct_guilds.saturating_sub(1) as f64;
+
+    extra.mul_add(0.5, 1.0).min(3.0)
+}
+
+/// Which band a score falls in
+#[must_use]
+pub const fn risk_level(score: u32) -> RiskLevel {
+    if score >= HIGH_RISK_SCORE {
+        RiskLevel::High
+    } else if score >= MEDIUM_RISK_SCORE {
+        RiskLevel::Medium
+    } else {
+        RiskLevel::Low
+    }
+}
+
+/// Works out a user's risk score from the offences that still count at `now`
+///
+/// Cleared users keep a fifth of their score so they still show up if they're reported again.
+#[must_use]
+pub fn calculate_risk(user: &UserInfo, cleared: bool, now: i64) -> RiskScore {
+    let active = user
+        .offences
+        .iter()
+        .filter(|offense| is_active(offense, now));
+
+    let mut guilds = HashSet::new();
+    let mut total = 0.0;
+
+    for offense in active {
+        guilds.insert(offense.guild_id.as_str());
+        total += category_weight(offense.category) * recency_factor(offense.added_at, now);
+    }
+
+    total *= guild_factor(guilds.len());
+
+    if cleared {
+        total *= 0.2;
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let score = total.round() as u32;
+
+    RiskScore {
+        score,
+        level: risk_level(score),
+    }
+}
+
+/// Works out a user's risk score, checking the DB for whether they've been cleared
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con, user))]
+pub async fn get_risk_score(
+    con: &mut redis::aio::MultiplexedConnection,
+    discord_id: &str,
+    user: &UserInfo,
+) -> Result<RiskScore, Error> {
+    let cleared: bool = redis::cmd("EXISTS")
+        .arg(format!("cleared-user:{discord_id}"))
+        .query_async(con)
+        .await?;
+
+    Ok(calculate_risk(
+        user,
+        cleared,
+        chrono::Utc::now().timestamp(),
+    ))
+}
+
+#[cfg(test)]
+mod risk_tests {
+    use super::*;
+    use crate::structs::Offense;
+
+    const YEAR: i64 = 365 * 86_400;
+
+    fn offence(
+        guild_id: &str,
+        category: Option<OffenceCategory>,
+        added_at: Option<i64>,
+    ) -> Offense {
+        Offense {
+            guild_id: guild_id.to_string(),
+            category,
+            added_at,
+            ..Default::default()
+        }
+    }
+
+    fn user(offences: Vec<Offense>) -> UserInfo {
+        UserInfo {
+            vrc_id: None,
+            username: None,
+            discord_id: Some("1".to_string()),
+            offences,
+        }
+    }
+
+    #[test]
+    fn calculate_risk_test() {
+        let now = 10 * YEAR;
+
+        let toxic = user(vec![offence("1", None, Some(now))]);
+        assert_eq!(calculate_risk(&toxic, false, now).score, 3);
+        assert_eq!(calculate_risk(&toxic, false, now).level, RiskLevel::Low);
+
+        let leaker = user(
+            (0..10)
+                .map(|i| {
+                    offence(
+                        &(i % 3).to_string(),
+                        Some(OffenceCategory::Leaking),
+                        Some(now),
+                    )
+                })
+                .collect(),
+        );
+        assert_eq!(calculate_risk(&leaker, false, now).score, 200);
+        assert_eq!(calculate_risk(&leaker, false, now).level, RiskLevel::High);
+        assert_eq!(calculate_risk(&leaker, true, now).score, 40);
+
+        let old = user(vec![offence(
+            "1",
+            Some(OffenceCategory::Ripping),
+            Some(now - YEAR),
+        )]);
+        assert_eq!(calculate_risk(&old, false, now).score, 5);
+
+        let ancient = user(vec![offence("1", Some(OffenceCategory::Ripping), Some(0))]);
+        assert_eq!(calculate_risk(&ancient, false, now).score, 3);
+
+        let mut expired = offence("1", Some(OffenceCategory::Scam), None);
+        expired.expires_at = Some(now - 1);
+        assert_eq!(calculate_risk(&user(vec![expired]), false, now).score, 0);
+    }
+
+    #[test]
+    fn guild_factor_test() {
+        assert!((guild_factor(0) - 1.0).abs() < f64::EPSILON);
+        assert!((guild_factor(1) - 1.0).abs() < f64::EPSILON);
+        assert!((guild_factor(3) - 2.0).abs() < f64::EPSILON);
+        assert!((guild_factor(20) - 3.0).abs() < f64::EPSILON);
+    }
+}
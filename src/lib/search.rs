@@ -0,0 +1,609 @@
+use crate::structs::{Offense, RiskScore, SearchDrift, SearchSyncJob, UserInfo};
+use crate::types::Error;
+use crate::utils::{open_redis_connection, truncate_text};
+use crate::vars::{
+    MEILISEARCH_API_KEY, MEILISEARCH_HOST, MEILISEARCH_INDEX, SEARCH_MATCH_LIMIT,
+    SEARCH_OFFENCES_PER_PAGE, SEARCH_SYNC_INTERVAL_SECS, SEARCH_SYNC_MAX_ATTEMPTS,
+};
+use meilisearch_sdk::client::Client;
+use meilisearch_sdk::documents::DocumentsQuery;
+use meilisearch_sdk::indexes::Index;
+use meilisearch_sdk::task_info::TaskInfo;
+use poise::serenity_prelude::{Colour, CreateEmbed, User};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::time::Duration;
+use tracing::{event, instrument, Level};
+
+/// Most fields Discord allows in one embed
+const EMBED_MAX_FIELDS: usize = 25;
+
+/// Most characters Discord allows across an embed's title, description, fields and footer
+const EMBED_MAX_CHARS: usize = 6000;
+
+/// Fields and characters kept for the user's details, title and footer on every page of search results
+///
+/// The details are cut short so they can never go over this.
+const SEARCH_HEADER_FIELDS: usize = 6;
+const SEARCH_HEADER_CHARS: usize = 1000;
+
+/// Connects to the index every DB entry gets pushed to
+///
+/// # Errors
+///
+/// This function will return an error if the client can't be built, no request is sent so it won't fail if Meilisearch is down.
+pub fn entries_index() -> Result<Index, Error> {
+    let client = Client::new(MEILISEARCH_HOST, Some(MEILISEARCH_API_KEY))?;
+
+    Ok(client.index(MEILISEARCH_INDEX))
+}
+
+/// Searches one field of the DB entries through Meilisearch, typos are handled by Meilisearch's typo tolerance
+///
+/// Returns at most [`SEARCH_MATCH_LIMIT`] users, best match first.
+///
+/// # Errors
+///
+/// This function will return an error if Meilisearch can't be reached or the index doesn't exist yet.
+#[instrument]
+pub async fn search_entries(query: &str, attribute: &str) -> Result<Vec<UserInfo>, Error> {
+    let index = entries_index()?;
+    let attributes = [attribute];
+
+    let results = index
+        .search()
+        .with_query(query)
+        .with_attributes_to_search_on(&attributes)
+        .with_limit(SEARCH_MATCH_LIMIT)
+        .execute::<UserInfo>()
+        .await?;
+
+    Ok(results.hits.into_iter().map(|hit| hit.result).collect())
+}
+
+/// Waits for Meilisearch to finish a task and turns a failed task into an error
+///
+/// # Errors
+///
+/// This function will return an error if the task failed or didn't finish before `timeout`.
+pub async fn wait_for_search_task(
+    index: &Index,
+    task: TaskInfo,
+    timeout: Option<Duration>,
+) -> Result<(), Error> {
+    let task = index.wait_for_task(task, None, timeout).await?;
+
+    if task.is_failure() {
+        return Err(Box::new(task.unwrap_failure()));
+    }
+
+    Ok(())
+}
+
+/// Queues users to have their search engine entry updated, or removed if they aren't in the DB anymore
+///
+/// The queue gets worked through by [`run_search_sync_worker`].
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn queue_search_sync(
+    con: &mut redis::aio::MultiplexedConnection,
+    discord_ids: &[String],
+) -> Result<(), Error> {
+    if discord_ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut jobs = Vec::new();
+    for discord_id in discord_ids {
+        jobs.push(serde_json::to_string(&SearchSyncJob {
+            discord_id: discord_id.clone(),
+            attempts: 0,
+        })?);
+    }
+
+    redis::cmd("RPUSH")
+        .arg("search-sync-queue")
+        .arg(jobs)
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Pushes the DB's current version of every queued user to the search engine
+async fn sync_jobs(
+    con: &mut redis::aio::MultiplexedConnection,
+    index: &Index,
+    jobs: &[SearchSyncJob],
+) -> Result<(), Error> {
+    let discord_ids: Vec<String> = jobs
+        .iter()
+        .map(|job| job.discord_id.clone())
+        .collect::<HashSet<String>>()
+        .into_iter()
+        .collect();
+
+    let mut key_pipe = redis::pipe();
+
+    for discord_id in &discord_ids {
+        key_pipe.cmd("JSON.GET").arg(format!("user:{discord_id}"));
+    }
+
+    let entries: Vec<Option<String>> = key_pipe.atomic().query_async(con).await?;
+
+    let mut upserts: Vec<UserInfo> = Vec::new();
+    let mut deletes: Vec<String> = Vec::new();
+
+    for (discord_id, entry) in discord_ids.into_iter().zip(entries) {
+        match entry.map(|json| serde_json::from_str(&json)) {
+            Some(Ok(user)) => upserts.push(user),
+            // Retrying won't fix a broken entry so don't hold up the rest of the batch for it
+            Some(Err(error)) => {
+                event!(Level::WARN, "Skipped invalid user for search sync" = discord_id, error = ?error);
+            }
+            None => deletes.push(discord_id),
+        }
+    }
+
+    if !upserts.is_empty() {
+        let task = index.add_or_replace(&upserts, Some("discord_id")).await?;
+        wait_for_search_task(index, task, None).await?;
+    }
+
+    if !deletes.is_empty() {
+        let task = index.delete_documents(&deletes).await?;
+        wait_for_search_task(index, task, None).await?;
+    }
+
+    event!(
+        Level::DEBUG,
+        "Search engine synced" = upserts.len(),
+        "Removed" = deletes.len()
+    );
+
+    Ok(())
+}
+
+/// Takes the next batch off the sync queue, putting it back if the search engine couldn't be updated
+async fn sync_queued_updates() -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+
+    let queued: Option<Vec<String>> = redis::cmd("LPOP")
+        .arg("search-sync-queue")
+        .arg(100)
+        .query_async(&mut con)
+        .await?;
+
+    let Some(queued) = queued else {
+        return Ok(());
+    };
+
+    let mut jobs: Vec<SearchSyncJob> = Vec::new();
+    for job in queued {
+        match serde_json::from_str(&job) {
+            Ok(job) => jobs.push(job),
+            Err(error) => {
+                event!(Level::WARN, "Dropped invalid search sync job" = job, error = ?error);
+            }
+        }
+    }
+
+    let index = entries_index()?;
+
+    if let Err(error) = sync_jobs(&mut con, &index, &jobs).await {
+        let mut pipe = redis::pipe();
+
+        for mut job in jobs {
+            job.attempts += 1;
+
+            let list = if job.attempts >= SEARCH_SYNC_MAX_ATTEMPTS {
+                "search-sync-failed"
+            } else {
+                "search-sync-queue"
+            };
+
+            pipe.cmd("RPUSH")
+                .arg(list)
+                .arg(serde_json::to_string(&job)?)
+                .ignore();
+        }
+
+        pipe.atomic().query_async::<_, ()>(&mut con).await?;
+
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+/// Works through the search sync queue forever, backing off while the search engine keeps failing
+///
+/// Spawn this once when the bot starts.
+pub async fn run_search_sync_worker() {
+    let mut failures: u32 = 0;
+
+    loop {
+        let delay = SEARCH_SYNC_INTERVAL_SECS * 2_u64.pow(failures.min(6));
+        tokio::time::sleep(Duration::from_secs(delay)).await;
+
+        match sync_queued_updates().await {
+            Ok(()) => failures = 0,
+            Err(error) => {
+                failures += 1;
+                event!(
+                    Level::WARN,
+                    "Failed to sync search engine" = ?error,
+                    "Failures in a row" = failures
+                );
+            }
+        }
+    }
+}
+
+/// Compares the DB against the search engine
+///
+/// `missing` users are in the DB but not the index, `stale` users are in the index but not the DB and `outdated` users are in both but don't match.
+#[must_use]
+pub fn diff_search_index(store: &[UserInfo], indexed: &[UserInfo]) -> SearchDrift {
+    let indexed: HashMap<Option<&String>, &UserInfo> = indexed
+        .iter()
+        .map(|user| (user.discord_id.as_ref(), user))
+        .collect();
+    let stored_ids: HashSet<Option<&String>> =
+        store.iter().map(|user| user.discord_id.as_ref()).collect();
+
+    let mut drift = SearchDrift::default();
+
+    for user in store {
+        let id = user.discord_id.clone().unwrap_or_default();
+
+        match indexed.get(&user.discord_id.as_ref()) {
+            None => drift.missing.push(id),
+            Some(indexed_user) if *indexed_user != user => drift.outdated.push(id),
+            Some(_) => {}
+        }
+    }
+
+    for (id, _) in indexed {
+        if !stored_ids.contains(&id) {
+            drift.stale.push(id.cloned().unwrap_or_default());
+        }
+    }
+
+    drift.missing.sort();
+    drift.stale.sort();
+    drift.outdated.sort();
+
+    drift
+}
+
+/// Reads every user from the DB and the search engine and reports where they don't match
+///
+/// # Errors
+///
+/// This function will return an error if the DB or the search engine can't be reached.
+#[instrument(skip(con))]
+pub async fn find_search_drift(
+    con: &mut redis::aio::MultiplexedConnection,
+) -> Result<SearchDrift, Error> {
+    let key_list: Vec<String> = redis::cmd("KEYS")
+        .arg("user:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let mut store = Vec::new();
+
+    if !key_list.is_empty() {
+        let mut key_pipe = redis::pipe();
+
+        for key in key_list {
+            key_pipe.cmd("JSON.GET").arg(key);
+        }
+
+        let entries: Vec<String> = key_pipe.atomic().query_async(con).await?;
+
+        for entry in entries {
+            let user: UserInfo = serde_json::from_str(&entry)?;
+            store.push(user);
+        }
+    }
+
+    let index = entries_index()?;
+    let mut indexed: Vec<UserInfo> = Vec::new();
+
+    loop {
+        let page = DocumentsQuery::new(&index)
+            .with_offset(indexed.len())
+            .with_limit(1000)
+            .execute::<UserInfo>()
+            .await?;
+
+        let done =
+            page.results.is_empty() || indexed.len() + page.results.len() >= page.total as usize;
+        indexed.extend(page.results);
+
+        if done {
+            break;
+        }
+    }
+
+    Ok(diff_search_index(&store, &indexed))
+}
+
+/// The fields one offence takes up on a page of search results, `number` is where it is in the user's offences starting at 1
+fn offence_fields(number: usize, offense: &Offense, now: i64) -> Vec<(&'static str, String, bool)> {
+    let expiry = match offense.expires_at {
+        Some(expires_at) if expires_at <= now => format!(" (expired <t:{expires_at}:R>)"),
+        Some(expires_at) => format!(" (expires <t:{expires_at}:R>)"),
+        None => String::new(),
+    };
+
+    let mut fields = vec![
+        ("Offense:", format!("#{number}{expiry}"), false),
+        ("Guild ID:", truncate_text(&offense.guild_id, 100), true),
+        ("Reason:", truncate_text(&offense.reason, 1000), true),
+        (
+            "Image(s):",
+            truncate_text(offense.image.as_deref().unwrap_or("N/A"), 300),
+            true,
+        ),
+        (
+            "Extra info:",
+            truncate_text(offense.extra.as_deref().unwrap_or("N/A"), 300),
+            true,
+        ),
+    ];
+
+    if !offense.evidence.is_empty() {
+        let links = offense
+            .evidence
+            .iter()
+            .map(|evidence| format!("[{}]({})", evidence.file_name, evidence.url))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        fields.push(("Evidence:", truncate_text(&links, 1024), true));
+    }
+
+    if let Some(provenance) = &offense.provenance {
+        fields.push((
+            "From peer:",
+            format!(
+                "`{}` <t:{}:R>",
+                truncate_text(&provenance.peer, 100),
+                provenance.received_at
+            ),
+            true,
+        ));
+    }
+
+    fields
+}
+
+/// Splits a user's offences into pages of search results
+///
+/// Each page gets up to [`SEARCH_OFFENCES_PER_PAGE`] offences, fewer when they wouldn't fit in one embed. There's always at least one page.
+#[must_use]
+pub fn search_pages(user: &UserInfo) -> Vec<Range<usize>> {
+    let now = chrono::Utc::now().timestamp();
+    let mut pages = Vec::new();
+    let mut start = 0;
+    let mut fields = 0;
+    let mut chars = 0;
+
+    for (i, offense) in user.offences.iter().enumerate() {
+        let offence_fields = offence_fields(i + 1, offense, now);
+        let offence_chars: usize = offence_fields
+            .iter()
+            .map(|(name, value, _)| name.chars().count() + value.chars().count())
+            .sum();
+
+        let full = i - start >= SEARCH_OFFENCES_PER_PAGE
+            || SEARCH_HEADER_FIELDS + fields + offence_fields.len() > EMBED_MAX_FIELDS
+            || SEARCH_HEADER_CHARS + chars + offence_chars > EMBED_MAX_CHARS;
+
+        if full && i > start {
+            pages.push(start..i);
+            start = i;
+            fields = 0;
+            chars = 0;
+        }
+
+        fields += offence_fields.len();
+        chars += offence_chars;
+    }
+
+    if start < user.offences.len() || pages.is_empty() {
+        pages.push(start..user.offences.len());
+    }
+
+    pages
+}
+
+/// Fills out one page of search results, the user's details go on every page and their offences are split across them by [`search_pages`]
+///
+/// Long fields get cut short and offences move to the next page so a page always fits in one embed, the export button has the full text.
+#[allow(clippy::too_many_arguments)]
+pub fn search_page_embed<'a>(
+    e: &'a mut CreateEmbed,
+    user: &UserInfo,
+    current: &User,
+    identity: Option<&str>,
+    risk: RiskScore,
+    colour: u32,
+    page: usize,
+) -> &'a mut CreateEmbed {
+    let pages = search_pages(user);
+    let offences = pages.get(page).cloned().unwrap_or_default();
+    let now = chrono::Utc::now().timestamp();
+
+    e.title("Result found!")
+        .description(format!("User has {} hit(s).", user.offences.len()))
+        .field(
+            "Username logged in DB:",
+            truncate_text(&user.username.clone().unwrap_or_else(|| current.tag()), 100),
+            true,
+        )
+        .field(
+            "Current username:",
+            truncate_text(&current.tag(), 100),
+            true,
+        )
+        .field(
+            "User ID:",
+            truncate_text(
+                &user
+                    .discord_id
+                    .clone()
+                    .unwrap_or_else(|| current.id.to_string()),
+                100,
+            ),
+            true,
+        )
+        .field(
+            "VRChat ID:",
+            truncate_text(user.vrc_id.as_deref().unwrap_or("N/A"), 100),
+            true,
+        )
+        .field(
+            "Identity:",
+            truncate_text(identity.unwrap_or("Not linked"), 256),
+            true,
+        )
+        .field(
+            "Risk score:",
+            format!("{} ({})", risk.score, risk.level),
+            true,
+        )
+        .color(Colour::new(colour))
+        .thumbnail(current.avatar_url().unwrap_or_else(|| {
+            "https://discord.com/assets/1f0bfc0865d324c2587920a7d80c609b.png".to_string()
+        }))
+        .footer(|f| {
+            f.text(format!(
+                "Page {}/{} - Use Export for the full offences",
+                page + 1,
+                pages.len()
+            ))
+        });
+
+    for i in offences {
+        for (name, value, inline) in offence_fields(i + 1, &user.offences[i], now) {
+            e.field(name, value, inline);
+        }
+    }
+
+    e
+}
+
+#[cfg(test)]
+mod search_tests {
+    use super::*;
+    use crate::structs::{Evidence, Provenance};
+
+    fn user(id: &str, reason: &str) -> UserInfo {
+        UserInfo {
+            vrc_id: Some("N/A".to_string()),
+            username: Some(format!("user{id}")),
+            discord_id: Some(id.to_string()),
+            offences: vec![Offense {
+                reason: reason.to_string(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn diff_search_index_test() {
+        let store = vec![user("1", "a"), user("2", "b"), user("3", "c")];
+        let indexed = vec![user("1", "a"), user("2", "changed"), user("4", "d")];
+
+        let drift = diff_search_index(&store, &indexed);
+
+        assert_eq!(drift.missing, vec!["3".to_string()]);
+        assert_eq!(drift.outdated, vec!["2".to_string()]);
+        assert_eq!(drift.stale, vec!["4".to_string()]);
+        assert_eq!(diff_search_index(&store, &store), SearchDrift::default());
+    }
+
+    #[test]
+    fn search_page_embed_test() {
+        let long = "a".repeat(6000);
+        let offence = Offense {
+            guild_id: long.clone(),
+            reason: long.clone(),
+            image: Some(long.clone()),
+            extra: Some(long.clone()),
+            provenance: Some(Provenance {
+                peer: long.clone(),
+                received_at: 1_700_000_000,
+            }),
+            evidence: vec![
+                Evidence {
+                    sha256: long.clone(),
+                    file_name: long.clone(),
+                    size: 1,
+                    url: long.clone(),
+                    added_by: "1".to_string(),
+                    added_at: 1_700_000_000,
+                };
+                3
+            ],
+            expires_at: Some(1_700_000_000),
+            ..Default::default()
+        };
+        let mut worst = user("1", &long);
+        worst.username = Some(long.clone());
+        worst.vrc_id = Some(long.clone());
+        worst.offences = vec![offence; 10];
+
+        let pages = search_pages(&worst);
+        assert_eq!(pages.iter().map(ExactSizeIterator::len).sum::<usize>(), 10);
+
+        for page in 0..pages.len() {
+            let mut e = CreateEmbed::default();
+            search_page_embed(
+                &mut e,
+                &worst,
+                &User::default(),
+                Some(&long),
+                RiskScore {
+                    score: 200,
+                    level: crate::enums::RiskLevel::High,
+                },
+                0,
+                page,
+            );
+
+            let text = |value: Option<&serde_json::Value>| {
+                value
+                    .and_then(serde_json::Value::as_str)
+                    .map_or(0, |text| text.chars().count())
+            };
+            let fields = e.0["fields"].as_array().unwrap();
+            let chars = text(e.0.get("title"))
+                + text(e.0.get("description"))
+                + text(e.0["footer"].get("text"))
+                + fields
+                    .iter()
+                    .map(|field| text(field.get("name")) + text(field.get("value")))
+                    .sum::<usize>();
+
+            assert!(fields.len() <= EMBED_MAX_FIELDS);
+            assert!(chars <= EMBED_MAX_CHARS);
+        }
+
+        assert_eq!(search_pages(&user("1", "a")), vec![0..1]);
+        assert_eq!(
+            search_pages(&UserInfo {
+                offences: Vec::new(),
+                ..user("1", "a")
+            }),
+            vec![0..0]
+        );
+    }
+}
@@ -0,0 +1,470 @@
+use crate::risk::{HIGH_RISK_SCORE, MEDIUM_RISK_SCORE};
+use crate::structs::{GuildSettings, SettingsMigrationReport, GUILD_SETTINGS_VERSION};
+use crate::types::Error;
+use poise::serenity_prelude::{colours, ButtonStyle, CreateComponents, CreateEmbed};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use tracing::instrument;
+
+/// Risk score policies the dashboard offers, anything else set with `/risk_policy` shows up as its own option
+pub const SCORE_POLICIES: [(u32, &str); 3] = [
+    (0, "Everyone in the DB"),
+    (MEDIUM_RISK_SCORE, "Medium risk and up"),
+    (HIGH_RISK_SCORE, "High risk only"),
+];
+
+fn describe_score(score: u32) -> String {
+    SCORE_POLICIES
+        .iter()
+        .find(|(policy, _)| *policy == score)
+        .map_or_else(
+            || format!("Risk score of {score} and up"),
+            |(_, label)| (*label).to_string(),
+        )
+}
+
+fn describe_channel(channel_id: Option<&str>) -> String {
+    channel_id.map_or_else(|| "None".to_string(), |id| format!("<#{id}>"))
+}
+
+/// Fills out the embed `/settings` shows a server's settings in
+pub fn settings_embed<'a>(e: &'a mut CreateEmbed, settings: &GuildSettings) -> &'a mut CreateEmbed {
+    e.title(format!("Settings for {}", settings.server_name))
+        .description("Use the menus and buttons below to change them.")
+        .color(colours::css::POSITIVE)
+        .field(
+            "Alert channel:",
+            describe_channel(Some(&settings.channel_id)),
+            true,
+        )
+        .field(
+            "Case log channel:",
+            describe_channel(settings.log_channel_id.as_deref()),
+            true,
+        )
+        .field("Alt protection:", settings.kick, true)
+        .field("Announcements:", !settings.announcements_opt_out, true)
+        .field(
+            "Warn about:",
+            describe_score(settings.alert_min_score),
+            true,
+        )
+        .field(
+            "Kick:",
+            settings
+                .kick_min_score
+                .map_or_else(|| "Never".to_string(), describe_score),
+            true,
+        )
+}
+
+/// Adds the menus and buttons `/settings` uses, `channels` are the server's text channels as `(ID, name)`
+///
+/// Select menus can only hold 25 options so only the first channels are offered.
+pub fn settings_components<'a>(
+    c: &'a mut CreateComponents,
+    ctx_id: u64,
+    settings: &GuildSettings,
+    channels: &[(u64, String)],
+) -> &'a mut CreateComponents {
+    let score_options = |current: Option<u32>| {
+        let mut options: Vec<(String, String)> = SCORE_POLICIES
+            .iter()
+            .map(|(score, label)| (score.to_string(), (*label).to_string()))
+            .collect();
+
+        if let Some(score) = current.filter(|score| !SCORE_POLICIES.iter().any(|p| p.0 == *score)) {
+            options.push((score.to_string(), describe_score(score)));
+        }
+
+        options
+    };
+
+    // Discord won't show a select menu with no options, so the channel menus are left off if there are no text channels
+    if !channels.is_empty() {
+        c.create_action_row(|r| {
+            r.create_select_menu(|m| {
+                m.custom_id(format!("{ctx_id}-settings-channel"))
+                    .placeholder("Alert channel")
+                    .options(|o| {
+                        for (id, name) in channels.iter().take(25) {
+                            o.create_option(|opt| {
+                                opt.label(format!("#{name}"))
+                                    .value(id)
+                                    .default_selection(id.to_string() == settings.channel_id)
+                            });
+                        }
+                        o
+                    })
+            })
+        })
+        .create_action_row(|r| {
+            r.create_select_menu(|m| {
+                m.custom_id(format!("{ctx_id}-settings-log-channel"))
+                    .placeholder("Case log channel")
+                    .options(|o| {
+                        o.create_option(|opt| {
+                            opt.label("Don't post cases")
+                                .value("none")
+                                .default_selection(settings.log_channel_id.is_none())
+                        });
+                        for (id, name) in channels.iter().take(24) {
+                            o.create_option(|opt| {
+                                opt.label(format!("#{name}")).value(id).default_selection(
+                                    settings.log_channel_id.as_deref() == Some(&id.to_string()),
+                                )
+                            });
+                        }
+                        o
+                    })
+            })
+        });
+    }
+
+    c.create_action_row(|r| {
+        r.create_select_menu(|m| {
+            m.custom_id(format!("{ctx_id}-settings-alert-policy"))
+                .placeholder("Who to warn about")
+                .options(|o| {
+                    for (value, label) in score_options(Some(settings.alert_min_score)) {
+                        o.create_option(|opt| {
+                            opt.label(format!("Warn about: {label}"))
+                                .default_selection(value == settings.alert_min_score.to_string())
+                                .value(value)
+                        });
+                    }
+                    o
+                })
+        })
+    })
+    .create_action_row(|r| {
+        r.create_select_menu(|m| {
+            m.custom_id(format!("{ctx_id}-settings-kick-policy"))
+                .placeholder("Who to kick")
+                .options(|o| {
+                    o.create_option(|opt| {
+                        opt.label("Don't kick by risk score")
+                            .value("none")
+                            .default_selection(settings.kick_min_score.is_none())
+                    });
+                    for (value, label) in score_options(settings.kick_min_score) {
+                        o.create_option(|opt| {
+                            opt.label(format!("Kick: {label}"))
+                                .default_selection(
+                                    settings.kick_min_score.map(|score| score.to_string())
+                                        == Some(value.clone()),
+                                )
+                                .value(value)
+                        });
+                    }
+                    o
+                })
+        })
+    })
+    .create_action_row(|r| {
+        r.create_button(|b| {
+            b.custom_id(format!("{ctx_id}-settings-kick"))
+                .label(if settings.kick {
+                    "Turn alt protection off"
+                } else {
+                    "Turn alt protection on"
+                })
+                .style(if settings.kick {
+                    ButtonStyle::Danger
+                } else {
+                    ButtonStyle::Success
+                })
+        })
+        .create_button(|b| {
+            b.custom_id(format!("{ctx_id}-settings-announcements"))
+                .label(if settings.announcements_opt_out {
+                    "Turn announcements on"
+                } else {
+                    "Turn announcements off"
+                })
+                .style(ButtonStyle::Secondary)
+        })
+    })
+}
+
+/// Applies a press on one of the `/settings` components, `setting` is the part of the custom ID after `-settings-`
+///
+/// Returns `false` if nothing changed.
+#[must_use]
+pub fn apply_setting(settings: &mut GuildSettings, setting: &str, value: Option<&str>) -> bool {
+    let before = settings.clone();
+
+    match (setting, value) {
+        ("channel", Some(channel_id)) if channel_id.parse::<u64>().is_ok() => {
+            settings.channel_id = channel_id.to_string();
+        }
+        ("log-channel", Some("none")) => settings.log_channel_id = None,
+        ("log-channel", Some(channel_id)) if channel_id.parse::<u64>().is_ok() => {
+            settings.log_channel_id = Some(channel_id.to_string());
+        }
+        ("alert-policy", Some(score)) => match score.parse() {
+            Ok(score) => settings.alert_min_score = score,
+            Err(_) => return false,
+        },
+        ("kick-policy", Some("none")) => settings.kick_min_score = None,
+        ("kick-policy", Some(score)) => match score.parse() {
+            Ok(score) => settings.kick_min_score = Some(score),
+            Err(_) => return false,
+        },
+        ("kick", None) => settings.kick = !settings.kick,
+        ("announcements", None) => {
+            settings.announcements_opt_out = !settings.announcements_opt_out;
+        }
+        _ => return false,
+    }
+
+    *settings != before
+}
+
+/// Version 0 is everything from before versions existed, some of it was edited by hand or written by older bots
+///
+/// Channel IDs saved as numbers become strings, an empty log channel becomes `null` and `kick` saved as text becomes a bool.
+fn migrate_v0(doc: &mut Map<String, Value>) {
+    for field in ["channel_id", "log_channel_id"] {
+        if let Some(Value::Number(id)) = doc.get(field) {
+            let id = id.to_string();
+            doc.insert(field.to_string(), Value::String(id));
+        }
+    }
+
+    if doc.get("log_channel_id").and_then(Value::as_str) == Some("") {
+        doc.insert("log_channel_id".to_string(), Value::Null);
+    }
+
+    if let Some(Value::String(kick)) = doc.get("kick") {
+        let kick = kick.eq_ignore_ascii_case("true");
+        doc.insert("kick".to_string(), Value::Bool(kick));
+    }
+}
+
+/// Upgrades for stored settings, the one at index `n` takes a document from version `n` to `n + 1`
+pub const MIGRATIONS: [fn(&mut Map<String, Value>); GUILD_SETTINGS_VERSION as usize] = [migrate_v0];
+
+/// Runs every migration a stored settings document needs, returns `false` if it was already up to date
+///
+/// # Errors
+///
+/// This function will return an error if the document isn't an object or is from a newer version than this bot knows.
+pub fn migrate_settings_document(doc: &mut Value) -> Result<bool, Error> {
+    let Some(fields) = doc.as_object_mut() else {
+        return Err("settings aren't a JSON object".into());
+    };
+
+    let version = match fields.get("version") {
+        None => 0,
+        Some(version) => version.as_u64().ok_or("`version` isn't a number")?,
+    };
+
+    if version > u64::from(GUILD_SETTINGS_VERSION) {
+        return Err(format!(
+            "settings are version {version} but this bot only knows up to {GUILD_SETTINGS_VERSION}"
+        )
+        .into());
+    }
+
+    for migrate in &MIGRATIONS[usize::try_from(version)?..] {
+        migrate(fields);
+    }
+
+    fields.insert("version".to_string(), GUILD_SETTINGS_VERSION.into());
+
+    Ok(version < u64::from(GUILD_SETTINGS_VERSION))
+}
+
+/// Reads a stored settings document, migrating it first, returns `true` alongside it if it needed migrating
+///
+/// # Errors
+///
+/// This function will return an error if the document can't be migrated or still isn't valid after.
+pub fn parse_guild_settings(json: &str) -> Result<(GuildSettings, bool), Error> {
+    let mut doc: Value = serde_json::from_str(json)?;
+    let migrated = migrate_settings_document(&mut doc)?;
+
+    Ok((serde_json::from_value(doc)?, migrated))
+}
+
+/// Finds anything in a server's settings pointing at something that's gone
+///
+/// `channels` are the IDs of every channel in the server, `None` if the bot isn't in it anymore.
+#[must_use]
+pub fn settings_problems(settings: &GuildSettings, channels: Option<&HashSet<u64>>) -> Vec<String> {
+    let Some(channels) = channels else {
+        return vec!["The bot isn't in this server anymore".to_string()];
+    };
+
+    let exists = |id: &str| id.parse::<u64>().is_ok_and(|id| channels.contains(&id));
+
+    let mut problems = Vec::new();
+
+    if settings.channel_id.is_empty() {
+        problems.push("No alert channel is set".to_string());
+    } else if !exists(&settings.channel_id) {
+        problems.push(format!(
+            "Alert channel `{}` doesn't exist anymore",
+            settings.channel_id
+        ));
+    }
+
+    if let Some(log_channel_id) = settings.log_channel_id.as_deref() {
+        if !exists(log_channel_id) {
+            problems.push(format!(
+                "Case log channel `{log_channel_id}` doesn't exist anymore"
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Migrates every `guild-settings:` entry in the DB to the current version
+///
+/// Entries that still can't be read are left alone and listed in the report so they can be fixed by hand.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn migrate_guild_settings(
+    con: &mut redis::aio::MultiplexedConnection,
+    dry_run: bool,
+) -> Result<SettingsMigrationReport, Error> {
+    let mut report = SettingsMigrationReport::default();
+
+    let key_list: Vec<String> = redis::cmd("KEYS")
+        .arg("guild-settings:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    if key_list.is_empty() {
+        return Ok(report);
+    }
+
+    let mut key_pipe = redis::pipe();
+
+    for key in &key_list {
+        key_pipe.cmd("JSON.GET").arg(key);
+    }
+
+    let entries: Vec<String> = key_pipe.atomic().query_async(con).await?;
+
+    let mut pipe = redis::pipe();
+
+    for (key, entry) in key_list.iter().zip(entries) {
+        let guild_id = key.trim_start_matches("guild-settings:").to_string();
+        report.checked += 1;
+
+        match parse_guild_settings(&entry) {
+            Ok((settings, migrated)) => {
+                if migrated {
+                    pipe.cmd("JSON.SET")
+                        .arg(key)
+                        .arg("$")
+                        .arg(serde_json::to_string(&settings)?)
+                        .ignore();
+
+                    report.migrated.push(guild_id.clone());
+                }
+
+                report.settings.push((guild_id, settings));
+            }
+            Err(error) => report.invalid.push((guild_id, error.to_string())),
+        }
+    }
+
+    if !dry_run && !report.migrated.is_empty() {
+        pipe.atomic().query_async::<_, ()>(con).await?;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod settings_tests {
+    use super::*;
+
+    #[test]
+    fn apply_setting_test() {
+        let mut settings = GuildSettings {
+            version: GUILD_SETTINGS_VERSION,
+            channel_id: "1".to_string(),
+            kick: false,
+            server_name: "Test".to_string(),
+            log_channel_id: None,
+            announcements_opt_out: false,
+            alert_min_score: 0,
+            kick_min_score: None,
+        };
+
+        assert!(apply_setting(&mut settings, "channel", Some("2")));
+        assert_eq!(settings.channel_id, "2");
+        assert!(!apply_setting(&mut settings, "channel", Some("2")));
+        assert!(!apply_setting(&mut settings, "channel", Some("general")));
+
+        assert!(apply_setting(&mut settings, "log-channel", Some("3")));
+        assert_eq!(settings.log_channel_id.as_deref(), Some("3"));
+        assert!(apply_setting(&mut settings, "log-channel", Some("none")));
+        assert_eq!(settings.log_channel_id, None);
+
+        assert!(apply_setting(&mut settings, "alert-policy", Some("25")));
+        assert_eq!(settings.alert_min_score, 25);
+        assert!(apply_setting(&mut settings, "kick-policy", Some("10")));
+        assert_eq!(settings.kick_min_score, Some(10));
+        assert!(apply_setting(&mut settings, "kick-policy", Some("none")));
+        assert_eq!(settings.kick_min_score, None);
+
+        assert!(apply_setting(&mut settings, "kick", None));
+        assert!(settings.kick);
+        assert!(apply_setting(&mut settings, "announcements", None));
+        assert!(settings.announcements_opt_out);
+        assert!(!apply_setting(&mut settings, "something-else", None));
+    }
+
+    #[test]
+    fn migrate_settings_test() {
+        let (settings, migrated) = parse_guild_settings(
+            r#"{"channel_id": 123, "kick": "true", "server_name": "Old", "log_channel_id": ""}"#,
+        )
+        .unwrap();
+        assert!(migrated);
+        assert_eq!(settings.version, GUILD_SETTINGS_VERSION);
+        assert_eq!(settings.channel_id, "123");
+        assert!(settings.kick);
+        assert_eq!(settings.log_channel_id, None);
+        assert_eq!(settings.alert_min_score, 0);
+
+        let current = serde_json::to_string(&settings).unwrap();
+        assert_eq!(parse_guild_settings(&current).unwrap(), (settings, false));
+
+        assert!(parse_guild_settings(r#"{"version": 99}"#).is_err());
+        assert!(parse_guild_settings(r#"{"kick": true, "server_name": "No channel"}"#).is_err());
+        assert!(parse_guild_settings("[]").is_err());
+    }
+
+    #[test]
+    fn settings_problems_test() {
+        let settings = GuildSettings {
+            version: GUILD_SETTINGS_VERSION,
+            channel_id: "1".to_string(),
+            kick: false,
+            server_name: "Test".to_string(),
+            log_channel_id: Some("2".to_string()),
+            announcements_opt_out: false,
+            alert_min_score: 0,
+            kick_min_score: None,
+        };
+
+        assert!(settings_problems(&settings, Some(&HashSet::from([1, 2]))).is_empty());
+        assert_eq!(
+            settings_problems(&settings, Some(&HashSet::from([1]))).len(),
+            1
+        );
+        assert_eq!(settings_problems(&settings, Some(&HashSet::new())).len(), 2);
+        assert_eq!(settings_problems(&settings, None).len(), 1);
+    }
+}
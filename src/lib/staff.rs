@@ -0,0 +1,192 @@
+use crate::enums::StaffTier;
+use crate::types::Error;
+use crate::vars::{BOT_OWNERS, LEGACY_BOT_ADMINS};
+use std::collections::HashMap;
+use tracing::{event, instrument, Level};
+
+/// Someone's tier once [`BOT_OWNERS`] is taken into account, they're always owners whatever is stored
+#[must_use]
+pub fn effective_tier(user_id: u64, stored: Option<StaffTier>) -> Option<StaffTier> {
+    if BOT_OWNERS.contains(&user_id) {
+        Some(StaffTier::Owner)
+    } else {
+        stored
+    }
+}
+
+/// Reads someone's staff tier, `None` if they aren't staff
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn get_staff_tier(
+    con: &mut redis::aio::MultiplexedConnection,
+    user_id: u64,
+) -> Result<Option<StaffTier>, Error> {
+    let stored: Option<String> = redis::cmd("HGET")
+        .arg("staff-tiers")
+        .arg(user_id)
+        .query_async(con)
+        .await?;
+
+    Ok(effective_tier(
+        user_id,
+        stored.and_then(|tier| tier.parse().ok()),
+    ))
+}
+
+/// Checks if someone is staff with at least `tier`
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+pub async fn has_staff_tier(
+    con: &mut redis::aio::MultiplexedConnection,
+    user_id: u64,
+    tier: StaffTier,
+) -> Result<bool, Error> {
+    Ok(get_staff_tier(con, user_id)
+        .await?
+        .is_some_and(|user_tier| user_tier >= tier))
+}
+
+/// Reads everyone on the bot's staff, highest tier first
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn get_all_staff(
+    con: &mut redis::aio::MultiplexedConnection,
+) -> Result<Vec<(u64, StaffTier)>, Error> {
+    let stored: HashMap<u64, String> = redis::cmd("HGETALL")
+        .arg("staff-tiers")
+        .query_async(con)
+        .await?;
+
+    let mut staff: HashMap<u64, StaffTier> = HashMap::new();
+
+    for (user_id, tier) in stored {
+        match tier.parse() {
+            Ok(tier) => {
+                staff.insert(user_id, tier);
+            }
+            Err(error) => event!(Level::WARN, "Invalid staff tier" = user_id, error = ?error),
+        }
+    }
+
+    for owner in BOT_OWNERS {
+        staff.insert(owner, StaffTier::Owner);
+    }
+
+    let mut staff: Vec<(u64, StaffTier)> = staff.into_iter().collect();
+    staff.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    Ok(staff)
+}
+
+/// Gives someone a staff tier, replacing the one they had
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn set_staff_tier(
+    con: &mut redis::aio::MultiplexedConnection,
+    user_id: u64,
+    tier: StaffTier,
+) -> Result<(), Error> {
+    redis::cmd("HSET")
+        .arg("staff-tiers")
+        .arg(user_id)
+        .arg(tier.to_string())
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Takes someone off the bot's staff, returns `false` if they weren't on it
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn remove_staff(
+    con: &mut redis::aio::MultiplexedConnection,
+    user_id: u64,
+) -> Result<bool, Error> {
+    let removed: u64 = redis::cmd("HDEL")
+        .arg("staff-tiers")
+        .arg(user_id)
+        .query_async(con)
+        .await?;
+
+    Ok(removed > 0)
+}
+
+/// Gives [`LEGACY_BOT_ADMINS`] the `Admin` tier, only ever runs once so revoking them later sticks
+///
+/// Returns `false` if it had already run.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn seed_legacy_admins(
+    con: &mut redis::aio::MultiplexedConnection,
+) -> Result<bool, Error> {
+    let first_run: bool = redis::cmd("SET")
+        .arg("staff-tiers-seeded")
+        .arg(chrono::Utc::now().timestamp())
+        .arg("NX")
+        .query_async(con)
+        .await?;
+
+    if !first_run {
+        return Ok(false);
+    }
+
+    let mut pipe = redis::pipe();
+    for user_id in LEGACY_BOT_ADMINS {
+        // Anyone already granted a tier by hand keeps it
+        pipe.cmd("HSETNX")
+            .arg("staff-tiers")
+            .arg(user_id)
+            .arg(StaffTier::Admin.to_string())
+            .ignore();
+    }
+    pipe.query_async::<_, ()>(con).await?;
+
+    event!(
+        Level::INFO,
+        "Seeded legacy bot admins" = LEGACY_BOT_ADMINS.len()
+    );
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod staff_tests {
+    use super::*;
+
+    #[test]
+    fn effective_tier_test() {
+        assert_eq!(effective_tier(BOT_OWNERS[0], None), Some(StaffTier::Owner));
+        assert_eq!(
+            effective_tier(BOT_OWNERS[0], Some(StaffTier::Support)),
+            Some(StaffTier::Owner)
+        );
+        assert_eq!(
+            effective_tier(1, Some(StaffTier::Reviewer)),
+            Some(StaffTier::Reviewer)
+        );
+        assert_eq!(effective_tier(1, None), None);
+        assert!(StaffTier::Admin > StaffTier::Reviewer);
+        assert_eq!(
+            "Reviewer".parse::<StaffTier>().ok(),
+            Some(StaffTier::Reviewer)
+        );
+    }
+}
@@ -1,6 +1,11 @@
+use crate::enums::{
+    AnnouncementStatus, AuthLevel, BotBanKind, FeedbackStatus, ModAction, OffenceCategory,
+    OnboardingStatus, PeerTrust, RiskLevel, StaffTier, WebhookEvent,
+};
 use merge::Merge;
 use serde::{Deserialize, Serialize};
 use serde_with::{As, FromInto};
+use std::collections::HashMap;
 
 // User data, which is stored and accessible in all command invocations
 #[derive(Debug)]
@@ -25,14 +30,36 @@ pub struct UserInfo {
     pub offences: Vec<Offense>,
 }
 
+/// Version of [`GuildSettings`] this bot writes, bump it and add a migration to `settings::MIGRATIONS` when the layout changes
+pub const GUILD_SETTINGS_VERSION: u32 = 1;
+
+/// A server's settings, stored under `guild-settings:<DiscordGuildID>`
+///
+/// Every field but `channel_id` has a default so older documents still load, read them with `settings::parse_guild_settings` to get them migrated first.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
 pub struct GuildSettings {
+    /// Layout version the document was written with, documents from before versions existed are 0
+    #[serde(default)]
+    pub version: u32,
+    /// Where warnings go, settings without one can't be used so there's no default
     pub channel_id: String,
+    #[serde(default)]
     pub kick: bool,
+    #[serde(default)]
     pub server_name: String,
+    #[serde(default)]
+    pub log_channel_id: Option<String>,
+    #[serde(default)]
+    pub announcements_opt_out: bool,
+    /// Lowest risk score that gets a join warning, 0 warns about everyone in the DB
+    #[serde(default)]
+    pub alert_min_score: u32,
+    /// Risk score at which blacklisted users are kicked as they join, `None` never kicks
+    #[serde(default)]
+    pub kick_min_score: Option<u32>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Merge, Hash, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Merge, Hash, Eq, Default)]
 pub struct Offense {
     #[merge(skip)]
     pub guild_id: String,
@@ -42,6 +69,483 @@ pub struct Offense {
     pub image: Option<String>,
     #[serde(with = "As::<FromInto<OptionalString2>>")]
     pub extra: Option<String>,
+    #[merge(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<OffenceCategory>,
+    #[merge(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+    #[merge(skip)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub evidence: Vec<Evidence>,
+    /// Unix time the offence stops counting, `None` for offences that never expire
+    #[merge(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    /// Unix time the offence was added, `None` for offences added before this was tracked
+    #[merge(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added_at: Option<i64>,
+}
+
+/// How risky a user is, worked out from their active offences
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Hash, Eq)]
+pub struct RiskScore {
+    pub score: u32,
+    pub level: RiskLevel,
+}
+
+/// Someone or a whole server blocked from using the bot, stored under `bot-ban:user:<DiscordUserID>` or `bot-ban:guild:<DiscordGuildID>`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct BotBan {
+    pub kind: BotBanKind,
+    pub id: String,
+    pub reason: String,
+    pub banned_by: String,
+    pub banned_at: i64,
+    /// When the ban is lifted, `None` if it's permanent
+    pub expires_at: Option<i64>,
+}
+
+/// A server asking to be set up, stored under `onboarding-request:<DiscordGuildID>`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct OnboardingRequest {
+    pub guild_id: String,
+    pub guild_name: String,
+    pub owner_id: String,
+    pub member_count: u64,
+    /// When the server was made
+    pub guild_created_at: i64,
+    pub requested_by: String,
+    /// Where kick messages and announcements go once it's approved
+    pub channel_id: String,
+    pub alt_protection: bool,
+    pub requested_at: i64,
+    pub status: OnboardingStatus,
+    pub reviewed_by: Option<String>,
+}
+
+/// An expired offence moved out of a user's entry, kept under `offence-archive:<DiscordUserID>`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct ArchivedOffence {
+    pub offence: Offense,
+    pub archived_at: i64,
+}
+
+/// A file attached to an offence, copied into the evidence store so the link doesn't expire like Discord's do
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct Evidence {
+    /// Hex SHA-256 of the file, files are stored under this so the same file is only kept once
+    pub sha256: String,
+    /// Name of the file when it was uploaded
+    pub file_name: String,
+    pub size: u64,
+    /// Where the file can be viewed from the evidence store
+    pub url: String,
+    pub added_by: String,
+    pub added_at: i64,
+}
+
+/// Where an offence came from when it was received from another instance of the bot
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct Provenance {
+    /// ID of the peer that sent it
+    pub peer: String,
+    pub received_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct ModCase {
+    pub case_id: u64,
+    pub guild_id: String,
+    pub action: ModAction,
+    pub moderator_id: String,
+    pub target_id: String,
+    pub target_name: String,
+    pub reason: String,
+    pub duration: Option<String>,
+    pub created_at: i64,
+    pub log_message_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct Announcement {
+    pub id: u64,
+    pub title: String,
+    pub body: String,
+    pub use_title_prefix: bool,
+    pub thumbnail: Option<String>,
+    pub author_id: String,
+    pub created_at: i64,
+    pub send_at: Option<i64>,
+    pub status: AnnouncementStatus,
+    pub deliveries: Vec<AnnouncementDelivery>,
+    pub failures: Vec<AnnouncementFailure>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct AnnouncementDelivery {
+    pub guild_id: String,
+    pub channel_id: String,
+    pub message_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct AnnouncementFailure {
+    pub guild_id: String,
+    pub server_name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct Feedback {
+    pub id: u64,
+    pub user_id: String,
+    pub username: String,
+    pub avatar_url: String,
+    pub guild_id: Option<String>,
+    pub message: String,
+    pub created_at: i64,
+    pub status: FeedbackStatus,
+    pub message_id: Option<String>,
+    pub replies: Vec<FeedbackReply>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct FeedbackReply {
+    pub staff_id: String,
+    pub staff_name: String,
+    pub message: String,
+    pub sent_at: i64,
+}
+
+/// One user read from a file given to `/excel`
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub struct ImportRecord {
+    pub discord_id: String,
+    pub username: Option<String>,
+    pub reason: Option<String>,
+    pub category: Option<OffenceCategory>,
+    pub vrc_id: Option<String>,
+    pub avatar_url: Option<String>,
+    pub is_bot: bool,
+}
+
+/// Which columns of a CSV (or keys of a JSON object) hold each part of an [`ImportRecord`]
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub struct ColumnMapping {
+    pub id: String,
+    pub name: String,
+    pub reason: Option<String>,
+    pub category: Option<String>,
+    pub vrc_id: Option<String>,
+}
+
+impl Default for ColumnMapping {
+    /// The columns DiscordChatExporter's CSVs use once they've been through the old parser
+    fn default() -> Self {
+        Self {
+            id: "AuthorID".to_string(),
+            name: "Author".to_string(),
+            reason: None,
+            category: None,
+            vrc_id: None,
+        }
+    }
+}
+
+/// What an import would do, worked out before anything is written so it can be previewed
+#[derive(Debug, Default, PartialEq, Clone, Eq)]
+pub struct ImportPlan {
+    pub new: Vec<ImportRecord>,
+    pub existing: Vec<ImportRecord>,
+    pub already_listed: Vec<ImportRecord>,
+    pub skipped_bots: Vec<ImportRecord>,
+    pub skipped_whitelisted: Vec<ImportRecord>,
+    /// Users already in the DB without a VRChat ID that the import has one for, they're in `existing` or `already_listed` too
+    pub vrc_ids_filled: Vec<ImportRecord>,
+}
+
+/// Everything `backup export` saves, `version` is bumped whenever the layout changes
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Backup {
+    pub version: u32,
+    pub created_at: i64,
+    #[serde(default)]
+    pub users: Vec<UserInfo>,
+    #[serde(default)]
+    pub cleared_users: Vec<ClearedUser>,
+    #[serde(default)]
+    pub guild_settings: HashMap<String, GuildSettings>,
+    #[serde(default)]
+    pub authed_server_users: HashMap<String, Vec<String>>,
+    /// Users and roles with a level in each guild, keyed by guild ID then `user:<ID>` or `role:<ID>`
+    #[serde(default)]
+    pub guild_auth: HashMap<String, HashMap<String, AuthLevel>>,
+    #[serde(default)]
+    pub kick_whitelist: Vec<String>,
+    #[serde(default)]
+    pub feedback: Vec<Feedback>,
+    #[serde(default)]
+    pub legacy_feedback: HashMap<String, String>,
+    /// Expired offences moved out of `user:` entries, keyed by Discord ID
+    #[serde(default)]
+    pub archived_offences: HashMap<String, Vec<ArchivedOffence>>,
+    /// `identity-of:` isn't saved, it's rebuilt from these on restore
+    #[serde(default)]
+    pub identities: Vec<Identity>,
+    /// Bot staff keyed by Discord ID, `BOT_OWNERS` aren't in here unless they were granted a tier too
+    #[serde(default)]
+    pub staff_tiers: HashMap<String, StaffTier>,
+    /// Includes users from `user-lists:banned-from-bot`, they're restored as `bot-ban:` entries
+    #[serde(default)]
+    pub bot_bans: Vec<BotBan>,
+}
+
+/// One line of a JSONL backup
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(tag = "kind", content = "data", rename_all = "snake_case")]
+pub enum BackupRecord {
+    Header {
+        version: u32,
+        created_at: i64,
+    },
+    User(UserInfo),
+    ClearedUser(ClearedUser),
+    GuildSettings {
+        guild_id: String,
+        settings: GuildSettings,
+    },
+    AuthedServerUsers {
+        guild_id: String,
+        users: Vec<String>,
+    },
+    GuildAuth {
+        guild_id: String,
+        grants: HashMap<String, AuthLevel>,
+    },
+    KickWhitelist(String),
+    Feedback(Feedback),
+    LegacyFeedback {
+        key: String,
+        message: String,
+    },
+    ArchivedOffences {
+        discord_id: String,
+        offences: Vec<ArchivedOffence>,
+    },
+    Identity(Identity),
+    StaffTier {
+        user_id: String,
+        tier: StaffTier,
+    },
+    BotBan(BotBan),
+}
+
+/// What a restore changed, or would change on a dry run
+#[derive(Debug, Default, PartialEq, Clone, Eq)]
+pub struct RestoreReport {
+    pub users_added: usize,
+    pub users_merged: usize,
+    pub cleared_users_added: usize,
+    pub guild_settings_added: usize,
+    pub authed_users_added: usize,
+    pub kick_whitelist_added: usize,
+    pub feedback_added: usize,
+    pub archived_offences_added: usize,
+    pub identities_added: usize,
+    pub staff_added: usize,
+    pub bot_bans_added: usize,
+}
+
+/// What a VRChat ID migration changed, or would change on a dry run
+#[derive(Debug, Default, PartialEq, Clone, Eq)]
+pub struct VrcMigrationReport {
+    /// Entries that have a VRChat ID at all
+    pub checked: usize,
+    /// Entries whose VRChat ID was already a plain `usr_` ID
+    pub already_valid: usize,
+    /// Discord IDs of entries that had a URL or badly formatted ID turned into a plain `usr_` ID
+    pub normalized: Vec<String>,
+    /// Discord IDs and values of entries with no VRChat ID to be found, these are left alone
+    pub invalid: Vec<(String, String)>,
+}
+
+/// What a guild settings migration changed, or would change on a dry run
+#[derive(Debug, Default, PartialEq, Clone, Eq)]
+pub struct SettingsMigrationReport {
+    /// Every `guild-settings:` entry in the DB
+    pub checked: usize,
+    /// Guild IDs of entries written with an older version
+    pub migrated: Vec<String>,
+    /// Guild IDs and errors of entries that can't be read even after migrating, these are left alone
+    pub invalid: Vec<(String, String)>,
+    /// Every entry that could be read, already migrated
+    pub settings: Vec<(String, GuildSettings)>,
+}
+
+/// One actor behind several accounts, stored under `identity:<n>` with `identity-of:<account>` pointing back at it
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct Identity {
+    pub id: u64,
+    /// Discord ID every other account is an alt of
+    pub primary: String,
+    /// Alts linked to the primary account, either Discord IDs or `usr_` VRChat IDs
+    pub accounts: Vec<LinkedAccount>,
+    pub created_at: i64,
+}
+
+/// An account linked into an [`Identity`] along with why it was linked
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct LinkedAccount {
+    /// Discord ID or `usr_` VRChat ID
+    pub account: String,
+    pub evidence: String,
+    pub linked_by: String,
+    pub linked_at: i64,
+}
+
+/// Another instance of the bot that entries are exchanged with, stored under `federation-peer:<id>`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct FederationPeer {
+    /// The peer's own `FEDERATION_INSTANCE_ID`
+    pub id: String,
+    /// Base URL of the peer's federation server
+    pub url: String,
+    /// Shared secret both sides sign batches with
+    pub secret: String,
+    pub trust: PeerTrust,
+    pub added_at: i64,
+    #[serde(default)]
+    pub last_sent: Option<i64>,
+    #[serde(default)]
+    pub last_received: Option<i64>,
+}
+
+/// A signed batch of users sent between federation peers
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct FederationBatch {
+    pub instance_id: String,
+    pub sent_at: i64,
+    pub users: Vec<UserInfo>,
+}
+
+/// What a peer did with a batch we sent it
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone, Eq)]
+pub struct FederationReceipt {
+    pub accepted: usize,
+    pub quarantined: usize,
+    pub ignored: usize,
+}
+
+/// A partner's key for the HTTP API, stored under `api-key:<SHA256 of the key>` so the key itself is never saved
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct ApiKey {
+    pub name: String,
+    pub created_at: i64,
+    pub created_by: String,
+    /// Allowed to add offences through `POST /api/v1/users`
+    pub can_submit: bool,
+    /// Guild ID offences submitted with this key are recorded under
+    pub guild_id: Option<String>,
+    pub rate_limit_per_minute: u32,
+}
+
+/// Body of a `POST /api/v1/users` submission
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ApiSubmission {
+    pub discord_id: String,
+    pub reason: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub vrc_id: Option<String>,
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub extra: Option<String>,
+    #[serde(default)]
+    pub category: Option<OffenceCategory>,
+}
+
+/// Body of a `POST /api/v1/users/lookup` bulk lookup
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Eq)]
+pub struct ApiBulkLookup {
+    pub ids: Vec<String>,
+}
+
+/// Response to a bulk lookup, every requested ID is either in `found` or `missing`
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ApiBulkLookupResult {
+    pub found: Vec<UserInfo>,
+    pub missing: Vec<String>,
+}
+
+/// Somewhere events get POSTed to, stored under `webhook:<WebhookNumber>`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct Webhook {
+    pub id: u64,
+    pub url: String,
+    /// Payloads are signed with this so the receiver can check they came from the bot
+    pub secret: String,
+    /// Only events from this guild are sent, `None` gets events from every guild
+    pub guild_id: Option<String>,
+    /// Events to send, empty means all of them
+    pub events: Vec<WebhookEvent>,
+    pub created_by: String,
+    pub created_at: i64,
+}
+
+/// The JSON body every webhook gets
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    pub guild_id: Option<String>,
+    pub sent_at: i64,
+    pub data: serde_json::Value,
+}
+
+/// A payload waiting to be sent to one webhook, kept in the `webhook-queue` sorted set until it's delivered or out of attempts
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct WebhookDelivery {
+    /// Random ID so the same payload can be queued twice
+    pub id: String,
+    pub webhook_id: u64,
+    pub payload: WebhookPayload,
+    pub attempts: u32,
+}
+
+/// One attempt at sending a payload, the last few are kept under `webhook-log:<WebhookNumber>`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Eq)]
+pub struct WebhookLogEntry {
+    pub event: WebhookEvent,
+    pub attempt: u32,
+    pub at: i64,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    /// When it will be tried again, `None` if it was delivered or gave up
+    pub retry_at: Option<i64>,
+}
+
+/// A user received from a peer that is waiting for review, stored under `federation-quarantine:<peer>:<DiscordUserID>`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct QuarantinedUser {
+    pub peer: String,
+    pub received_at: i64,
+    pub user: UserInfo,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct SearchSyncJob {
+    pub discord_id: String,
+    pub attempts: u32,
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Eq)]
+pub struct SearchDrift {
+    pub missing: Vec<String>,
+    pub stale: Vec<String>,
+    pub outdated: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash, Eq)]
@@ -74,6 +578,11 @@ pub struct BlacklistHit {
     pub reason: String,
     pub image: String,
     pub extra: String,
+    /// Discord ID of the account this user is a linked alt of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt_of: Option<String>,
+    #[serde(default)]
+    pub risk_score: u32,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -155,3 +664,27 @@ impl From<Option<String>> for OptionalString2 {
 pub struct PasteResponse {
     pub key: String,
 }
+
+/// The parts of a DiscordChatExporter JSON export that `/excel` reads
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DceExport {
+    pub messages: Vec<DceMessage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DceMessage {
+    pub author: DceAuthor,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DceAuthor {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub discriminator: Option<String>,
+    #[serde(default)]
+    pub is_bot: bool,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+}
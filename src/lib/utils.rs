@@ -1,10 +1,15 @@
 use crate::args::Args;
-use crate::structs::GuildSettings;
+use crate::structs::{GuildSettings, Offense, UserInfo, GUILD_SETTINGS_VERSION};
 use crate::types::Context;
 use crate::types::Error;
-use crate::vars::REDIS_ADDR;
+use crate::vars::{BULK_BAN_DELAY_MS, REDIS_ADDR};
 use clap::Parser;
+use once_cell::sync::Lazy;
+use poise::serenity_prelude::{self as serenity, GuildId, UserId};
+use regex::Regex;
 use tracing::instrument;
+#[cfg(feature = "database")]
+use tracing::{event, Level};
 
 /// Converts a dsicord snowflake to a unix timecode
 #[must_use]
@@ -41,43 +46,198 @@ pub async fn set_guild_settings(
     con: &mut redis::aio::MultiplexedConnection,
     settings: GuildSettings,
 ) -> Result<(), Error> {
-    let json = serde_json::to_string(&settings).unwrap();
+    save_guild_settings(
+        con,
+        *ctx.guild_id().expect("Not run inside guild").as_u64(),
+        &settings,
+    )
+    .await
+}
+
+/// Pushes guild settings to DB for any guild, for when there's no command context like button presses
+#[cfg(feature = "database")]
+#[instrument(skip(con))]
+pub async fn save_guild_settings(
+    con: &mut redis::aio::MultiplexedConnection,
+    guild_id: u64,
+    settings: &GuildSettings,
+) -> Result<(), Error> {
+    let json = serde_json::to_string(settings)?;
 
     let mut pipe = redis::pipe();
 
-    pipe.cmd("JSON.SET").arg(&[
-        format!(
-            "guild-settings:{}",
-            ctx.guild_id().expect("Not run inside guild")
-        ),
-        "$".to_string(),
-        json,
-    ]);
+    pipe.cmd("JSON.SET")
+        .arg(&[format!("guild-settings:{guild_id}"), "$".to_string(), json]);
 
-    pipe.atomic().query_async(con).await?;
+    pipe.atomic().query_async::<_, ()>(con).await?;
 
     Ok(())
 }
 
-/// Adds the user to a server's auth list in the DB
+/// Points a guild's kick messages and announcements at a channel and sets alt protection, like `/setup` does
+///
+/// Creates the guild's settings if it doesn't have any yet, returns `true` if it did.
 #[cfg(feature = "database")]
 #[instrument(skip(con))]
-pub async fn auth(
-    ctx: Context<'_>,
+pub async fn setup_guild_settings(
     con: &mut redis::aio::MultiplexedConnection,
-    uid: String,
+    guild_id: u64,
+    channel_id: String,
+    server_name: String,
+    alt_protection: bool,
+) -> Result<bool, Error> {
+    let (settings, created) = match get_guild_settings(con, guild_id).await? {
+        Some(mut settings) => {
+            settings.channel_id = channel_id;
+            settings.kick = alt_protection;
+            settings.server_name = server_name;
+            (settings, false)
+        }
+        None => (
+            GuildSettings {
+                version: GUILD_SETTINGS_VERSION,
+                channel_id,
+                kick: alt_protection,
+                server_name,
+                log_channel_id: None,
+                announcements_opt_out: false,
+                alert_min_score: 0,
+                kick_min_score: None,
+            },
+            true,
+        ),
+    };
+
+    save_guild_settings(con, guild_id, &settings).await?;
+
+    Ok(created)
+}
+
+/// Reads a guild's settings from the DB, `None` if the guild hasn't been setup yet
+#[cfg(feature = "database")]
+#[instrument(skip(con))]
+pub async fn get_guild_settings(
+    con: &mut redis::aio::MultiplexedConnection,
+    guild_id: u64,
+) -> Result<Option<GuildSettings>, Error> {
+    let guild_settings_json_in: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!("guild-settings:{guild_id}"))
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let Some(json_in) = guild_settings_json_in else {
+        return Ok(None);
+    };
+
+    let (settings, migrated) = crate::settings::parse_guild_settings(&json_in)?;
+
+    // Save the upgrade so it only has to be done once
+    if migrated {
+        save_guild_settings(con, guild_id, &settings).await?;
+    }
+
+    Ok(Some(settings))
+}
+
+/// Adds an offence to a user's DB entry, creating the entry first if they aren't in the DB yet
+///
+/// `username` and `vrc_id` are only used when a new entry gets created, `vrc_id` is normalized with [`crate::vrchat::normalize_vrc_id`] when it can be.
+/// `added_at` is set to now if the offence doesn't have one. An `OffenceAdded` webhook event is fired once it's saved, `added_by` goes in it.
+#[cfg(feature = "database")]
+#[instrument(skip(con))]
+pub async fn add_offence(
+    con: &mut redis::aio::MultiplexedConnection,
+    uid: u64,
+    username: String,
+    vrc_id: Option<String>,
+    mut offense: Offense,
+    added_by: &str,
 ) -> Result<(), Error> {
-    redis::cmd("SADD")
-        .arg(&[
-            format!(
-                "authed-server-users:{}",
-                ctx.guild_id().expect("Not run inside guild")
-            ),
-            uid,
-        ])
+    offense
+        .added_at
+        .get_or_insert_with(|| chrono::Utc::now().timestamp());
+
+    let vrc_id = vrc_id.map(|id| crate::vrchat::normalize_vrc_id(&id).unwrap_or(id));
+
+    let webhook_data = serde_json::json!({
+        "discord_id": uid.to_string(),
+        "username": username,
+        "vrc_id": vrc_id,
+        "offence": offense,
+        "added_by": added_by,
+    });
+    let webhook_guild = offense.guild_id.parse::<u64>().ok();
+
+    if !offense.evidence.is_empty() {
+        let mut pipe = redis::pipe();
+
+        for evidence in &offense.evidence {
+            crate::evidence::track_evidence(&mut pipe, &evidence.sha256, &uid.to_string());
+        }
+
+        pipe.query_async::<_, ()>(con).await?;
+    }
+
+    let existing: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!("user:{uid}"))
+        .clone()
         .query_async(con)
         .await?;
 
+    if existing.is_some() {
+        let json_offense = serde_json::to_string(&offense)?;
+
+        redis::cmd("JSON.ARRAPPEND")
+            .arg(format!("user:{uid}"))
+            .arg("$.offences".to_string())
+            .arg(json_offense)
+            .query_async::<_, ()>(con)
+            .await?;
+    } else {
+        if let Some(vrc_id) = vrc_id
+            .as_deref()
+            .filter(|id| crate::vrchat::has_vrc_id(Some(id)))
+        {
+            let mut pipe = redis::pipe();
+            crate::vrchat::index_vrc_id(&mut pipe, vrc_id, &uid.to_string());
+            pipe.query_async::<_, ()>(con).await?;
+        }
+
+        let new = UserInfo {
+            vrc_id: vrc_id.or_else(|| Some("N/A".to_string())),
+            username: Some(username),
+            discord_id: Some(format!("{uid}")),
+            offences: vec![offense],
+        };
+
+        let json_user = serde_json::to_string(&new)?;
+
+        redis::cmd("JSON.SET")
+            .arg(format!("user:{uid}"))
+            .arg("$".to_string())
+            .arg(json_user)
+            .query_async::<_, ()>(con)
+            .await?;
+    }
+
+    crate::search::queue_search_sync(con, &[uid.to_string()]).await?;
+
+    #[cfg(feature = "federation")]
+    crate::federation::queue_federation_sync(con, &[uid.to_string()]).await?;
+
+    // The offence is already saved so a webhook problem shouldn't make it look like it wasn't
+    if let Err(error) = crate::webhooks::fire_webhook_event(
+        con,
+        crate::enums::WebhookEvent::OffenceAdded,
+        webhook_guild,
+        webhook_data,
+    )
+    .await
+    {
+        event!(Level::WARN, "Failed to fire offence webhook" = uid, error = %error);
+    }
+
     Ok(())
 }
 
@@ -124,16 +284,255 @@ pub async fn is_uid_valid_user(uid: u64, ctx: &Context<'_>) -> anyhow::Result<bo
     Ok(u_opt.is_some())
 }
 
+// Word boundaries so longer numbers don't get cut up into IDs, mentions like <@ID> still match
+static USER_ID_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b\d{17,20}\b").unwrap());
+
+/// Pulls every Discord user ID out of some text, works on plain lists, mentions and CSV files alike
+///
+/// IDs are returned in the order they first appear with any duplicates removed.
+#[must_use]
+pub fn extract_user_ids(text: &str) -> Vec<u64> {
+    let mut ids = Vec::new();
+
+    for found in USER_ID_RE.find_iter(text) {
+        if let Ok(id) = found.as_str().parse::<u64>() {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+
+    ids
+}
+
+// Headers [`extract_csv_user_ids`] treats as the ID column, compared in lowercase
+const CSV_ID_COLUMNS: [&str; 6] = [
+    "authorid",
+    "userid",
+    "user id",
+    "discordid",
+    "discord id",
+    "id",
+];
+
+/// Pulls the user IDs out of a CSV's ID column, like the `AuthorID` column of a DiscordChatExporter CSV
+///
+/// Returns `None` if there's no ID column, searching the whole file with [`extract_user_ids`] would also pick up channel, message and attachment IDs from the other columns.
+#[must_use]
+pub fn extract_csv_user_ids(text: &str) -> Option<Vec<u64>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let id_column = reader
+        .headers()
+        .ok()?
+        .iter()
+        .position(|header| CSV_ID_COLUMNS.contains(&header.trim().to_lowercase().as_str()))?;
+
+    let mut ids = Vec::new();
+
+    for row in reader.records().flatten() {
+        for id in extract_user_ids(row.get(id_column).unwrap_or_default()) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+
+    Some(ids)
+}
+
+/// Turns a duration like `30m`, `12h`, `7d` or `1d12h` into a [`chrono::Duration`]
+///
+/// Understands `s`, `m`, `h`, `d` and `w`, anything else (or a duration of zero) gives `None`.
+#[must_use]
+pub fn parse_duration(input: &str) -> Option<chrono::Duration> {
+    let mut total = chrono::Duration::zero();
+    let mut number = String::new();
+
+    for c in input.trim().to_lowercase().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let amount = number.parse::<i64>().ok()?;
+        number.clear();
+
+        let part = match c {
+            's' => chrono::Duration::try_seconds(amount)?,
+            'm' => chrono::Duration::try_minutes(amount)?,
+            'h' => chrono::Duration::try_hours(amount)?,
+            'd' => chrono::Duration::try_days(amount)?,
+            'w' => chrono::Duration::try_weeks(amount)?,
+            _ => return None,
+        };
+
+        total = total.checked_add(&part)?;
+    }
+
+    // A number with no unit on the end is ambiguous so refuse it
+    if !number.is_empty() || total <= chrono::Duration::zero() {
+        return None;
+    }
+
+    Some(total)
+}
+
+/// Cuts text down to `max` characters, ending it with `...` if anything was removed
+#[must_use]
+pub fn truncate_text(text: &str, max: usize) -> String {
+    if text.chars().count() > max {
+        let mut cut: String = text.chars().take(max.saturating_sub(3)).collect();
+        cut.push_str("...");
+        cut
+    } else {
+        text.to_string()
+    }
+}
+
+/// Whether Discord refused a DM because the user doesn't accept DMs from the bot, rather than something else going wrong
+#[must_use]
+pub fn is_dm_blocked(error: &serenity::Error) -> bool {
+    // 50007 is Discord's "Cannot send messages to this user"
+    matches!(
+        error,
+        serenity::Error::Http(http) if matches!(
+            http.as_ref(),
+            serenity::HttpError::UnsuccessfulRequest(response) if response.error.code == 50007
+        )
+    )
+}
+
+/// Cuts a ban/kick reason down to the 512 characters Discord allows in the audit log
+#[must_use]
+pub fn audit_log_reason(reason: &str) -> String {
+    truncate_text(reason, 512)
+}
+
+/// Signs a request body with a shared secret as a hex HMAC-SHA256, used by webhooks and federation so the receiver can check it came from us
+#[must_use]
+pub fn sign_body(secret: &str, body: &[u8]) -> String {
+    use hmac::{Hmac, Mac};
+
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts any key length");
+    mac.update(body);
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Bans every user in `targets` with their own reason, waiting between each ban so long lists don't get rate limited
+///
+/// Returns each user ID alongside the result of trying to ban them.
+#[instrument(skip(ctx, targets))]
+pub async fn bulk_ban(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    targets: Vec<(UserId, String)>,
+    dmd: u8,
+) -> Vec<(UserId, Result<(), serenity::Error>)> {
+    let mut results = Vec::with_capacity(targets.len());
+
+    for (i, (user_id, reason)) in targets.into_iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(BULK_BAN_DELAY_MS)).await;
+        }
+
+        let result = guild_id
+            .ban_with_reason(ctx, user_id, dmd, audit_log_reason(&reason))
+            .await;
+
+        results.push((user_id, result));
+    }
+
+    results
+}
+
 #[cfg(test)]
 mod utils_tests {
 
-    use crate::utils::{snowflake_to_unix, verbose_mode};
+    use crate::utils::{
+        audit_log_reason, extract_csv_user_ids, extract_user_ids, is_dm_blocked, parse_duration,
+        snowflake_to_unix, truncate_text, verbose_mode,
+    };
 
     #[test]
     fn snowflake_unix_test() {
         assert_eq!(snowflake_to_unix(383_507_911_160_233_985), 1_511_505_811);
     }
 
+    #[test]
+    fn extract_user_ids_test() {
+        assert_eq!(
+            extract_user_ids("<@383507911160233985>, 212132817017110528\n383507911160233985 123"),
+            vec![383_507_911_160_233_985, 212_132_817_017_110_528]
+        );
+        assert!(extract_user_ids("1234567890123456789012345").is_empty());
+    }
+
+    #[test]
+    fn extract_csv_user_ids_test() {
+        assert_eq!(
+            extract_csv_user_ids("AuthorID,Author,Content,Attachments\n383507911160233985,Ripper,<#212132817017110528>,https://cdn.discordapp.com/attachments/164694510947794944/1.png\n"),
+            Some(vec![383_507_911_160_233_985])
+        );
+        assert_eq!(extract_csv_user_ids("Name,Reason\nRipper,Leaking\n"), None);
+    }
+
+    #[test]
+    fn parse_duration_test() {
+        assert_eq!(parse_duration("30m"), chrono::Duration::try_minutes(30));
+        assert_eq!(parse_duration("1d 12h"), chrono::Duration::try_hours(36));
+        assert_eq!(parse_duration("2W"), chrono::Duration::try_days(14));
+        assert_eq!(parse_duration("10"), None);
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("5y"), None);
+    }
+
+    #[test]
+    fn truncate_text_test() {
+        assert_eq!(truncate_text("Ripper", 10), "Ripper");
+        assert_eq!(truncate_text("Ripper of avatars", 10), "Ripper ...");
+        assert_eq!(truncate_text(&"é".repeat(2000), 1024).chars().count(), 1024);
+    }
+
+    #[test]
+    fn is_dm_blocked_test() {
+        use poise::serenity_prelude as serenity;
+        use serenity::http::error::{DiscordJsonError, ErrorResponse};
+
+        let discord_error = |code| {
+            serenity::Error::Http(Box::new(serenity::HttpError::UnsuccessfulRequest(
+                ErrorResponse {
+                    status_code: serenity::http::StatusCode::FORBIDDEN,
+                    url: reqwest::Url::parse("https://discord.com/api/v10/channels/1/messages")
+                        .unwrap(),
+                    error: serde_json::from_value::<DiscordJsonError>(serde_json::json!({
+                        "code": code,
+                        "message": "",
+                    }))
+                    .unwrap(),
+                },
+            )))
+        };
+
+        assert!(is_dm_blocked(&discord_error(50007)));
+        assert!(!is_dm_blocked(&discord_error(50035)));
+        assert!(!is_dm_blocked(&serenity::Error::Other("Embed too long")));
+    }
+
+    #[test]
+    fn audit_log_reason_test() {
+        assert_eq!(audit_log_reason("Ripper"), "Ripper");
+        assert_eq!(audit_log_reason(&"a".repeat(600)).chars().count(), 512);
+    }
+
     #[test]
     fn verbose_mode_test() {
         // Inverting output since verbose mode is disabled by default
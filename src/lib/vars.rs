@@ -2,17 +2,23 @@ pub const VERSION: Option<&str> = option_env!("CARGO_PKG_VERSION");
 
 pub const HELP_EXTRA_TEXT: &str = "Find the documentation website at https://fbtsecurity.fbtheaven.com/\nRun the About command to find out more (/about)";
 
-// TODO: change this list to your own bot admin user IDs
+// TODO: change this list to your own user IDs
 
+// These users are always bot owners so there's someone to grant staff tiers with `/staff grant`, everyone else's tier is kept in `staff-tiers`
 // You need to increase the number in [u64; X] so rust knows the limit of the array
-pub const BOT_ADMINS: [u64; 6] = [
+pub const BOT_OWNERS: [u64; 3] = [
     212_132_817_017_110_528,
+    164_694_510_947_794_944,
+    383_507_911_160_233_985,
+]; // Azuki, Cross, Mojo
+
+// The bot admins from before staff tiers, they're given `Admin` in `staff-tiers` the first time the bot starts
+pub const LEGACY_BOT_ADMINS: [u64; 4] = [
     288_186_677_967_585_280,
     211_027_317_068_136_448,
-    383_507_911_160_233_985,
     168_600_506_233_651_201,
     231_482_341_921_521_664,
-]; // Azuki, Komi, Xeno, Mojo, Ellie, Wundie
+]; // Komi, Xeno, Ellie, Wundie
 
 // TODO: you can mass replace the name of this variable easily
 // TODO: change to your own guild ID
@@ -22,6 +28,12 @@ pub const FBT_GUILD_ID: u64 = 737_168_134_502_350_849; // FBT's guild ID
 // TODO: this is the channel wehre the feedback command sends it's response for you to read
 pub const FEEDBACK_CHANNEL_ID: u64 = 925_599_477_283_311_636;
 
+// TODO: this is the channel where servers asking to be set up get posted for staff to approve
+pub const ONBOARDING_CHANNEL_ID: u64 = 953_435_498_318_286_898;
+
+// How long a server request stays claimed while a reviewer's approve or deny goes through, a crashed review can be redone after this
+pub const ONBOARDING_REVIEW_CLAIM_SECS: u64 = 60;
+
 //pub const FBT_GUILD_ID: u64 = 838658675916275722; // My test server ID
 
 // TODO: you need your own Redis DB, this is where you put in the login details and adress of the DB
@@ -39,6 +51,105 @@ pub const MEILISEARCH_HOST: &str = "http://google.com:7777";
 #[cfg(feature = "database")]
 pub const MEILISEARCH_API_KEY: &str = "why-so-strange";
 
+// Meilisearch index every DB entry gets pushed to
+#[cfg(feature = "database")]
+pub const MEILISEARCH_INDEX: &str = "entries";
+
+// How many matches `/search name` and `/search vrchat` show
+pub const SEARCH_MATCH_LIMIT: usize = 10;
+
+// How many times a search engine update gets retried before it's moved to `search-sync-failed`
+pub const SEARCH_SYNC_MAX_ATTEMPTS: u32 = 5;
+
+// How long the search sync worker waits between checking the queue, doubled for every failed batch in a row
+pub const SEARCH_SYNC_INTERVAL_SECS: u64 = 5;
+
+// How many times a webhook delivery is tried before giving up
+#[cfg(feature = "database")]
+pub const WEBHOOK_MAX_ATTEMPTS: u32 = 6;
+
+// How long before a failed webhook delivery is retried, doubled for every failed attempt
+#[cfg(feature = "database")]
+pub const WEBHOOK_RETRY_BASE_SECS: i64 = 30;
+
+// How many delivery attempts are kept in each webhook's log
+#[cfg(feature = "database")]
+pub const WEBHOOK_LOG_LENGTH: isize = 50;
+
+// How often expired offences get moved into `offence-archive:`
+#[cfg(feature = "database")]
+pub const OFFENCE_EXPIRY_INTERVAL_SECS: u64 = 3600;
+
+// Folder evidence files are saved in when `EVIDENCE_S3_ENDPOINT` is empty
+#[cfg(feature = "database")]
+pub const EVIDENCE_DIR: &str = "evidence";
+
+// TODO: change to where evidence can be viewed from, files are linked as `<EVIDENCE_PUBLIC_URL>/<file>`
+// For local storage point a web server at `EVIDENCE_DIR`, for S3 this is normally the bucket's public URL
+#[cfg(feature = "database")]
+pub const EVIDENCE_PUBLIC_URL: &str = "https://evidence.example.com";
+
+// TODO: set to an S3-compatible endpoint like "https://s3.us-east-1.amazonaws.com" to keep evidence there instead of on disk
+#[cfg(feature = "database")]
+pub const EVIDENCE_S3_ENDPOINT: &str = "";
+
+// TODO: change to your own bucket, region and access keys if using S3
+#[cfg(feature = "database")]
+pub const EVIDENCE_S3_BUCKET: &str = "fbt-evidence";
+
+#[cfg(feature = "database")]
+pub const EVIDENCE_S3_REGION: &str = "us-east-1";
+
+#[cfg(feature = "database")]
+pub const EVIDENCE_S3_ACCESS_KEY: &str = "not-a-real-key";
+
+#[cfg(feature = "database")]
+pub const EVIDENCE_S3_SECRET_KEY: &str = "not-a-real-secret";
+
+// Biggest attachment that gets copied into the evidence store, 25MB like Discord's own limit
+#[cfg(feature = "database")]
+pub const EVIDENCE_MAX_BYTES: u64 = 25 * 1024 * 1024;
+
+// Most files a DiscordChatExporter `.zip` given to `/excel` can have
+#[cfg(feature = "database")]
+pub const IMPORT_ZIP_MAX_ENTRIES: usize = 500;
+
+// Biggest a single JSON file in an imported `.zip` can be once unzipped, bigger ones are skipped
+#[cfg(feature = "database")]
+pub const IMPORT_ZIP_MAX_FILE_BYTES: u64 = 32 * 1024 * 1024;
+
+// Biggest an imported `.zip` can be once unzipped, stops zip bombs from using up all the bot's memory
+#[cfg(feature = "database")]
+pub const IMPORT_ZIP_MAX_TOTAL_BYTES: u64 = 128 * 1024 * 1024;
+
+// TODO: change to a name for your instance, peers add you under this ID
+#[cfg(feature = "federation")]
+pub const FEDERATION_INSTANCE_ID: &str = "fbt-heaven";
+
+// TODO: change to where peers should reach your federation server
+#[cfg(feature = "federation")]
+pub const FEDERATION_BIND_ADDR: &str = "0.0.0.0:7878";
+
+// How long the federation worker waits between sending batches to peers
+#[cfg(feature = "federation")]
+pub const FEDERATION_SYNC_INTERVAL_SECS: u64 = 60;
+
+// How old a received batch can be before it's rejected, stops old batches being replayed
+#[cfg(feature = "federation")]
+pub const FEDERATION_MAX_BATCH_AGE_SECS: i64 = 300;
+
+// TODO: change to the address the partner API should listen on
+#[cfg(feature = "api")]
+pub const API_BIND_ADDR: &str = "0.0.0.0:7879";
+
+// Most IDs that can be looked up in one bulk lookup request
+#[cfg(feature = "api")]
+pub const API_BULK_LOOKUP_LIMIT: usize = 100;
+
+// Requests per minute a new API key gets unless another limit is given
+#[cfg(feature = "api")]
+pub const API_DEFAULT_RATE_LIMIT: u32 = 60;
+
 // TODO: change to your own bot token
 pub const BOT_TOKEN: &str =
     "not touching this <3";
@@ -76,6 +187,31 @@ pub const BOT_IDS: [u64; 22] = [
 pub const TICKET_CATEGORY: u64 = 982_769_870_259_240_981;
 pub const CLOSED_TICKET_CATEGORY: u64 = 983_228_142_107_918_336;
 
+// TODO: Change to your own announcement branding
+pub const ANNOUNCEMENT_TITLE_PREFIX: &str = "New announcement from FBT Security: ";
+pub const ANNOUNCEMENT_AUTHOR_NAME: &str = "FBT Security";
+pub const ANNOUNCEMENT_AUTHOR_ICON: &str = "https://cdn.discordapp.com/avatars/743269383438073856/959512463b1559b14818590d8c8a9d2a.webp?size=4096";
+pub const ANNOUNCEMENT_THUMBNAIL: &str =
+    "https://media.giphy.com/media/U4sfHXAALLYBQzPcWk/giphy.gif";
+
+// How long an announcement stays locked while it's being sent or changed, a crashed send can be retried after this
+pub const ANNOUNCEMENT_CLAIM_SECS: u64 = 3600;
+
+// How long to wait between each ban when banning a list of users, keeps big lists from hitting Discord's rate limits
+pub const BULK_BAN_DELAY_MS: u64 = 500;
+
+// Most users that can be banned from one list, with the delay above this finishes well before the 15 minutes Discord gives to send the results
+pub const BULK_BAN_MAX_USERS: usize = 1000;
+
+// Most offences shown on each page of search results, pages get fewer when the offences wouldn't fit in one embed
+pub const SEARCH_OFFENCES_PER_PAGE: usize = 3;
+
+// How long the buttons on paginated results keep working after the last press
+pub const PAGINATION_TIMEOUT_SECS: u64 = 120;
+
+// How long the `/settings` menus and buttons keep working after the last change
+pub const SETTINGS_TIMEOUT_SECS: u64 = 300;
+
 #[cfg(feature = "database")]
 #[derive(Debug, poise::ChoiceParameter)]
 pub enum BlacklistOutput {
@@ -0,0 +1,203 @@
+use crate::search::{queue_search_sync, search_entries};
+use crate::structs::{UserInfo, VrcMigrationReport};
+use crate::types::Error;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tracing::instrument;
+
+static VRC_ID_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)usr_[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}").unwrap()
+});
+
+/// Pulls a canonical `usr_<uuid>` VRChat ID out of an ID or profile URL
+///
+/// Works on plain IDs and every URL we've seen people paste, like `vrchat.com/home/user/<id>`,
+/// `vrchat.com/api/1/users/<id>` and `?userId=<id>` links. Gives `None` if there's no ID in there.
+#[must_use]
+pub fn normalize_vrc_id(input: &str) -> Option<String> {
+    VRC_ID_RE
+        .find(input.trim())
+        .map(|found| found.as_str().to_lowercase())
+}
+
+/// Checks if a stored VRChat ID is actually set, entries without one hold `N/A`
+#[must_use]
+pub fn has_vrc_id(vrc_id: Option<&str>) -> bool {
+    vrc_id.is_some_and(|id| !id.trim().is_empty() && id != "N/A")
+}
+
+/// Adds a user to the reverse lookup for their VRChat ID, `vrc_id` should already be normalized
+pub fn index_vrc_id(pipe: &mut redis::Pipeline, vrc_id: &str, discord_id: &str) {
+    pipe.cmd("SADD")
+        .arg(format!("vrc-id:{vrc_id}"))
+        .arg(discord_id)
+        .ignore();
+}
+
+/// Finds every user with a VRChat ID, `vrc_id` should already be normalized
+///
+/// Uses the `vrc-id:` reverse lookup, falling back to the search engine for entries that haven't been indexed yet.
+///
+/// # Errors
+///
+/// This function will return an error if the DB or search engine can't be reached.
+#[instrument(skip(con))]
+pub async fn get_users_by_vrc_id(
+    con: &mut redis::aio::MultiplexedConnection,
+    vrc_id: &str,
+) -> Result<Vec<UserInfo>, Error> {
+    let discord_ids: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(format!("vrc-id:{vrc_id}"))
+        .query_async(con)
+        .await?;
+
+    let mut users = Vec::new();
+
+    if discord_ids.is_empty() {
+        users = search_entries(vrc_id, "vrc_id").await?;
+    } else {
+        let mut key_pipe = redis::pipe();
+
+        for discord_id in &discord_ids {
+            key_pipe.cmd("JSON.GET").arg(format!("user:{discord_id}"));
+        }
+
+        let entries: Vec<Option<String>> = key_pipe.query_async(con).await?;
+
+        for entry in entries.into_iter().flatten() {
+            users.push(serde_json::from_str::<UserInfo>(&entry)?);
+        }
+    }
+
+    // The lookup isn't cleaned when an entry's ID changes so check every match is still right
+    users.retain(|user| {
+        user.vrc_id.as_deref().and_then(normalize_vrc_id).as_deref() == Some(vrc_id)
+    });
+
+    Ok(users)
+}
+
+/// Rewrites every VRChat ID in the DB to a plain `usr_` ID and rebuilds the `vrc-id:` reverse lookup
+///
+/// Values with no ID to be found are left alone and listed in the report so they can be fixed by hand.
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn migrate_vrc_ids(
+    con: &mut redis::aio::MultiplexedConnection,
+    dry_run: bool,
+) -> Result<VrcMigrationReport, Error> {
+    let mut report = VrcMigrationReport::default();
+
+    let key_list: Vec<String> = redis::cmd("KEYS")
+        .arg("user:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    if key_list.is_empty() {
+        return Ok(report);
+    }
+
+    let mut key_pipe = redis::pipe();
+
+    for key in &key_list {
+        key_pipe.cmd("JSON.GET").arg(key);
+    }
+
+    let entries: Vec<String> = key_pipe.atomic().query_async(con).await?;
+
+    let old_index: Vec<String> = redis::cmd("KEYS")
+        .arg("vrc-id:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    let mut pipe = redis::pipe();
+
+    for key in old_index {
+        pipe.cmd("DEL").arg(key).ignore();
+    }
+
+    for (key, entry) in key_list.iter().zip(entries) {
+        let user: UserInfo = serde_json::from_str(&entry)?;
+        let discord_id = key.trim_start_matches("user:").to_string();
+
+        let Some(raw) = user.vrc_id.filter(|id| has_vrc_id(Some(id))) else {
+            continue;
+        };
+
+        report.checked += 1;
+
+        let Some(vrc_id) = normalize_vrc_id(&raw) else {
+            report.invalid.push((discord_id, raw));
+            continue;
+        };
+
+        if vrc_id == raw {
+            report.already_valid += 1;
+        } else {
+            pipe.cmd("JSON.SET")
+                .arg(key)
+                .arg("$.vrc_id".to_string())
+                .arg(serde_json::to_string(&vrc_id)?)
+                .ignore();
+
+            report.normalized.push(discord_id.clone());
+        }
+
+        index_vrc_id(&mut pipe, &vrc_id, &discord_id);
+    }
+
+    if !dry_run {
+        pipe.atomic().query_async::<_, ()>(con).await?;
+        queue_search_sync(con, &report.normalized).await?;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod vrchat_tests {
+    use super::*;
+
+    const ID: &str = "usr_c1644b5b-3ca4-45b4-97c6-a2a0de70d469";
+
+    #[test]
+    fn normalize_vrc_id_test() {
+        assert_eq!(normalize_vrc_id(ID).as_deref(), Some(ID));
+        assert_eq!(
+            normalize_vrc_id(" USR_C1644B5B-3CA4-45B4-97C6-A2A0DE70D469 ").as_deref(),
+            Some(ID)
+        );
+        assert_eq!(
+            normalize_vrc_id(&format!("https://vrchat.com/home/user/{ID}")).as_deref(),
+            Some(ID)
+        );
+        assert_eq!(
+            normalize_vrc_id(&format!("vrchat.com/home/user/{ID}/favorites?tab=1")).as_deref(),
+            Some(ID)
+        );
+        assert_eq!(
+            normalize_vrc_id(&format!("https://vrchat.com/api/1/users/{ID}")).as_deref(),
+            Some(ID)
+        );
+        assert_eq!(
+            normalize_vrc_id(&format!("https://vrchat.com/home/launch?userId={ID}")).as_deref(),
+            Some(ID)
+        );
+        assert_eq!(normalize_vrc_id("usr_1234"), None);
+        assert_eq!(normalize_vrc_id("SomeDisplayName"), None);
+        assert_eq!(normalize_vrc_id("N/A"), None);
+    }
+
+    #[test]
+    fn has_vrc_id_test() {
+        assert!(has_vrc_id(Some(ID)));
+        assert!(!has_vrc_id(Some("N/A")));
+        assert!(!has_vrc_id(Some(" ")));
+        assert!(!has_vrc_id(None));
+    }
+}
@@ -0,0 +1,551 @@
+use crate::enums::WebhookEvent;
+use crate::structs::{Webhook, WebhookDelivery, WebhookLogEntry, WebhookPayload};
+use crate::types::Error;
+use crate::utils::{open_redis_connection, sign_body};
+use crate::vars::{WEBHOOK_LOG_LENGTH, WEBHOOK_MAX_ATTEMPTS, WEBHOOK_RETRY_BASE_SECS};
+use rand::Rng;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tracing::{event, instrument, Level};
+
+/// Header holding the hex HMAC-SHA256 of the body, signed with the webhook's secret
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-fbt-signature";
+
+/// Header holding the event name so receivers can route without parsing the body
+pub const WEBHOOK_EVENT_HEADER: &str = "x-fbt-event";
+
+/// Checks if a webhook wants an event from a guild
+#[must_use]
+pub fn webhook_matches(webhook: &Webhook, event: WebhookEvent, guild_id: Option<&str>) -> bool {
+    let wants_event = webhook.events.is_empty() || webhook.events.contains(&event);
+    let wants_guild = webhook
+        .guild_id
+        .as_deref()
+        .is_none_or(|id| Some(id) == guild_id);
+
+    wants_event && wants_guild
+}
+
+/// How long to wait before retrying a delivery that has failed `attempts` times, `None` once it should give up
+#[must_use]
+pub fn retry_delay(attempts: u32) -> Option<i64> {
+    (attempts < WEBHOOK_MAX_ATTEMPTS)
+        .then(|| WEBHOOK_RETRY_BASE_SECS * 2_i64.pow(attempts.saturating_sub(1)))
+}
+
+/// Makes a random secret for a new webhook
+#[must_use]
+pub fn generate_webhook_secret() -> String {
+    let bytes: [u8; 24] = rand::thread_rng().gen();
+
+    hex::encode(bytes)
+}
+
+/// Reads a webhook from the DB
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or the entry is invalid.
+#[instrument(skip(con))]
+pub async fn get_webhook(
+    con: &mut redis::aio::MultiplexedConnection,
+    id: u64,
+) -> Result<Option<Webhook>, Error> {
+    let json_in: Option<String> = redis::cmd("JSON.GET")
+        .arg(format!("webhook:{id}"))
+        .clone()
+        .query_async(con)
+        .await?;
+
+    match json_in {
+        None => Ok(None),
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+    }
+}
+
+/// Writes a webhook to the DB
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con, webhook))]
+pub async fn save_webhook(
+    con: &mut redis::aio::MultiplexedConnection,
+    webhook: &Webhook,
+) -> Result<(), Error> {
+    redis::cmd("JSON.SET")
+        .arg(format!("webhook:{}", webhook.id))
+        .arg("$".to_string())
+        .arg(serde_json::to_string(webhook)?)
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Reads every webhook from the DB, oldest first
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn get_all_webhooks(
+    con: &mut redis::aio::MultiplexedConnection,
+) -> Result<Vec<Webhook>, Error> {
+    let key_list: Vec<String> = redis::cmd("KEYS")
+        .arg("webhook:*")
+        .clone()
+        .query_async(con)
+        .await?;
+
+    if key_list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut key_pipe = redis::pipe();
+
+    for key in key_list {
+        key_pipe.cmd("JSON.GET").arg(key);
+    }
+
+    let entries: Vec<String> = key_pipe.atomic().query_async(con).await?;
+
+    let mut webhooks = Vec::new();
+    for entry in entries {
+        let webhook: Webhook = serde_json::from_str(&entry)?;
+        webhooks.push(webhook);
+    }
+
+    webhooks.sort_by_key(|w| w.id);
+
+    Ok(webhooks)
+}
+
+/// Deletes a webhook and its delivery log, anything still queued for it is dropped when the worker gets to it
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con))]
+pub async fn delete_webhook(
+    con: &mut redis::aio::MultiplexedConnection,
+    id: u64,
+) -> Result<(), Error> {
+    redis::cmd("DEL")
+        .arg(format!("webhook:{id}"))
+        .arg(format!("webhook-log:{id}"))
+        .query_async::<_, ()>(con)
+        .await?;
+
+    Ok(())
+}
+
+/// Reads a webhook's most recent delivery attempts, newest first
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached or an entry is invalid.
+#[instrument(skip(con))]
+pub async fn get_webhook_log(
+    con: &mut redis::aio::MultiplexedConnection,
+    id: u64,
+) -> Result<Vec<WebhookLogEntry>, Error> {
+    let entries: Vec<String> = redis::cmd("LRANGE")
+        .arg(format!("webhook-log:{id}"))
+        .arg(0)
+        .arg(-1)
+        .query_async(con)
+        .await?;
+
+    let mut log = Vec::new();
+    for entry in entries {
+        let item: WebhookLogEntry = serde_json::from_str(&entry)?;
+        log.push(item);
+    }
+
+    Ok(log)
+}
+
+/// Queues an event for every webhook subscribed to it, the webhook worker sends them
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con, data))]
+pub async fn fire_webhook_event(
+    con: &mut redis::aio::MultiplexedConnection,
+    event: WebhookEvent,
+    guild_id: Option<u64>,
+    data: serde_json::Value,
+) -> Result<(), Error> {
+    fire_webhook_events(con, event, vec![(guild_id, data)]).await
+}
+
+/// Same as [`fire_webhook_event`] for a batch of events of one kind, webhooks are only read once
+///
+/// # Errors
+///
+/// This function will return an error if the DB can't be reached.
+#[instrument(skip(con, events))]
+pub async fn fire_webhook_events(
+    con: &mut redis::aio::MultiplexedConnection,
+    event: WebhookEvent,
+    events: Vec<(Option<u64>, serde_json::Value)>,
+) -> Result<(), Error> {
+    let webhooks = get_all_webhooks(con).await?;
+
+    if webhooks.is_empty() {
+        return Ok(());
+    }
+
+    let sent_at = chrono::Utc::now().timestamp();
+    let mut pipe = redis::pipe();
+    let mut queued = false;
+
+    for (guild_id, data) in events {
+        let guild_id = guild_id.map(|id| id.to_string());
+
+        let payload = WebhookPayload {
+            event,
+            guild_id: guild_id.clone(),
+            sent_at,
+            data,
+        };
+
+        for webhook in webhooks
+            .iter()
+            .filter(|webhook| webhook_matches(webhook, event, guild_id.as_deref()))
+        {
+            queue_delivery(
+                &mut pipe,
+                &WebhookDelivery {
+                    id: generate_webhook_secret(),
+                    webhook_id: webhook.id,
+                    payload: payload.clone(),
+                    attempts: 0,
+                },
+                sent_at,
+            )?;
+            queued = true;
+        }
+    }
+
+    if queued {
+        pipe.atomic().query_async::<_, ()>(con).await?;
+    }
+
+    Ok(())
+}
+
+/// Adds a delivery to the queue, to be sent at `send_at`
+fn queue_delivery(
+    pipe: &mut redis::Pipeline,
+    delivery: &WebhookDelivery,
+    send_at: i64,
+) -> Result<(), Error> {
+    pipe.cmd("ZADD")
+        .arg("webhook-queue")
+        .arg(send_at)
+        .arg(serde_json::to_string(delivery)?)
+        .ignore();
+
+    Ok(())
+}
+
+/// Whether an address is out on the internet, rather than the bot's own machine, its network or a cloud metadata service
+#[must_use]
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // Carrier-grade NAT, benchmarking and reserved ranges
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 198 && (18..20).contains(&b))
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(mapped));
+            }
+
+            let first = ip.segments()[0];
+
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local, link-local and documentation ranges
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || first == 0x2001 && ip.segments()[1] == 0x0db8)
+        }
+    }
+}
+
+/// Checks a webhook URL can be posted to and works out where it points
+///
+/// Only `https://` is allowed unless the webhook is global, and every address the host resolves to has to be public so webhooks can't be pointed at the bot's own network.
+/// Returns the host and the addresses to connect to so they can't change between checking and sending.
+///
+/// # Errors
+///
+/// This function will return why the URL isn't allowed.
+pub async fn resolve_webhook_url(
+    url: &str,
+    global: bool,
+) -> Result<(String, Vec<SocketAddr>), String> {
+    let url = reqwest::Url::parse(url).map_err(|_| "That isn't a valid URL.".to_string())?;
+
+    if global && !matches!(url.scheme(), "https" | "http") {
+        return Err("The URL needs to start with `https://` or `http://`.".to_string());
+    }
+
+    if !global && url.scheme() != "https" {
+        return Err("The URL needs to start with `https://`.".to_string());
+    }
+
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return Err("The URL needs a host.".to_string());
+    };
+    let host = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|_| format!("Couldn't look up `{host}`."))?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(format!("Couldn't look up `{host}`."));
+    }
+
+    if addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+        return Err(format!(
+            "`{host}` points somewhere private, webhooks can only be sent to public addresses."
+        ));
+    }
+
+    Ok((host, addrs))
+}
+
+/// POSTs a payload to a webhook, returns the status code if the webhook answered
+///
+/// The URL is checked with [`resolve_webhook_url`] again first and redirects aren't followed, so a webhook can't be moved onto the bot's own network after it was added.
+///
+/// # Errors
+///
+/// This function will return an error if the webhook's URL isn't allowed, it couldn't be reached or didn't answer with a 2xx status.
+pub async fn send_webhook(
+    webhook: &Webhook,
+    payload: &WebhookPayload,
+) -> Result<u16, (Option<u16>, String)> {
+    let (host, addrs) = resolve_webhook_url(&webhook.url, webhook.guild_id.is_none())
+        .await
+        .map_err(|error| (None, error))?;
+
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve_to_addrs(&host, &addrs)
+        .build()
+        .map_err(|error| (None, error.to_string()))?;
+
+    let body = serde_json::to_vec(payload).map_err(|error| (None, error.to_string()))?;
+
+    let response = client
+        .post(&webhook.url)
+        .header(WEBHOOK_SIGNATURE_HEADER, sign_body(&webhook.secret, &body))
+        .header(
+            WEBHOOK_EVENT_HEADER,
+            serde_json::to_string(&payload.event)
+                .unwrap_or_default()
+                .trim_matches('"'),
+        )
+        .header("content-type", "application/json")
+        .timeout(Duration::from_secs(10))
+        .body(body)
+        .send()
+        .await
+        .map_err(|error| (None, error.to_string()))?;
+
+    let status = response.status();
+
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        Err((Some(status.as_u16()), format!("Answered with {status}")))
+    }
+}
+
+/// Sends every delivery that's due, requeueing failures with backoff and logging every attempt
+async fn send_due_deliveries() -> Result<(), Error> {
+    let mut con = open_redis_connection().await?;
+    let now = chrono::Utc::now().timestamp();
+
+    let due: Vec<String> = redis::cmd("ZRANGEBYSCORE")
+        .arg("webhook-queue")
+        .arg("-inf")
+        .arg(now)
+        .arg("LIMIT")
+        .arg(0)
+        .arg(50)
+        .query_async(&mut con)
+        .await?;
+
+    for raw in due {
+        // Only whoever removes it gets to send it
+        let removed: u32 = redis::cmd("ZREM")
+            .arg("webhook-queue")
+            .arg(&raw)
+            .query_async(&mut con)
+            .await?;
+
+        if removed == 0 {
+            continue;
+        }
+
+        let mut delivery: WebhookDelivery = match serde_json::from_str(&raw) {
+            Ok(delivery) => delivery,
+            Err(error) => {
+                event!(Level::WARN, "Dropped invalid webhook delivery" = raw, error = ?error);
+                continue;
+            }
+        };
+
+        let Some(webhook) = get_webhook(&mut con, delivery.webhook_id).await? else {
+            continue;
+        };
+
+        delivery.attempts += 1;
+        let now = chrono::Utc::now().timestamp();
+
+        let mut entry = WebhookLogEntry {
+            event: delivery.payload.event,
+            attempt: delivery.attempts,
+            at: now,
+            status: None,
+            error: None,
+            retry_at: None,
+        };
+
+        let mut pipe = redis::pipe();
+
+        match send_webhook(&webhook, &delivery.payload).await {
+            Ok(status) => entry.status = Some(status),
+            Err((status, error)) => {
+                entry.status = status;
+                entry.error = Some(error);
+                entry.retry_at = retry_delay(delivery.attempts).map(|delay| now + delay);
+
+                if let Some(retry_at) = entry.retry_at {
+                    queue_delivery(&mut pipe, &delivery, retry_at)?;
+                }
+            }
+        }
+
+        let log = format!("webhook-log:{}", webhook.id);
+        pipe.cmd("LPUSH")
+            .arg(&log)
+            .arg(serde_json::to_string(&entry)?)
+            .ignore()
+            .cmd("LTRIM")
+            .arg(&log)
+            .arg(0)
+            .arg(WEBHOOK_LOG_LENGTH - 1)
+            .ignore();
+
+        pipe.atomic().query_async::<_, ()>(&mut con).await?;
+    }
+
+    Ok(())
+}
+
+/// Works through the webhook queue forever
+///
+/// Spawn this once when the bot starts.
+pub async fn run_webhook_worker() {
+    loop {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+
+        if let Err(error) = send_due_deliveries().await {
+            event!(Level::WARN, "Failed to send webhooks" = ?error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod webhooks_tests {
+    use super::*;
+
+    fn webhook(guild_id: Option<&str>, events: Vec<WebhookEvent>) -> Webhook {
+        Webhook {
+            id: 1,
+            url: "https://example.com".to_string(),
+            secret: "secret".to_string(),
+            guild_id: guild_id.map(str::to_string),
+            events,
+            created_by: "1".to_string(),
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn webhook_matches_test() {
+        let global = webhook(None, vec![]);
+        assert!(webhook_matches(&global, WebhookEvent::AltKicked, Some("1")));
+        assert!(webhook_matches(&global, WebhookEvent::UserCleared, None));
+
+        let guild = webhook(Some("1"), vec![WebhookEvent::OffenceAdded]);
+        assert!(webhook_matches(
+            &guild,
+            WebhookEvent::OffenceAdded,
+            Some("1")
+        ));
+        assert!(!webhook_matches(
+            &guild,
+            WebhookEvent::OffenceAdded,
+            Some("2")
+        ));
+        assert!(!webhook_matches(&guild, WebhookEvent::OffenceAdded, None));
+        assert!(!webhook_matches(&guild, WebhookEvent::AltKicked, Some("1")));
+    }
+
+    #[test]
+    fn retry_delay_test() {
+        assert_eq!(retry_delay(1), Some(WEBHOOK_RETRY_BASE_SECS));
+        assert_eq!(retry_delay(3), Some(WEBHOOK_RETRY_BASE_SECS * 4));
+        assert_eq!(retry_delay(WEBHOOK_MAX_ATTEMPTS), None);
+    }
+
+    #[test]
+    fn is_public_ip_test() {
+        for ip in ["1.1.1.1", "93.184.216.34", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00:ec2::254",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+}
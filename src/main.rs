@@ -20,29 +20,62 @@ use tracing::metadata::LevelFilter;
 use tracing::{event, Level};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
-#[macro_use]
-extern crate maplit;
-
+#[cfg(feature = "database")]
+use rusted_fbt_lib::announcements::run_announcement_scheduler;
+#[cfg(feature = "api")]
+use rusted_fbt_lib::api::run_api_server;
+#[cfg(feature = "database")]
+use rusted_fbt_lib::botban::{bot_ban_notice, get_bot_ban};
+#[cfg(feature = "database")]
+use rusted_fbt_lib::expiry::run_offence_expiry_worker;
+#[cfg(feature = "federation")]
+use rusted_fbt_lib::federation::{run_federation_server, run_federation_worker};
+#[cfg(feature = "database")]
+use rusted_fbt_lib::feedback::{handle_feedback_button, FEEDBACK_BUTTON_PREFIX};
+#[cfg(feature = "database")]
+use rusted_fbt_lib::onboarding::{handle_onboarding_button, ONBOARDING_BUTTON_PREFIX};
+#[cfg(feature = "database")]
+use rusted_fbt_lib::search::run_search_sync_worker;
+#[cfg(feature = "database")]
+use rusted_fbt_lib::staff::seed_legacy_admins;
 #[cfg(feature = "database")]
 use rusted_fbt_lib::utils::open_redis_connection;
-use rusted_fbt_lib::vars::FBT_GUILD_ID;
+use rusted_fbt_lib::vars::{BOT_OWNERS, FBT_GUILD_ID};
+#[cfg(feature = "database")]
+use rusted_fbt_lib::webhooks::run_webhook_worker;
 // Import everything from the commands folder
 mod commands;
 use commands::admin::{
-    announcement, authorize, ban, botmsg, request_setup, setup, shutdown, toggle_kick,
+    announcement, auth, authorize, ban, botmsg, deauthorize, request_setup, risk_policy, settings,
+    setup, shutdown, toggle_announcements, toggle_kick,
 };
+#[cfg(feature = "api")]
+use commands::api::api_key;
+#[cfg(feature = "database")]
+use commands::botban::botban;
 #[cfg(feature = "database")]
 use commands::database::{
-    add, excel, footprint_lookup, key, search, update_search_engine, whitelist,
+    add, backup, clear, excel, footprint_lookup, key, migrate_vrchat_ids, purge_evidence_file,
+    reconcile_search_engine, search, settings_audit, update_search_engine, whitelist,
 };
+#[cfg(feature = "federation")]
+use commands::federation::federation;
 use commands::fun::{cringe, owo, ping, pog, toxic, waifu};
+#[cfg(feature = "database")]
+use commands::identity::identity;
 use commands::info::{about, feedback, help};
+#[cfg(feature = "database")]
+use commands::moderation::{case, kick, softban, timeout, unban, warn};
+#[cfg(feature = "database")]
+use commands::staff::staff;
 use commands::tickets::{close_ticket, new_ticket};
 use commands::tools::{account_age, bot_owner_tool_1, creation_date};
+#[cfg(feature = "database")]
+use commands::webhooks::webhook;
 
 // New rust librabry to never leave this reposity :D
 use rusted_fbt_lib::args::Args;
-use rusted_fbt_lib::checks::bot_admin_check;
+use rusted_fbt_lib::checks::{bot_admin_check, owner_check};
 use rusted_fbt_lib::enums::{DebugLevel, LogDebugLevel};
 use rusted_fbt_lib::event_handlers::{
     alt_kicker, bl_warner, handle_dms, handle_msg_delete, handle_msg_edit, handle_resume,
@@ -58,7 +91,7 @@ use crate::commands::tools::invite_info;
 ///
 /// Run with no arguments to register in guild, run with argument "global" to register globally.
 #[instrument(skip(ctx))]
-#[poise::command(prefix_command, slash_command, hide_in_help, owners_only)]
+#[poise::command(prefix_command, slash_command, hide_in_help, check = "owner_check")]
 async fn register(ctx: Context<'_>) -> Result<(), Error> {
     register_application_commands_buttons(ctx).await?;
     event!(Level::INFO, "Commandwhere registered");
@@ -89,7 +122,7 @@ async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
         poise::FrameworkError::CommandCheckFailed { error, ctx } => {
             if ctx.command().name.as_str() == "setup" {
                 ctx.send(|m| {
-                        m.content("If you can't run this because you don't have the correct permissions then please ask a local admin to run `/request_setup` or `/authorize`!\nAn admin will come and check out your server ASAP after `/request_setup` is executed.")
+                        m.content("If you can't run this because you don't have the correct permissions then please ask a local admin to run `/request_setup` or `/authorize`!\nFBT staff will look at your server ASAP after `/request_setup` is executed.")
                         .ephemeral(true)
                     }).await
                     .expect("Failed to tell user about request_setup during error handeling");
@@ -151,6 +184,18 @@ async fn event_listener(
             // TODO: Store and get from DB so we can change it later and keep it consistent between boots
             ctx.set_presence(Some(activity), status).await;
         }
+        #[cfg(feature = "database")]
+        poise::Event::InteractionCreate {
+            interaction: serenity::Interaction::MessageComponent(press),
+        } if press.data.custom_id.starts_with(FEEDBACK_BUTTON_PREFIX) => {
+            handle_feedback_button(ctx, press).await?;
+        }
+        #[cfg(feature = "database")]
+        poise::Event::InteractionCreate {
+            interaction: serenity::Interaction::MessageComponent(press),
+        } if press.data.custom_id.starts_with(ONBOARDING_BUTTON_PREFIX) => {
+            handle_onboarding_button(ctx, press).await?;
+        }
         poise::Event::Resume { event } => {
             handle_resume(event);
         }
@@ -341,13 +386,15 @@ async fn main() {
         .with(info_file_layer)
         .init();
 
-    // TODO: Like bot admins, put your own IDs here
+    #[cfg(feature = "database")]
+    if let Some(command) = args.command {
+        if let Err(error) = rusted_fbt_lib::backup::run_backup_cli(command).await {
+            event!(Level::ERROR, "Backup command failed" = %error);
+        }
+        return;
+    }
 
-    let bot_owners: HashSet<UserId> = hashset! {
-        UserId::from(212_132_817_017_110_528), // Azuki
-        UserId::from(164_694_510_947_794_944), // Cross
-        UserId::from(383_507_911_160_233_985), // Mojo
-    };
+    let bot_owners: HashSet<UserId> = BOT_OWNERS.into_iter().map(UserId::from).collect();
 
     // * This is where we put the functions that we want in discord
     #[allow(unused_mut)]
@@ -378,6 +425,8 @@ async fn main() {
             add(),
             announcement(),
             authorize(),
+            deauthorize(),
+            auth(),
             footprint_lookup(),
             excel(),
             feedback(),
@@ -388,14 +437,41 @@ async fn main() {
             setup(),
             // sqlite_transfer(), // Deprecated
             toggle_kick(),
+            toggle_announcements(),
+            risk_policy(),
+            settings(),
             update_search_engine(),
+            reconcile_search_engine(),
+            migrate_vrchat_ids(),
+            settings_audit(),
+            backup(),
+            clear(),
+            purge_evidence_file(),
+            identity(),
+            staff(),
+            botban(),
+            webhook(),
             key(),
             invite_info(),
+            kick(),
+            timeout(),
+            unban(),
+            warn(),
+            softban(),
+            case(),
         ];
 
         discord_commands.append(&mut db_vec);
     }
 
+    // * Partner HTTP API
+    #[cfg(feature = "api")]
+    discord_commands.push(api_key());
+
+    // * Federation with other instances of the bot
+    #[cfg(feature = "federation")]
+    discord_commands.push(federation());
+
     // * Any command that are not complete/working here
     #[cfg(feature = "beta")]
     {
@@ -421,27 +497,30 @@ async fn main() {
             #[cfg(feature = "database")]
             command_check: Some(|ctx| {
                 Box::pin(async move {
-                    if bot_admin_check(ctx).await.unwrap() {
+                    if bot_admin_check(ctx).await? {
                         return Ok(true);
                     }
 
                     let mut con = open_redis_connection().await?;
 
-                    let key_list: HashSet<String> = redis::cmd("SMEMBERS")
-                        .arg("user-lists:banned-from-bot")
-                        .clone()
-                        .query_async(&mut con)
-                        .await?;
-
-                    if key_list.contains(&format!("{}", ctx.author().id.as_u64())) {
-                        Ok({
-                            println!(
-                                "{}/{} was blocked from using the bot",
-                                ctx.author().id,
-                                ctx.author().name
-                            );
-                            false
-                        })
+                    let ban = get_bot_ban(
+                        &mut con,
+                        *ctx.author().id.as_u64(),
+                        ctx.guild_id().map(|guild_id| *guild_id.as_u64()),
+                    )
+                    .await?;
+
+                    if let Some(ban) = ban {
+                        println!(
+                            "{}/{} was blocked from using the bot",
+                            ctx.author().id,
+                            ctx.author().name
+                        );
+
+                        ctx.send(|b| b.content(bot_ban_notice(&ban)).ephemeral(true))
+                            .await?;
+
+                        Ok(false)
                     } else {
                         Ok(true)
                     }
@@ -457,7 +536,45 @@ async fn main() {
         })
         .token(args.token)
         .intents(serenity::GatewayIntents::all())
-        .setup(move |_ctx, _ready, _framework| Box::pin(async move { Ok(Data {}) }))
+        .setup(move |ctx, _ready, _framework| {
+            Box::pin(async move {
+                #[cfg(feature = "database")]
+                {
+                    let seeded: Result<bool, Error> =
+                        async { seed_legacy_admins(&mut open_redis_connection().await?).await }
+                            .await;
+
+                    if let Err(error) = seeded {
+                        event!(Level::ERROR, "Failed to seed staff tiers" = %error);
+                    }
+                }
+                #[cfg(feature = "database")]
+                tokio::spawn(run_announcement_scheduler(ctx.http.clone()));
+                #[cfg(feature = "database")]
+                tokio::spawn(run_search_sync_worker());
+                #[cfg(feature = "database")]
+                tokio::spawn(run_webhook_worker());
+                #[cfg(feature = "database")]
+                tokio::spawn(run_offence_expiry_worker());
+                #[cfg(feature = "api")]
+                tokio::spawn(async {
+                    if let Err(error) = run_api_server().await {
+                        event!(Level::ERROR, "API server stopped" = ?error);
+                    }
+                });
+                #[cfg(feature = "federation")]
+                {
+                    tokio::spawn(run_federation_worker());
+                    tokio::spawn(async {
+                        if let Err(error) = run_federation_server().await {
+                            event!(Level::ERROR, "Federation server stopped" = ?error);
+                        }
+                    });
+                }
+
+                Ok(Data {})
+            })
+        })
         .client_settings(|f| f.cache_settings(|cs| cs.max_messages(5_000)));
 
     framework.run_autosharded().await.unwrap();